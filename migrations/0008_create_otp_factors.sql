@@ -0,0 +1,7 @@
+-- Segundo factor por código de un solo uso; como mucho uno por usuario
+CREATE TABLE IF NOT EXISTS otp_factors (
+    user_id BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    channel VARCHAR(16) NOT NULL CHECK (channel IN ('email', 'sms')),
+    destination VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
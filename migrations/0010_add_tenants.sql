@@ -0,0 +1,21 @@
+-- Cada marca (tenant) tiene sus propios usuarios; los datos existentes quedan en 'default'.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+
+-- El correo pasa a ser único por tenant
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_email_key;
+CREATE UNIQUE INDEX IF NOT EXISTS users_tenant_email_idx ON users (tenant_id, email);
+
+-- Una misma cuenta externa puede vincularse a un usuario de cada tenant
+ALTER TABLE user_identities ADD COLUMN IF NOT EXISTS tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+ALTER TABLE user_identities DROP CONSTRAINT IF EXISTS user_identities_provider_subject_key;
+CREATE UNIQUE INDEX IF NOT EXISTS user_identities_tenant_subject_idx
+    ON user_identities (tenant_id, provider, subject);
+
+ALTER TABLE organizations ADD COLUMN IF NOT EXISTS tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+CREATE INDEX IF NOT EXISTS organizations_tenant_id_idx ON organizations (tenant_id);
+
+-- Los administradores solo ven los eventos y webhooks de su tenant
+ALTER TABLE auth_events ADD COLUMN IF NOT EXISTS tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+CREATE INDEX IF NOT EXISTS auth_events_tenant_created_at_idx ON auth_events (tenant_id, created_at DESC);
+
+ALTER TABLE webhook_endpoints ADD COLUMN IF NOT EXISTS tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
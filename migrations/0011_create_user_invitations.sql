@@ -0,0 +1,13 @@
+-- Invitaciones de administradores: el usuario existe (sin contraseña utilizable)
+-- hasta que acepta y elige su nombre y contraseña.
+CREATE TABLE IF NOT EXISTS user_invitations (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    invited_by BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    accepted_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS user_invitations_user_id_idx ON user_invitations (user_id);
@@ -0,0 +1,4 @@
+-- Un teléfono verificado pertenece a una sola cuenta del tenant (SMS-OTP y recuperación)
+CREATE UNIQUE INDEX IF NOT EXISTS users_tenant_verified_phone_idx
+    ON users (tenant_id, phone_number)
+    WHERE phone_verified_at IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Avatares subidos: prefijo de sus ficheros en el BlobStore y URL de cada miniatura
+ALTER TABLE users ADD COLUMN IF NOT EXISTS avatar_key TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS avatar_thumbnails JSONB;
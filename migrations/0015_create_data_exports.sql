@@ -0,0 +1,19 @@
+-- Exportaciones de datos personales (RGPD): el archivo se genera en segundo
+-- plano y se guarda en el BlobStore hasta que caduca.
+CREATE TABLE IF NOT EXISTS data_exports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id VARCHAR(64) NOT NULL,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'ready', 'failed')),
+    blob_key TEXT,
+    size_bytes BIGINT,
+    expires_at TIMESTAMPTZ NOT NULL,
+    completed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS data_exports_user_id_idx ON data_exports (user_id, created_at DESC);
+
+-- Como mucho una exportación en curso por usuario
+CREATE UNIQUE INDEX IF NOT EXISTS data_exports_user_pending_idx ON data_exports (user_id)
+    WHERE status = 'pending';
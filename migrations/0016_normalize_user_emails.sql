@@ -0,0 +1,10 @@
+-- Los correos se guardan normalizados (sin espacios y en minúsculas) para que
+-- el login, el registro y los proveedores externos comparen igual.
+--
+-- Si el UPDATE falla por el índice único users_tenant_email_idx, hay cuentas
+-- del mismo tenant que solo difieren en mayúsculas: hay que fusionarlas o
+-- renombrarlas a mano antes de volver a aplicar la migración.
+UPDATE users SET email = lower(btrim(email)) WHERE email <> lower(btrim(email));
+
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_email_normalized;
+ALTER TABLE users ADD CONSTRAINT users_email_normalized CHECK (email = lower(btrim(email)));
@@ -0,0 +1,8 @@
+-- Las exportaciones pendientes forman una cola: un proceso por tenant las
+-- reserva durante un tiempo (`locked_until`) y, si el proceso cae a mitad,
+-- otro las retoma cuando vence la reserva, hasta agotar los intentos.
+ALTER TABLE data_exports ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE data_exports ADD COLUMN IF NOT EXISTS locked_until TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS data_exports_pending_idx ON data_exports (tenant_id, created_at)
+    WHERE status = 'pending';
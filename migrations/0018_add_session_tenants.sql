@@ -0,0 +1,8 @@
+-- Las sesiones de Postgres quedan en el tenant de su usuario, igual que las
+-- de Redis bajo su prefijo.
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+UPDATE sessions s SET tenant_id = u.tenant_id FROM users u
+    WHERE u.id = s.user_id AND s.tenant_id <> u.tenant_id;
+
+DROP INDEX IF EXISTS sessions_user_id_idx;
+CREATE INDEX IF NOT EXISTS sessions_tenant_user_id_idx ON sessions (tenant_id, user_id);
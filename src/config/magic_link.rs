@@ -0,0 +1,27 @@
+use crate::config::tenants::Tenant;
+
+/// Parámetros del login por enlace mágico.
+#[derive(Debug, Clone)]
+pub struct MagicLinkSettings {
+    /// Página del frontend que recibe `?token=...` y llama a `/auth/magic-link/verify`.
+    pub url: String,
+    pub ttl_seconds: i64,
+    /// Cookie que ata el enlace al navegador que lo pidió.
+    pub nonce_cookie: String,
+}
+
+pub fn load_settings(tenant: &Tenant) -> MagicLinkSettings {
+    MagicLinkSettings {
+        url: tenant
+            .env("MAGIC_LINK_URL")
+            .unwrap_or_else(|| "http://localhost:3000/auth/magic-link".to_string()),
+        ttl_seconds: tenant
+            .env("MAGIC_LINK_TTL_SECONDS")
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &i64| *v > 0)
+            .unwrap_or(900),
+        nonce_cookie: tenant
+            .env("MAGIC_LINK_NONCE_COOKIE")
+            .unwrap_or_else(|| "magic_link_nonce".to_string()),
+    }
+}
@@ -1,2 +1,14 @@
 pub mod database;
-pub mod redis; 
\ No newline at end of file
+pub mod magic_link;
+pub mod oauth;
+pub mod oidc;
+pub mod organizations;
+pub mod otp;
+pub mod password;
+pub mod profile;
+pub mod proxies;
+pub mod redis;
+pub mod registration;
+pub mod session;
+pub mod tenants;
+pub mod webhooks;
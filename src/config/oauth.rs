@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+
+use crate::config::tenants::Tenant;
+
+/// Clientes autorizados a usar `/oauth/introspect` y `/oauth/revoke` de un tenant.
+#[derive(Debug, Clone, Default)]
+pub struct OAuthClients {
+    clients: HashMap<String, String>,
+}
+
+impl OAuthClients {
+    pub fn authenticate(&self, client_id: &str, client_secret: &str) -> bool {
+        match self.clients.get(client_id) {
+            Some(secret) => secret.as_bytes().ct_eq(client_secret.as_bytes()).into(),
+            None => false,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn for_tests(clients: &[(&str, &str)]) -> Self {
+        OAuthClients {
+            clients: clients.iter().map(|(id, secret)| (id.to_string(), secret.to_string())).collect(),
+        }
+    }
+}
+
+/// Carga `OAUTH_CLIENTS` con el formato `id:secreto,id2:secreto2`.
+pub fn load_clients(tenant: &Tenant) -> OAuthClients {
+    let raw = tenant.env("OAUTH_CLIENTS").unwrap_or_default();
+    let clients = raw
+        .split(',')
+        .filter_map(|entry| entry.trim().split_once(':'))
+        .map(|(id, secret)| (id.to_string(), secret.to_string()))
+        .collect::<HashMap<_, _>>();
+
+    log::info!("{} OAuth client(s) configured for tenant {}", clients.len(), tenant.id);
+    OAuthClients { clients }
+}
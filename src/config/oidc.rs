@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use crate::config::tenants::Tenant;
+
+/// Cómo se valida la identidad que devuelve el proveedor.
+#[derive(Debug, Clone)]
+pub enum ProviderProtocol {
+    /// El token endpoint debe devolver un `id_token` firmado con una clave
+    /// de `jwks_url` y emitido por `issuer` para nuestro `client_id`.
+    Oidc { issuer: String, jwks_url: String },
+    /// OAuth2 sin `id_token` (p. ej. GitHub): la identidad sale solo del
+    /// endpoint de userinfo.
+    OAuth2,
+}
+
+/// Configuración de un proveedor OIDC/OAuth2 externo.
+#[derive(Debug, Clone)]
+pub struct OidcProvider {
+    pub name: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub authorize_url: String,
+    pub token_url: String,
+    pub userinfo_url: String,
+    pub redirect_uri: String,
+    pub protocol: ProviderProtocol,
+    pub scopes: String,
+    pub subject_claim: String,
+    pub email_claim: String,
+    pub name_claim: String,
+    /// Para proveedores OAuth2 que solo devuelven correos ya verificados
+    /// (p. ej. el correo primario de GitHub) y no envían `email_verified`.
+    pub trust_email: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OidcProviders {
+    providers: HashMap<String, OidcProvider>,
+}
+
+impl OidcProviders {
+    pub fn get(&self, name: &str) -> Option<&OidcProvider> {
+        self.providers.get(name)
+    }
+
+    #[cfg(test)]
+    pub fn for_tests(providers: Vec<OidcProvider>) -> Self {
+        OidcProviders {
+            providers: providers.into_iter().map(|p| (p.name.clone(), p)).collect(),
+        }
+    }
+}
+
+/// Carga los proveedores listados en `OIDC_PROVIDERS` (separados por comas).
+///
+/// Cada proveedor `x` se configura con variables `OIDC_X_*`, por ejemplo
+/// `OIDC_GOOGLE_CLIENT_ID` o `OIDC_GITHUB_TOKEN_URL`, con el prefijo del
+/// tenant si lo tiene (`TENANT_X_OIDC_GOOGLE_CLIENT_ID`). Salvo con
+/// `OIDC_X_PROTOCOL=oauth2`, `OIDC_X_ISSUER` y `OIDC_X_JWKS_URL` son obligatorias.
+pub fn load_providers(tenant: &Tenant) -> Result<OidcProviders, String> {
+    let names = tenant.env("OIDC_PROVIDERS").unwrap_or_default();
+    let mut providers = HashMap::new();
+
+    for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let name = name.to_lowercase();
+        let prefix = format!("OIDC_{}_", name.to_uppercase());
+        let required = |key: &str| {
+            tenant
+                .env(&format!("{}{}", prefix, key))
+                .ok_or_else(|| format!("{}{} must be set", prefix, key))
+        };
+        let optional = |key: &str, default: &str| {
+            tenant
+                .env(&format!("{}{}", prefix, key))
+                .unwrap_or_else(|| default.to_string())
+        };
+
+        let protocol = match optional("PROTOCOL", "oidc").to_lowercase().as_str() {
+            "oauth2" => ProviderProtocol::OAuth2,
+            "oidc" => ProviderProtocol::Oidc {
+                issuer: required("ISSUER")?,
+                jwks_url: required("JWKS_URL")?,
+            },
+            other => return Err(format!("{}PROTOCOL must be oidc or oauth2, got {}", prefix, other)),
+        };
+
+        let provider = OidcProvider {
+            name: name.clone(),
+            client_id: required("CLIENT_ID")?,
+            client_secret: required("CLIENT_SECRET")?,
+            authorize_url: required("AUTHORIZE_URL")?,
+            token_url: required("TOKEN_URL")?,
+            userinfo_url: required("USERINFO_URL")?,
+            redirect_uri: required("REDIRECT_URI")?,
+            protocol,
+            scopes: optional("SCOPES", "openid email profile"),
+            subject_claim: optional("SUBJECT_CLAIM", "sub"),
+            email_claim: optional("EMAIL_CLAIM", "email"),
+            name_claim: optional("NAME_CLAIM", "name"),
+            trust_email: optional("TRUST_EMAIL", "false") == "true",
+        };
+
+        log::info!("OIDC provider configured for tenant {}: {}", tenant.id, name);
+        providers.insert(name, provider);
+    }
+
+    Ok(OidcProviders { providers })
+}
@@ -0,0 +1,26 @@
+use chrono::Duration;
+
+use crate::config::tenants::Tenant;
+
+/// Parámetros de las invitaciones a organizaciones.
+#[derive(Debug, Clone)]
+pub struct InvitationSettings {
+    /// Página del frontend que recibe `?token=...` y llama a `/organizations/invitations/accept`.
+    pub url: String,
+    pub ttl: Duration,
+}
+
+pub fn load_settings(tenant: &Tenant) -> InvitationSettings {
+    InvitationSettings {
+        url: tenant
+            .env("ORG_INVITATION_URL")
+            .unwrap_or_else(|| "http://localhost:3000/invitations".to_string()),
+        ttl: Duration::hours(
+            tenant
+                .env("ORG_INVITATION_TTL_HOURS")
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &i64| *v > 0)
+                .unwrap_or(72),
+        ),
+    }
+}
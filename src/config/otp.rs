@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use crate::config::tenants::Tenant;
+
+/// Parámetros de los códigos de un solo uso (segundo factor).
+#[derive(Debug, Clone)]
+pub struct OtpSettings {
+    pub code_length: usize,
+    pub ttl_seconds: u64,
+    /// Intentos fallidos antes de invalidar el código.
+    pub max_attempts: u32,
+    /// Envíos por usuario dentro de `send_window_seconds`.
+    pub max_sends: u32,
+    pub send_window_seconds: u64,
+    /// Pasarela SMS: recibe `POST {"to": ..., "message": ...}`.
+    pub sms_gateway_url: Option<String>,
+    pub sms_gateway_token: Option<String>,
+    /// Si está definido, los códigos de todos los canales se escriben aquí
+    /// en lugar de enviarse; para pruebas automatizadas.
+    pub capture_file: Option<PathBuf>,
+}
+
+fn env_or<T: std::str::FromStr>(tenant: &Tenant, key: &str, default: T) -> T {
+    tenant
+        .env(key)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+pub fn load_settings(tenant: &Tenant) -> OtpSettings {
+    OtpSettings {
+        code_length: env_or(tenant, "OTP_CODE_LENGTH", 6usize).clamp(6, 10),
+        ttl_seconds: env_or(tenant, "OTP_TTL_SECONDS", 300),
+        max_attempts: env_or(tenant, "OTP_MAX_ATTEMPTS", 5),
+        max_sends: env_or(tenant, "OTP_MAX_SENDS", 5),
+        send_window_seconds: env_or(tenant, "OTP_SEND_WINDOW_SECONDS", 900),
+        sms_gateway_url: tenant.env("SMS_GATEWAY_URL"),
+        sms_gateway_token: tenant.env("SMS_GATEWAY_TOKEN"),
+        capture_file: tenant.env("OTP_CAPTURE_FILE").map(PathBuf::from),
+    }
+}
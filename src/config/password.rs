@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use crate::config::tenants::Tenant;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasswordAlgorithm {
+    Argon2id,
+    Bcrypt,
+}
+
+/// Algoritmo y parámetros para los hashes nuevos.
+///
+/// Los hashes guardados con otro algoritmo o parámetros se siguen
+/// verificando y se rehashean en el siguiente login correcto.
+#[derive(Debug, Clone)]
+pub struct PasswordSettings {
+    pub algorithm: PasswordAlgorithm,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    pub bcrypt_cost: u32,
+}
+
+fn env_or<T: std::str::FromStr>(tenant: &Tenant, key: &str, default: T) -> T {
+    tenant
+        .env(key)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+pub fn load_settings(tenant: &Tenant) -> PasswordSettings {
+    let algorithm = match tenant
+        .env("PASSWORD_HASH_ALGORITHM")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "bcrypt" => PasswordAlgorithm::Bcrypt,
+        _ => PasswordAlgorithm::Argon2id,
+    };
+
+    // Valores por defecto: recomendación mínima de OWASP para Argon2id
+    PasswordSettings {
+        algorithm,
+        argon2_memory_kib: env_or(tenant, "ARGON2_MEMORY_KIB", 19 * 1024),
+        argon2_iterations: env_or(tenant, "ARGON2_ITERATIONS", 2),
+        argon2_parallelism: env_or(tenant, "ARGON2_PARALLELISM", 1),
+        bcrypt_cost: env_or(tenant, "BCRYPT_COST", bcrypt::DEFAULT_COST),
+    }
+}
+
+/// Reglas que debe cumplir toda contraseña nueva.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Puntuación mínima de 0 a 4, en la escala de zxcvbn.
+    pub min_score: u8,
+    /// Directorio con el corpus de contraseñas filtradas en formato de rangos
+    /// k-anonymity: un fichero por prefijo SHA-1 de 5 caracteres hexadecimales,
+    /// con líneas `SUFIJO:APARICIONES`. Sin directorio no se comprueba.
+    pub breached_corpus_dir: Option<PathBuf>,
+    /// Cuántas contraseñas anteriores no se pueden reutilizar; 0 lo desactiva.
+    pub history_size: usize,
+    /// Antigüedad máxima antes de exigir un cambio en el login.
+    pub max_age: Option<chrono::Duration>,
+}
+
+pub fn load_policy(tenant: &Tenant) -> PasswordPolicy {
+    let min_length = env_or(tenant, "PASSWORD_MIN_LENGTH", 8);
+
+    PasswordPolicy {
+        min_length,
+        max_length: env_or(tenant, "PASSWORD_MAX_LENGTH", 128).max(min_length),
+        min_score: env_or(tenant, "PASSWORD_MIN_SCORE", 2u8).min(4),
+        breached_corpus_dir: tenant.env("PASSWORD_BREACHED_CORPUS_DIR").map(PathBuf::from),
+        history_size: env_or(tenant, "PASSWORD_HISTORY_SIZE", 5),
+        max_age: tenant
+            .env("PASSWORD_MAX_AGE_DAYS")
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|days| *days > 0)
+            .map(chrono::Duration::days),
+    }
+}
@@ -0,0 +1,142 @@
+use chrono::Duration;
+use jsonschema::JSONSchema;
+use serde_json::json;
+use std::fs;
+use std::sync::Arc;
+
+use crate::config::tenants::Tenant;
+
+/// Reglas del perfil editable, de los avatares y de la exportación de datos.
+#[derive(Debug, Clone)]
+pub struct ProfileSettings {
+    /// JSON Schema de `metadata`; por defecto cualquier objeto.
+    pub metadata_schema: Arc<JSONSchema>,
+    /// Tamaño máximo de `metadata` serializado.
+    pub metadata_max_bytes: usize,
+    /// Tamaño máximo del fichero subido como avatar.
+    pub avatar_max_bytes: usize,
+    /// Lados de las miniaturas cuadradas que se generan.
+    pub avatar_sizes: Vec<u32>,
+    /// Ancho y alto máximos de la imagen original.
+    pub avatar_max_dimension: u32,
+    /// Tiempo que se conserva el archivo de una exportación de datos.
+    pub export_ttl: Duration,
+    /// Intentos de generar una exportación antes de darla por fallida.
+    pub export_max_attempts: i32,
+}
+
+pub fn load_settings(tenant: &Tenant) -> Result<ProfileSettings, String> {
+    let schema = match tenant.env("PROFILE_METADATA_SCHEMA_FILE") {
+        Some(path) => {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Cannot read PROFILE_METADATA_SCHEMA_FILE {}: {}", path, e))?;
+            serde_json::from_str(&contents).map_err(|e| format!("Invalid JSON in {}: {}", path, e))?
+        }
+        None => json!({ "type": "object" }),
+    };
+    let metadata_schema = JSONSchema::compile(&schema)
+        .map_err(|e| format!("Invalid profile metadata schema: {}", e))?;
+
+    let mut avatar_sizes = tenant
+        .env("AVATAR_SIZES")
+        .unwrap_or_else(|| "64,128,256".to_string())
+        .split(',')
+        .map(|size| match size.trim().parse() {
+            Ok(size) if (16..=1024).contains(&size) => Ok(size),
+            _ => Err(format!("Invalid AVATAR_SIZES entry: {}", size)),
+        })
+        .collect::<Result<Vec<u32>, String>>()?;
+    avatar_sizes.sort_unstable();
+    avatar_sizes.dedup();
+
+    Ok(ProfileSettings {
+        metadata_schema: Arc::new(metadata_schema),
+        metadata_max_bytes: tenant
+            .env("PROFILE_METADATA_MAX_BYTES")
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &usize| *v > 0)
+            .unwrap_or(16 * 1024),
+        avatar_max_bytes: tenant
+            .env("AVATAR_MAX_BYTES")
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &usize| *v > 0)
+            .unwrap_or(5 * 1024 * 1024),
+        avatar_sizes,
+        avatar_max_dimension: tenant
+            .env("AVATAR_MAX_DIMENSION")
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u32| *v > 0)
+            .unwrap_or(4096),
+        export_ttl: Duration::hours(
+            tenant
+                .env("DATA_EXPORT_TTL_HOURS")
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &i64| *v > 0)
+                .unwrap_or(7 * 24),
+        ),
+        export_max_attempts: tenant
+            .env("DATA_EXPORT_MAX_ATTEMPTS")
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &i32| *v > 0)
+            .unwrap_or(3),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tenant propio para cada prueba: sus variables no se cruzan con las de otras.
+    fn tenant(id: &str, vars: &[(&str, &str)]) -> Tenant {
+        let tenant = Tenant::for_tests(id, "secret").with_own_env();
+        for (key, value) in vars {
+            std::env::set_var(format!("TENANT_{}_{}", id.to_uppercase().replace('-', "_"), key), value);
+        }
+        tenant
+    }
+
+    fn schema_file(contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("schema-{}.json", uuid::Uuid::new_v4()));
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn metadata_schema_is_loaded_from_the_tenant_file() {
+        let path = schema_file(r#"{"type": "object", "properties": {"theme": {"enum": ["light", "dark"]}}}"#);
+        let settings = load_settings(&tenant("profile-schema", &[("PROFILE_METADATA_SCHEMA_FILE", &path)])).unwrap();
+        assert!(settings.metadata_schema.is_valid(&json!({"theme": "dark"})));
+        assert!(!settings.metadata_schema.is_valid(&json!({"theme": "sepia"})));
+
+        // Sin fichero, cualquier objeto
+        let settings = load_settings(&tenant("profile-default", &[])).unwrap();
+        assert!(settings.metadata_schema.is_valid(&json!({"theme": "sepia"})));
+        assert!(!settings.metadata_schema.is_valid(&json!(["theme"])));
+    }
+
+    #[test]
+    fn unreadable_or_invalid_schemas_are_config_errors() {
+        let missing = tenant("profile-missing", &[("PROFILE_METADATA_SCHEMA_FILE", "/nonexistent/schema.json")]);
+        assert!(load_settings(&missing).unwrap_err().starts_with("Cannot read PROFILE_METADATA_SCHEMA_FILE"));
+
+        let path = schema_file("{ not json");
+        let broken = tenant("profile-broken", &[("PROFILE_METADATA_SCHEMA_FILE", &path)]);
+        assert!(load_settings(&broken).unwrap_err().starts_with("Invalid JSON in"));
+
+        let path = schema_file(r#"{"type": "no-such-type"}"#);
+        let invalid = tenant("profile-invalid", &[("PROFILE_METADATA_SCHEMA_FILE", &path)]);
+        assert!(load_settings(&invalid).unwrap_err().starts_with("Invalid profile metadata schema"));
+    }
+
+    #[test]
+    fn avatar_sizes_are_sorted_deduplicated_and_bounded() {
+        let settings = load_settings(&tenant("avatar-sizes", &[("AVATAR_SIZES", "256, 48,256,96")])).unwrap();
+        assert_eq!(settings.avatar_sizes, [48, 96, 256]);
+        assert_eq!(load_settings(&tenant("avatar-default", &[])).unwrap().avatar_sizes, [64, 128, 256]);
+
+        for (id, sizes) in [("avatar-small", "8,64"), ("avatar-large", "64,2048"), ("avatar-text", "64,big")] {
+            let error = load_settings(&tenant(id, &[("AVATAR_SIZES", sizes)])).unwrap_err();
+            assert!(error.starts_with("Invalid AVATAR_SIZES entry"), "{}", error);
+        }
+    }
+}
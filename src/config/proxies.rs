@@ -0,0 +1,94 @@
+use ipnet::IpNet;
+use std::env;
+use std::net::IpAddr;
+
+/// Proxies inversos cuyas cabeceras `X-Forwarded-For` se creen.
+///
+/// Es común a todos los tenants: depende del despliegue, no de la marca.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.networks.iter().any(|network| network.contains(ip))
+    }
+
+    /// IP del cliente: la del par TCP salvo que sea un proxy de confianza.
+    ///
+    /// En ese caso se recorre `X-Forwarded-For` de derecha a izquierda y se
+    /// devuelve la primera dirección que no es de un proxy de confianza; lo
+    /// que haya a su izquierda lo ha podido escribir el cliente.
+    pub fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>) -> IpAddr {
+        let mut client = peer;
+        if !self.contains(&peer) {
+            return client;
+        }
+
+        let hops = forwarded_for.unwrap_or_default().rsplit(',').map(str::trim);
+        for hop in hops {
+            match hop.parse::<IpAddr>() {
+                Ok(ip) => {
+                    client = ip;
+                    if !self.contains(&ip) {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        client
+    }
+}
+
+/// Lee `TRUSTED_PROXIES`: IPs o rangos CIDR separados por comas.
+pub fn load_trusted_proxies() -> Result<TrustedProxies, String> {
+    let networks = env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("Invalid TRUSTED_PROXIES entry: {}", entry))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(TrustedProxies { networks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxies(networks: &[&str]) -> TrustedProxies {
+        TrustedProxies {
+            networks: networks.iter().map(|n| n.parse().unwrap()).collect(),
+        }
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_headers_from_untrusted_peers_are_ignored() {
+        let proxies = proxies(&["10.0.0.0/8"]);
+        assert_eq!(proxies.client_ip(ip("203.0.113.7"), Some("198.51.100.1")), ip("203.0.113.7"));
+        assert_eq!(TrustedProxies::default().client_ip(ip("10.0.0.1"), Some("198.51.100.1")), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn trusted_hops_are_skipped_from_the_right() {
+        let proxies = proxies(&["10.0.0.0/8"]);
+        // El cliente antepuso una dirección falsa: se toma la que añadió el proxy
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), Some("1.2.3.4, 203.0.113.7, 10.0.0.2")),
+            ip("203.0.113.7")
+        );
+        assert_eq!(proxies.client_ip(ip("10.0.0.1"), None), ip("10.0.0.1"));
+        assert_eq!(proxies.client_ip(ip("10.0.0.1"), Some("garbage, 10.0.0.2")), ip("10.0.0.2"));
+    }
+}
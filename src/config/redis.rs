@@ -1,5 +1,120 @@
-use redis::Client;
+use redis::aio::{ConnectionLike, ConnectionManager};
+use redis::{Client, Cmd, ErrorKind, Pipeline, RedisError, RedisFuture, RedisResult, Value};
+use std::env;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
-pub fn create_redis_client(redis_url: &str) -> Client {
-    Client::open(redis_url).expect("Failed to create Redis client")
-} 
\ No newline at end of file
+/// Conexión multiplexada compartida por todos los workers.
+///
+/// `ConnectionManager` reconecta solo cuando la conexión se pierde; el semáforo
+/// limita las operaciones en vuelo para que una caída de Redis no acumule
+/// peticiones sin límite.
+#[derive(Clone)]
+pub struct RedisPool {
+    manager: ConnectionManager,
+    permits: Arc<Semaphore>,
+    timeout: Duration,
+    /// Prefijo de todas las claves (ver `key`); vacío salvo por tenant.
+    namespace: String,
+}
+
+/// Conexión prestada por `RedisPool`; libera su permiso al soltarse.
+pub struct RedisConnection {
+    manager: ConnectionManager,
+    timeout: Duration,
+    _permit: OwnedSemaphorePermit,
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+fn timed_out() -> RedisError {
+    RedisError::from((ErrorKind::IoError, "Redis operation timed out"))
+}
+
+async fn with_timeout<T>(
+    timeout: Duration,
+    future: impl Future<Output = RedisResult<T>>,
+) -> RedisResult<T> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .unwrap_or_else(|_| Err(timed_out()))
+}
+
+pub async fn create_redis_pool(redis_url: &str) -> RedisResult<RedisPool> {
+    let client = Client::open(redis_url)?;
+    let timeout = Duration::from_millis(env_or("REDIS_TIMEOUT_MS", 2000));
+    let max_in_flight = env_or("REDIS_MAX_IN_FLIGHT", 256);
+
+    let manager = with_timeout(
+        Duration::from_secs(env_or("REDIS_CONNECT_TIMEOUT_SECONDS", 10)),
+        ConnectionManager::new_with_backoff(client, 2, 100, env_or("REDIS_RECONNECT_RETRIES", 6)),
+    )
+    .await?;
+
+    Ok(RedisPool {
+        manager,
+        permits: Arc::new(Semaphore::new(max_in_flight)),
+        timeout,
+        namespace: String::new(),
+    })
+}
+
+impl RedisPool {
+    /// Misma conexión y límites, con las claves bajo otro prefijo.
+    pub fn namespaced(&self, namespace: String) -> RedisPool {
+        RedisPool {
+            namespace,
+            ..self.clone()
+        }
+    }
+
+    /// Clave completa en Redis: quien usa el pool nunca escribe fuera de su prefijo.
+    pub fn key(&self, key: &str) -> String {
+        format!("{}{}", self.namespace, key)
+    }
+
+    /// Toma una conexión esperando como máximo el timeout configurado.
+    pub async fn get(&self) -> RedisResult<RedisConnection> {
+        let permit = tokio::time::timeout(self.timeout, self.permits.clone().acquire_owned())
+            .await
+            .map_err(|_| RedisError::from((ErrorKind::IoError, "Redis pool exhausted")))?
+            .map_err(|_| RedisError::from((ErrorKind::IoError, "Redis pool closed")))?;
+
+        Ok(RedisConnection {
+            manager: self.manager.clone(),
+            timeout: self.timeout,
+            _permit: permit,
+        })
+    }
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        let timeout = self.timeout;
+        Box::pin(with_timeout(timeout, self.manager.req_packed_command(cmd)))
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        let timeout = self.timeout;
+        Box::pin(with_timeout(
+            timeout,
+            self.manager.req_packed_commands(cmd, offset, count),
+        ))
+    }
+
+    fn get_db(&self) -> i64 {
+        self.manager.get_db()
+    }
+}
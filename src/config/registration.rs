@@ -0,0 +1,104 @@
+use chrono::Duration;
+use std::collections::HashSet;
+use std::fs;
+
+use crate::config::tenants::Tenant;
+
+/// Dominios de correo temporal más comunes; `DISPOSABLE_EMAIL_DOMAINS_FILE`
+/// añade más (uno por línea).
+const DISPOSABLE_DOMAINS: &[&str] = &[
+    "10minutemail.com",
+    "discard.email",
+    "dispostable.com",
+    "emailondeck.com",
+    "fakeinbox.com",
+    "getnada.com",
+    "guerrillamail.com",
+    "maildrop.cc",
+    "mailinator.com",
+    "mintemail.com",
+    "mohmal.com",
+    "sharklasers.com",
+    "temp-mail.org",
+    "tempmail.com",
+    "throwawaymail.com",
+    "trashmail.com",
+    "yopmail.com",
+];
+
+/// Alta de usuarios: registro abierto, reglas de dominios, CAPTCHA e
+/// invitaciones de administradores.
+#[derive(Debug, Clone)]
+pub struct RegistrationSettings {
+    /// Con `false`, `/auth/register` y el alta automática vía OIDC quedan
+    /// cerrados y solo se entra por invitación.
+    pub self_registration: bool,
+    /// Si no está vacía, solo se admiten estos dominios (y sus subdominios).
+    pub allowed_domains: Vec<String>,
+    pub blocked_domains: Vec<String>,
+    /// Vacío si `BLOCK_DISPOSABLE_EMAILS=false`.
+    pub disposable_domains: HashSet<String>,
+    /// Endpoint `siteverify` (reCAPTCHA, hCaptcha o Turnstile); sin él no se pide CAPTCHA.
+    pub captcha_verify_url: Option<String>,
+    pub captcha_secret: Option<String>,
+    /// Página del frontend que recibe `?token=...` y llama a `/auth/accept-invite`.
+    pub invitation_url: String,
+    pub invitation_ttl: Duration,
+}
+
+fn domain_list(value: Option<String>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(|d| d.trim().trim_start_matches('@').to_lowercase())
+        .filter(|d| !d.is_empty())
+        .collect()
+}
+
+fn disposable_domains(tenant: &Tenant) -> Result<HashSet<String>, String> {
+    let enabled = tenant
+        .env("BLOCK_DISPOSABLE_EMAILS")
+        .map(|v| v != "false" && v != "0")
+        .unwrap_or(true);
+    if !enabled {
+        return Ok(HashSet::new());
+    }
+
+    let mut domains: HashSet<String> = DISPOSABLE_DOMAINS.iter().map(|d| d.to_string()).collect();
+    if let Some(path) = tenant.env("DISPOSABLE_EMAIL_DOMAINS_FILE") {
+        match fs::read_to_string(&path) {
+            Ok(contents) => domains.extend(
+                contents
+                    .lines()
+                    .map(|line| line.trim().to_lowercase())
+                    .filter(|line| !line.is_empty() && !line.starts_with('#')),
+            ),
+            Err(e) => return Err(format!("Cannot read DISPOSABLE_EMAIL_DOMAINS_FILE {}: {}", path, e)),
+        }
+    }
+    Ok(domains)
+}
+
+pub fn load_settings(tenant: &Tenant) -> Result<RegistrationSettings, String> {
+    Ok(RegistrationSettings {
+        self_registration: tenant
+            .env("SELF_REGISTRATION_ENABLED")
+            .map(|v| v != "false" && v != "0")
+            .unwrap_or(true),
+        allowed_domains: domain_list(tenant.env("REGISTRATION_ALLOWED_DOMAINS")),
+        blocked_domains: domain_list(tenant.env("REGISTRATION_BLOCKED_DOMAINS")),
+        disposable_domains: disposable_domains(tenant)?,
+        captcha_verify_url: tenant.env("CAPTCHA_VERIFY_URL").filter(|v| !v.is_empty()),
+        captcha_secret: tenant.env("CAPTCHA_SECRET").filter(|v| !v.is_empty()),
+        invitation_url: tenant
+            .env("USER_INVITATION_URL")
+            .unwrap_or_else(|| "http://localhost:3000/accept-invite".to_string()),
+        invitation_ttl: Duration::hours(
+            tenant
+                .env("USER_INVITATION_TTL_HOURS")
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &i64| *v > 0)
+                .unwrap_or(72),
+        ),
+    })
+}
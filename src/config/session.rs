@@ -0,0 +1,120 @@
+use actix_web::cookie::time::Duration as CookieDuration;
+use actix_web::cookie::{Cookie, SameSite};
+use crate::config::tenants::Tenant;
+
+/// Caducidad de las sesiones, en segundos.
+///
+/// La sesión muere si pasa `idle_timeout` sin peticiones o, en cualquier
+/// caso, al cumplir `absolute_timeout` desde el login. La actividad solo se
+/// escribe en el store cada `touch_interval` para no hacerlo en cada petición.
+/// Las operaciones sensibles exigen haberse autenticado hace menos de
+/// `reauth_max_age`.
+#[derive(Debug, Clone)]
+pub struct SessionSettings {
+    pub idle_timeout: i64,
+    pub absolute_timeout: i64,
+    pub touch_interval: i64,
+    pub reauth_max_age: i64,
+}
+
+fn seconds(tenant: &Tenant, key: &str, default: i64) -> i64 {
+    tenant
+        .env(key)
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &i64| *v > 0)
+        .unwrap_or(default)
+}
+
+impl SessionSettings {
+    /// TTL con el que se guarda la sesión: el idle timeout, sin pasar del límite absoluto.
+    pub fn ttl_seconds(&self, now: i64, expires_at: i64) -> u64 {
+        self.idle_timeout.min(expires_at - now).max(1) as u64
+    }
+}
+
+pub fn load_settings(tenant: &Tenant) -> SessionSettings {
+    let absolute_timeout = seconds(tenant, "SESSION_ABSOLUTE_TIMEOUT_SECONDS", 12 * 3600);
+    let idle_timeout = seconds(tenant, "SESSION_IDLE_TIMEOUT_SECONDS", 3600).min(absolute_timeout);
+
+    SessionSettings {
+        idle_timeout,
+        absolute_timeout,
+        touch_interval: seconds(tenant, "SESSION_TOUCH_INTERVAL_SECONDS", 60).min(idle_timeout),
+        reauth_max_age: seconds(tenant, "REAUTH_MAX_AGE_SECONDS", 300),
+    }
+}
+
+/// Cookies del modo de sesión para navegadores.
+#[derive(Debug, Clone)]
+pub struct CookieSettings {
+    pub session_name: String,
+    pub csrf_name: String,
+    pub secure: bool,
+    pub same_site: SameSite,
+    pub domain: Option<String>,
+    /// `/` salvo con tenants por ruta, para que no compartan cookies.
+    pub path: String,
+}
+
+impl CookieSettings {
+    /// Cookie `HttpOnly` con el token de sesión.
+    pub fn session_cookie(&self, token: &str, max_age: i64) -> Cookie<'static> {
+        self.cookie(self.session_name.clone(), token.to_string(), max_age, true)
+    }
+
+    /// Cookie legible desde JavaScript para el patrón double-submit.
+    pub fn csrf_cookie(&self, token: &str, max_age: i64) -> Cookie<'static> {
+        self.cookie(self.csrf_name.clone(), token.to_string(), max_age, false)
+    }
+
+    /// Cookies caducadas que borran la sesión del navegador.
+    pub fn removal_cookies(&self) -> [Cookie<'static>; 2] {
+        [
+            self.cookie(self.session_name.clone(), String::new(), 0, true),
+            self.cookie(self.csrf_name.clone(), String::new(), 0, false),
+        ]
+    }
+
+    /// Cookie con los mismos atributos (dominio, `Secure`, `SameSite`) que la de sesión.
+    pub fn cookie(&self, name: String, value: String, max_age: i64, http_only: bool) -> Cookie<'static> {
+        let mut cookie = Cookie::build(name, value)
+            .path(self.path.clone())
+            .secure(self.secure)
+            .http_only(http_only)
+            .same_site(self.same_site)
+            .max_age(CookieDuration::seconds(max_age))
+            .finish();
+        if let Some(domain) = &self.domain {
+            cookie.set_domain(domain.clone());
+        }
+        cookie
+    }
+}
+
+pub fn load_cookie_settings(tenant: &Tenant) -> CookieSettings {
+    let same_site = match tenant
+        .env("SESSION_COOKIE_SAMESITE")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "strict" => SameSite::Strict,
+        "none" => SameSite::None,
+        _ => SameSite::Lax,
+    };
+
+    CookieSettings {
+        session_name: tenant.env("SESSION_COOKIE_NAME").unwrap_or_else(|| "session".to_string()),
+        csrf_name: tenant.env("CSRF_COOKIE_NAME").unwrap_or_else(|| "csrf_token".to_string()),
+        // SameSite=None solo funciona con Secure
+        secure: same_site == SameSite::None
+            || tenant.env("SESSION_COOKIE_SECURE").map(|v| v != "false").unwrap_or(true),
+        same_site,
+        domain: tenant.env("SESSION_COOKIE_DOMAIN"),
+        path: if tenant.path_prefix.is_empty() {
+            "/".to_string()
+        } else {
+            tenant.path_prefix.clone()
+        },
+    }
+}
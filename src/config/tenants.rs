@@ -0,0 +1,143 @@
+use std::env;
+
+/// Tenant de las instalaciones sin `TENANTS`; también es el de los datos
+/// anteriores a la separación por tenants.
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Una marca que comparte la API con las demás pero tiene sus propios
+/// usuarios, sesiones, claves JWT y configuración.
+#[derive(Debug, Clone)]
+pub struct Tenant {
+    pub id: String,
+    /// Hosts que resuelven a este tenant (solo con `TENANT_RESOLUTION=host`).
+    pub hosts: Vec<String>,
+    /// Prefijo de sus rutas (`/{id}`) con `TENANT_RESOLUTION=path`; vacío en otro caso.
+    pub path_prefix: String,
+    pub jwt_secret: String,
+    /// Claim `iss` de sus tokens; sin él no se emite ni se comprueba.
+    pub jwt_issuer: Option<String>,
+    env_prefix: Option<String>,
+}
+
+impl Tenant {
+    /// Lee `TENANT_{ID}_{KEY}` y, si no existe, la variable global `KEY`.
+    pub fn env(&self, key: &str) -> Option<String> {
+        self.env_prefix
+            .as_ref()
+            .and_then(|prefix| env::var(format!("{}{}", prefix, key)).ok())
+            .or_else(|| env::var(key).ok())
+    }
+
+    /// Prefijo de sus claves en Redis. El tenant por defecto no lleva
+    /// prefijo para conservar las claves existentes.
+    pub fn redis_namespace(&self) -> String {
+        if self.id == DEFAULT_TENANT {
+            String::new()
+        } else {
+            format!("{}:", self.id)
+        }
+    }
+}
+
+#[cfg(test)]
+impl Tenant {
+    /// Tenant montado en la raíz que solo lee las variables globales.
+    pub fn for_tests(id: &str, jwt_secret: &str) -> Self {
+        Tenant {
+            id: id.to_string(),
+            hosts: Vec::new(),
+            path_prefix: String::new(),
+            jwt_secret: jwt_secret.to_string(),
+            jwt_issuer: None,
+            env_prefix: None,
+        }
+    }
+
+    /// Lee primero sus variables `TENANT_{ID}_*`, como los tenants de `TENANTS`.
+    pub fn with_own_env(mut self) -> Self {
+        self.env_prefix = Some(format!("TENANT_{}_", self.id.to_uppercase().replace('-', "_")));
+        self
+    }
+}
+
+/// Cómo se averigua el tenant de una petición.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TenantResolution {
+    /// Por la cabecera `Host`.
+    Host,
+    /// Por el primer segmento de la ruta: `/{tenant}/auth/login`.
+    Path,
+}
+
+/// Carga los tenants listados en `TENANTS` (separados por comas).
+///
+/// Cada tenant `x` se configura con variables `TENANT_X_*`: obligatoria
+/// `TENANT_X_JWT_SECRET`, y `TENANT_X_HOSTS` si se resuelve por host. El
+/// resto de variables de la aplicación admiten el mismo prefijo para
+/// sobrescribir el valor global (p. ej. `TENANT_X_MAGIC_LINK_URL`).
+///
+/// Sin `TENANTS` hay un único tenant `default` montado en la raíz con
+/// `JWT_SECRET` y `JWT_ISSUER` (opcional).
+pub fn load_tenants() -> Vec<Tenant> {
+    let resolution = match env::var("TENANT_RESOLUTION")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "path" => TenantResolution::Path,
+        _ => TenantResolution::Host,
+    };
+
+    let ids = env::var("TENANTS").unwrap_or_default();
+    let mut tenants = Vec::new();
+
+    for id in ids.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+        let id = id.to_lowercase();
+        if !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            panic!("Invalid tenant id: {}", id);
+        }
+        let prefix = format!("TENANT_{}_", id.to_uppercase().replace('-', "_"));
+        let var = |key: &str| env::var(format!("{}{}", prefix, key)).ok();
+
+        let (hosts, path_prefix) = match resolution {
+            TenantResolution::Host => {
+                let hosts: Vec<String> = var("HOSTS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|h| h.trim().to_lowercase())
+                    .filter(|h| !h.is_empty())
+                    .collect();
+                if hosts.is_empty() {
+                    panic!("{}HOSTS must be set", prefix);
+                }
+                (hosts, String::new())
+            }
+            TenantResolution::Path => (Vec::new(), format!("/{}", id)),
+        };
+
+        let tenant = Tenant {
+            path_prefix,
+            jwt_secret: var("JWT_SECRET").unwrap_or_else(|| panic!("{}JWT_SECRET must be set", prefix)),
+            jwt_issuer: Some(var("JWT_ISSUER").unwrap_or_else(|| id.clone())),
+            id,
+            hosts,
+            env_prefix: Some(prefix),
+        };
+
+        log::info!("Tenant configured: {}", tenant.id);
+        tenants.push(tenant);
+    }
+
+    if tenants.is_empty() {
+        tenants.push(Tenant {
+            id: DEFAULT_TENANT.to_string(),
+            hosts: Vec::new(),
+            path_prefix: String::new(),
+            jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
+            jwt_issuer: env::var("JWT_ISSUER").ok(),
+            env_prefix: None,
+        });
+    }
+
+    tenants
+}
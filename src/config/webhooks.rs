@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use crate::config::tenants::Tenant;
+
+/// Parámetros del despachador de webhooks de un tenant.
+#[derive(Debug, Clone)]
+pub struct WebhookSettings {
+    pub poll_interval: Duration,
+    pub batch_size: i64,
+    pub max_attempts: i32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    pub request_timeout: Duration,
+}
+
+fn seconds(tenant: &Tenant, key: &str, default: u64) -> Duration {
+    Duration::from_secs(
+        tenant
+            .env(key)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default),
+    )
+}
+
+pub fn load_settings(tenant: &Tenant) -> WebhookSettings {
+    WebhookSettings {
+        poll_interval: seconds(tenant, "WEBHOOK_POLL_INTERVAL_SECONDS", 5),
+        batch_size: tenant
+            .env("WEBHOOK_BATCH_SIZE")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(20),
+        max_attempts: tenant
+            .env("WEBHOOK_MAX_ATTEMPTS")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(8),
+        base_backoff: seconds(tenant, "WEBHOOK_BASE_BACKOFF_SECONDS", 30),
+        max_backoff: seconds(tenant, "WEBHOOK_MAX_BACKOFF_SECONDS", 3600),
+        request_timeout: seconds(tenant, "WEBHOOK_TIMEOUT_SECONDS", 10),
+    }
+}
@@ -0,0 +1,435 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::config::registration::RegistrationSettings;
+use crate::config::tenants::Tenant;
+use crate::handlers::auth::auth_error_response;
+use crate::middleware::auth::{authenticate, require_admin};
+use crate::models::auth_event::{page_limit, ActivityQuery, AuthEvent, AuthEventFilter};
+use crate::models::user_invitation::NewUserInvitation;
+use crate::models::webhook::{
+    CreatedWebhookEndpoint, NewWebhookEndpoint, WebhookDelivery, WebhookEndpoint,
+};
+use crate::services::audit::{self, AuthEventType, Outcome, RequestContext};
+use crate::services::auth::AuthService;
+use crate::services::crypto::random_token;
+use crate::services::mailer::{EmailMessage, Mailer};
+use crate::services::user_invitations::{self, InviteError};
+use crate::services::webhooks::SUPPORTED_EVENTS;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::with_fn(authenticate);
+
+    cfg.service(
+        web::scope("/admin")
+            .wrap(auth)
+            .route("/auth-events", web::get().to(list_auth_events))
+            .route("/invitations", web::post().to(create_invitation))
+            .route("/webhooks", web::post().to(create_webhook))
+            .route("/webhooks", web::get().to(list_webhooks))
+            .route("/webhooks/{id}", web::delete().to(deactivate_webhook))
+            .route("/webhooks/{id}/deliveries", web::get().to(list_webhook_deliveries)),
+    );
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/auth-events",
+    params(AuthEventFilter),
+    responses(
+        (status = 200, description = "Security events matching the filters, newest first", body = [AuthEvent]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Admin privileges required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn list_auth_events(
+    req: HttpRequest,
+    filter: web::Query<AuthEventFilter>,
+    pool: web::Data<PgPool>,
+    tenant: web::Data<Tenant>,
+) -> impl Responder {
+    let admin_id = match require_admin(&req, &pool).await {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let events = match sqlx::query_as::<_, AuthEvent>(
+        "SELECT id, user_id, event_type, outcome, ip, user_agent, details, created_at \
+         FROM auth_events \
+         WHERE tenant_id = $9 \
+           AND ($1::BIGINT IS NULL OR user_id = $1) \
+           AND ($2::TEXT IS NULL OR event_type = $2) \
+           AND ($3::TEXT IS NULL OR outcome = $3) \
+           AND ($4::TEXT IS NULL OR ip = $4) \
+           AND ($5::TIMESTAMPTZ IS NULL OR created_at >= $5) \
+           AND ($6::TIMESTAMPTZ IS NULL OR created_at < $6) \
+         ORDER BY created_at DESC LIMIT $7 OFFSET $8",
+    )
+    .bind(filter.user_id)
+    .bind(&filter.event_type)
+    .bind(&filter.outcome)
+    .bind(&filter.ip)
+    .bind(filter.from)
+    .bind(filter.to)
+    .bind(page_limit(filter.limit))
+    .bind(filter.offset.unwrap_or(0).max(0))
+    .bind(&tenant.id)
+    .fetch_all(&**pool)
+    .await
+    {
+        Ok(events) => events,
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+
+    audit::record(
+        &pool,
+        Some(admin_id),
+        AuthEventType::AdminAction,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "action": "auth_events.query",
+            "user_id": filter.user_id,
+            "event_type": filter.event_type
+        }),
+    )
+    .await;
+
+    HttpResponse::Ok().json(events)
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/invitations",
+    request_body = NewUserInvitation,
+    responses(
+        (status = 201, description = "Pending user created and invitation sent", body = UserInvitation),
+        (status = 400, description = "Invalid input"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Admin privileges required"),
+        (status = 409, description = "Email already registered"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_invitation(
+    req: HttpRequest,
+    body: web::Json<NewUserInvitation>,
+    pool: web::Data<PgPool>,
+    tenant: web::Data<Tenant>,
+    auth: web::Data<AuthService>,
+    mailer: web::Data<dyn Mailer>,
+    settings: web::Data<RegistrationSettings>,
+) -> impl Responder {
+    let admin_id = match require_admin(&req, &pool).await {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    if let Err(errors) = body.validate() {
+        return HttpResponse::BadRequest().json(json!({ "errors": errors }));
+    }
+
+    // Contraseña aleatoria hasta que el usuario elija la suya al aceptar
+    let unusable_password = match auth.hash_password(&random_token(48)).await {
+        Ok(hash) => hash,
+        Err(e) => return auth_error_response(&e),
+    };
+    let name = body.name.clone().unwrap_or_else(|| body.email.clone());
+
+    let (invitation, token) = match user_invitations::create(
+        &pool,
+        &tenant.id,
+        admin_id,
+        &body.email,
+        &name,
+        &unusable_password,
+        settings.invitation_ttl,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(InviteError::EmailTaken) => {
+            return HttpResponse::Conflict().json(json!({
+                "error": "Email already registered"
+            }));
+        }
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+
+    let message = EmailMessage {
+        to: invitation.email.clone(),
+        subject: "You have been invited".to_string(),
+        body: format!(
+            "An administrator has created an account for you.\n\n\
+             Choose your name and password here:\n\n{}?token={}\n\n\
+             The invitation expires on {}.\n",
+            settings.invitation_url,
+            token,
+            invitation.expires_at.format("%Y-%m-%d %H:%M UTC")
+        ),
+    };
+    if let Err(e) = mailer.send(&message).await {
+        // El usuario queda pendiente; invitarlo otra vez genera un enlace nuevo
+        log::error!("Error sending user invitation {}: {}", invitation.id, e);
+    }
+
+    audit::record(
+        &pool,
+        Some(admin_id),
+        AuthEventType::AdminAction,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "action": "user.invited",
+            "user_id": invitation.user_id,
+            "invitation_id": invitation.id
+        }),
+    )
+    .await;
+
+    HttpResponse::Created().json(invitation)
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/webhooks",
+    request_body = NewWebhookEndpoint,
+    responses(
+        (status = 201, description = "Webhook endpoint created; the secret is only returned once", body = CreatedWebhookEndpoint),
+        (status = 400, description = "Invalid input"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Admin privileges required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn create_webhook(
+    req: HttpRequest,
+    new_endpoint: web::Json<NewWebhookEndpoint>,
+    pool: web::Data<PgPool>,
+    tenant: web::Data<Tenant>,
+) -> impl Responder {
+    let admin_id = match require_admin(&req, &pool).await {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    if let Err(errors) = new_endpoint.0.validate() {
+        return HttpResponse::BadRequest().json(json!({ "errors": errors }));
+    }
+
+    if let Some(event) = new_endpoint
+        .events
+        .iter()
+        .find(|event| !SUPPORTED_EVENTS.contains(&event.as_str()))
+    {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("Unknown event: {}", event),
+            "supported_events": SUPPORTED_EVENTS
+        }));
+    }
+
+    let secret = new_endpoint
+        .secret
+        .clone()
+        .unwrap_or_else(|| format!("whsec_{}", random_token(40)));
+
+    let endpoint = match sqlx::query_as::<_, WebhookEndpoint>(
+        "INSERT INTO webhook_endpoints (tenant_id, url, secret, events) VALUES ($1, $2, $3, $4) \
+         RETURNING id, url, events, active, created_at",
+    )
+    .bind(&tenant.id)
+    .bind(&new_endpoint.url)
+    .bind(&secret)
+    .bind(&new_endpoint.events)
+    .fetch_one(&**pool)
+    .await
+    {
+        Ok(endpoint) => endpoint,
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+
+    audit::record(
+        &pool,
+        Some(admin_id),
+        AuthEventType::AdminAction,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "action": "webhook.created",
+            "webhook_id": endpoint.id,
+            "url": endpoint.url
+        }),
+    )
+    .await;
+
+    HttpResponse::Created().json(CreatedWebhookEndpoint { secret, endpoint })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks",
+    responses(
+        (status = 200, description = "Configured webhook endpoints", body = [WebhookEndpoint]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Admin privileges required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn list_webhooks(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    tenant: web::Data<Tenant>,
+) -> impl Responder {
+    if let Err(response) = require_admin(&req, &pool).await {
+        return response;
+    }
+
+    match sqlx::query_as::<_, WebhookEndpoint>(
+        "SELECT id, url, events, active, created_at FROM webhook_endpoints \
+         WHERE tenant_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(&tenant.id)
+    .fetch_all(&**pool)
+    .await
+    {
+        Ok(endpoints) => HttpResponse::Ok().json(endpoints),
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/webhooks/{id}",
+    params(("id" = Uuid, Path, description = "Webhook endpoint id")),
+    responses(
+        (status = 204, description = "Webhook endpoint deactivated; its delivery log is kept"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Admin privileges required"),
+        (status = 404, description = "Webhook endpoint not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn deactivate_webhook(
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    tenant: web::Data<Tenant>,
+) -> impl Responder {
+    let admin_id = match require_admin(&req, &pool).await {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+    let webhook_id = path.into_inner();
+
+    match sqlx::query(
+        "UPDATE webhook_endpoints SET active = false WHERE id = $1 AND tenant_id = $2 AND active",
+    )
+    .bind(webhook_id)
+    .bind(&tenant.id)
+    .execute(&**pool)
+    .await
+    {
+        Ok(result) if result.rows_affected() == 0 => HttpResponse::NotFound().json(json!({
+            "error": "Webhook endpoint not found"
+        })),
+        Ok(_) => {
+            audit::record(
+                &pool,
+                Some(admin_id),
+                AuthEventType::AdminAction,
+                Outcome::Success,
+                &RequestContext::from_request(&req),
+                json!({
+                    "action": "webhook.deactivated",
+                    "webhook_id": webhook_id
+                }),
+            )
+            .await;
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/{id}/deliveries",
+    params(("id" = Uuid, Path, description = "Webhook endpoint id"), ActivityQuery),
+    responses(
+        (status = 200, description = "Delivery attempts for the endpoint, newest first", body = [WebhookDelivery]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Admin privileges required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn list_webhook_deliveries(
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    query: web::Query<ActivityQuery>,
+    pool: web::Data<PgPool>,
+    tenant: web::Data<Tenant>,
+) -> impl Responder {
+    if let Err(response) = require_admin(&req, &pool).await {
+        return response;
+    }
+
+    match sqlx::query_as::<_, WebhookDelivery>(
+        "SELECT d.id, d.outbox_id, d.event_type, d.attempt, d.status_code, d.error, d.duration_ms, \
+                d.created_at \
+         FROM webhook_deliveries d JOIN webhook_endpoints e ON e.id = d.endpoint_id \
+         WHERE d.endpoint_id = $1 AND e.tenant_id = $4 \
+         ORDER BY d.created_at DESC LIMIT $2 OFFSET $3",
+    )
+    .bind(path.into_inner())
+    .bind(page_limit(query.limit))
+    .bind(query.offset.unwrap_or(0).max(0))
+    .bind(&tenant.id)
+    .fetch_all(&**pool)
+    .await
+    {
+        Ok(deliveries) => HttpResponse::Ok().json(deliveries),
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }))
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::middleware::auth::{authenticate, current_user_id, require_recent_auth, require_scope};
+use crate::models::api_key::{ApiKey, CreatedApiKey, NewApiKey, API_KEY_SCOPES};
+use crate::services::api_key;
+use crate::services::audit::{self, AuthEventType, Outcome, RequestContext};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::with_fn(authenticate);
+
+    cfg.service(
+        web::scope("/api-keys")
+            .wrap(auth)
+            .route("", web::post().to(create_api_key))
+            .route("", web::get().to(list_api_keys))
+            .route("/{id}", web::delete().to(revoke_api_key)),
+    );
+}
+
+#[utoipa::path(
+    post,
+    path = "/api-keys",
+    request_body = NewApiKey,
+    responses(
+        (status = 201, description = "API key created; the key is only returned once", body = CreatedApiKey),
+        (status = 400, description = "Invalid input"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope, or recent authentication required (`reauth_required`)"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "api-keys"
+)]
+pub async fn create_api_key(
+    req: HttpRequest,
+    new_key: web::Json<NewApiKey>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "api_keys:write") {
+        return response;
+    }
+    let user_id = match require_recent_auth(&req) {
+        Ok(session) => session.user_id,
+        Err(response) => return response,
+    };
+
+    if let Err(errors) = new_key.0.validate() {
+        return HttpResponse::BadRequest().json(json!({ "errors": errors }));
+    }
+
+    if let Some(scope) = new_key
+        .scopes
+        .iter()
+        .find(|scope| !API_KEY_SCOPES.contains(&scope.as_str()))
+    {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("Unknown scope: {}", scope),
+            "allowed_scopes": API_KEY_SCOPES
+        }));
+    }
+
+    let (key, prefix) = api_key::generate();
+    let expires_at = new_key
+        .expires_in_days
+        .map(|days| Utc::now() + Duration::days(days));
+
+    let created = match sqlx::query_as::<_, ApiKey>(
+        "INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at) \
+         VALUES ($1, $2, $3, $4, $5, $6) \
+         RETURNING id, name, prefix, scopes, expires_at, last_used_at, created_at",
+    )
+    .bind(user_id)
+    .bind(&new_key.name)
+    .bind(&prefix)
+    .bind(api_key::hash_key(&key))
+    .bind(&new_key.scopes)
+    .bind(expires_at)
+    .fetch_one(&**pool)
+    .await
+    {
+        Ok(created) => created,
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+
+    audit::record(
+        &pool,
+        Some(user_id),
+        AuthEventType::ApiKeyCreated,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "api_key_id": created.id,
+            "scopes": created.scopes
+        }),
+    )
+    .await;
+
+    HttpResponse::Created().json(CreatedApiKey {
+        key,
+        api_key: created,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api-keys",
+    responses(
+        (status = 200, description = "Active API keys of the current user", body = [ApiKey]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "api-keys"
+)]
+pub async fn list_api_keys(req: HttpRequest, pool: web::Data<PgPool>) -> impl Responder {
+    if let Err(response) = require_scope(&req, "api_keys:read") {
+        return response;
+    }
+    let user_id = match current_user_id(&req) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match sqlx::query_as::<_, ApiKey>(
+        "SELECT id, name, prefix, scopes, expires_at, last_used_at, created_at FROM api_keys \
+         WHERE user_id = $1 AND revoked_at IS NULL ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(&**pool)
+    .await
+    {
+        Ok(keys) => HttpResponse::Ok().json(keys),
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api-keys/{id}",
+    params(("id" = Uuid, Path, description = "API key id")),
+    responses(
+        (status = 204, description = "API key revoked"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope"),
+        (status = 404, description = "API key not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "api-keys"
+)]
+pub async fn revoke_api_key(
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "api_keys:write") {
+        return response;
+    }
+    let user_id = match current_user_id(&req) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let api_key_id = path.into_inner();
+
+    match sqlx::query(
+        "UPDATE api_keys SET revoked_at = now() \
+         WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(api_key_id)
+    .bind(user_id)
+    .execute(&**pool)
+    .await
+    {
+        Ok(result) if result.rows_affected() == 0 => HttpResponse::NotFound().json(json!({
+            "error": "API key not found"
+        })),
+        Ok(_) => {
+            audit::record(
+                &pool,
+                Some(user_id),
+                AuthEventType::ApiKeyRevoked,
+                Outcome::Success,
+                &RequestContext::from_request(&req),
+                json!({
+                    "api_key_id": api_key_id
+                }),
+            )
+            .await;
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }))
+        }
+    }
+}
@@ -1,76 +1,368 @@
-use actix_web::{web, HttpResponse, Responder, HttpMessage};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
 use actix_web_httpauth::middleware::HttpAuthentication;
-use bcrypt::{hash, verify, DEFAULT_COST};
-use jsonwebtoken::{encode, EncodingKey, Header};
-use redis::Commands;
 use serde_json::json;
 use sqlx::PgPool;
-use std::time::{SystemTime, UNIX_EPOCH};
-use validator::Validate;
-use uuid::Uuid;
 
-use crate::models::user::{LoginUser, NewUser, TokenClaims, User};
-use crate::middleware::auth::validator;
+use crate::config::registration::RegistrationSettings;
+use crate::config::session::CookieSettings;
+use crate::config::tenants::Tenant;
+use crate::models::otp::{OtpChannel, OtpFactor};
+use crate::models::session::{LoginQuery, Session, SessionMode, SessionSummary, AMR_OTP, AMR_PASSWORD};
+use crate::models::user::{
+    ChangePassword, ExpiredPasswordChange, LoginUser, NewUser, Reauthenticate, User,
+};
+use crate::models::user_invitation::AcceptInvite;
+use crate::handlers::{magic_link, mfa, oidc};
+use crate::middleware::auth::{require_recent_auth, validator};
+use crate::services::audit::{self, AuthEventType, Outcome, RequestContext};
+use crate::services::auth::{AuthError, AuthService, LoginOutcome};
+use crate::services::otp::{OtpError, OtpService};
+use crate::services::registration;
+use crate::services::captcha::CaptchaVerifier;
+use crate::services::crypto;
+use crate::services::user_invitations::{self, InviteError};
+use crate::services::users::UserRepository;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
-    let auth = HttpAuthentication::bearer(validator);
-    
+    let auth = HttpAuthentication::with_fn(validator);
+
     cfg.service(
         web::scope("/auth")
+            .configure(oidc::config)
+            .configure(magic_link::config)
+            .configure(mfa::config)
             .route("/register", web::post().to(register))
+            .route("/accept-invite", web::post().to(accept_invite))
             .route("/login", web::post().to(login))
-            .route("/logout", web::post().to(logout).wrap(auth)),
+            .route("/logout", web::post().to(logout).wrap(auth.clone()))
+            .route("/password", web::post().to(change_password).wrap(auth.clone()))
+            .route("/password/expired", web::post().to(change_expired_password))
+            .route("/reauthenticate", web::post().to(reauthenticate).wrap(auth.clone()))
+            .route("/reauthenticate/otp", web::post().to(send_reauth_code).wrap(auth.clone()))
+            .service(
+                web::resource("/sessions")
+                    .wrap(auth)
+                    .route(web::get().to(list_sessions))
+                    .route(web::delete().to(revoke_all_sessions)),
+            ),
     );
 }
 
+/// Traduce los errores de `AuthService` a respuestas HTTP.
+pub(crate) fn auth_error_response(e: &AuthError) -> HttpResponse {
+    match e {
+        AuthError::Validation(errors) => HttpResponse::BadRequest().json(json!({ "errors": errors })),
+        AuthError::EmailTaken => HttpResponse::Conflict().json(json!({
+            "error": "Email already registered"
+        })),
+        AuthError::UnknownEmail | AuthError::InvalidPassword { .. } => {
+            HttpResponse::Unauthorized().json(json!({
+                "error": "Invalid credentials"
+            }))
+        }
+        AuthError::PasswordExpired { .. } => HttpResponse::Forbidden().json(json!({
+            "error": "Password expired",
+            "password_change_required": true
+        })),
+        AuthError::UserNotFound => HttpResponse::NotFound().json(json!({
+            "error": "User not found"
+        })),
+        AuthError::SessionNotFound => HttpResponse::Unauthorized().json(json!({
+            "error": "Session not found"
+        })),
+        AuthError::Hashing(_) => {
+            log::error!("{}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error"
+            }))
+        }
+        AuthError::Token(_) => {
+            log::error!("{}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Error generating token"
+            }))
+        }
+        AuthError::Repository(_) => {
+            log::error!("{}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }))
+        }
+        AuthError::Session(_) => {
+            log::error!("{}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Session store error"
+            }))
+        }
+    }
+}
+
+/// Respuesta de login compartida por el login con contraseña y el de OIDC.
+fn session_response(user: &User, session: &Session) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "session_id": session.id,
+        "token": session.token,
+        "user": {
+            "id": user.id,
+            "email": user.email,
+            "name": user.name
+        }
+    }))
+}
+
+/// Login en modo cookie: el token nunca llega al JavaScript del cliente.
+///
+/// El token CSRF va en el cuerpo y en una cookie legible; debe enviarse en
+/// `X-CSRF-Token` en las peticiones que cambian estado.
+fn cookie_session_response(
+    user: &User,
+    session: &Session,
+    cookies: &CookieSettings,
+    jwt_secret: &str,
+) -> HttpResponse {
+    let csrf_token = crypto::csrf_token(jwt_secret, &session.id);
+    let max_age = session.expires_at - session.created_at;
+
+    HttpResponse::Ok()
+        .cookie(cookies.session_cookie(&session.token, max_age))
+        .cookie(cookies.csrf_cookie(&csrf_token, max_age))
+        .json(json!({
+            "session_id": session.id,
+            "csrf_token": csrf_token,
+            "user": {
+                "id": user.id,
+                "email": user.email,
+                "name": user.name
+            }
+        }))
+}
+
+/// Respuesta de login según el modo pedido en `?mode=`.
+pub(crate) fn login_response(
+    user: &User,
+    session: &Session,
+    mode: SessionMode,
+    cookies: &CookieSettings,
+    jwt_secret: &str,
+) -> HttpResponse {
+    match mode {
+        SessionMode::Token => session_response(user, session),
+        SessionMode::Cookie => cookie_session_response(user, session, cookies, jwt_secret),
+    }
+}
+
+pub(crate) fn current_session(req: &HttpRequest) -> Result<Session, HttpResponse> {
+    match req.extensions().get::<Session>() {
+        Some(session) => Ok(session.clone()),
+        None => {
+            log::error!("Session not found in request extensions");
+            Err(HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error"
+            })))
+        }
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/auth/register",
     request_body = NewUser,
     responses(
         (status = 201, description = "User created successfully", body = User),
-        (status = 400, description = "Invalid input"),
-        (status = 500, description = "Internal server error")
+        (status = 400, description = "Invalid input, email domain not accepted or CAPTCHA failed"),
+        (status = 403, description = "Self-registration is disabled; accounts are created by invitation"),
+        (status = 409, description = "Email already registered"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "CAPTCHA provider unavailable")
     ),
     tag = "auth"
 )]
 async fn register(
+    req: HttpRequest,
     user: web::Json<NewUser>,
     pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+    settings: web::Data<RegistrationSettings>,
+    captcha: web::Data<dyn CaptchaVerifier>,
 ) -> impl Responder {
-    if let Err(errors) = user.0.validate() {
-        return HttpResponse::BadRequest().json(json!({ "errors": errors }));
+    if !settings.self_registration {
+        return HttpResponse::Forbidden().json(json!({
+            "error": "Self-registration is disabled"
+        }));
     }
 
-    let hashed_password = match hash(&user.password, DEFAULT_COST) {
-        Ok(hash) => hash,
+    let context = RequestContext::from_request(&req);
+
+    // Dominios y CAPTCHA se comprueban antes de tocar la base de datos
+    if let Err(rejection) = registration::check_email_domain(&settings, &user.email) {
+        audit::record(
+            &pool,
+            None,
+            AuthEventType::Register,
+            Outcome::Failure,
+            &context,
+            json!({
+                "email": user.email,
+                "reason": rejection.as_str()
+            }),
+        )
+        .await;
+        return HttpResponse::BadRequest().json(json!({
+            "error": rejection.to_string()
+        }));
+    }
+
+    match captcha
+        .verify(user.captcha_token.as_deref(), context.ip.as_deref())
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            audit::record(
+                &pool,
+                None,
+                AuthEventType::Register,
+                Outcome::Failure,
+                &context,
+                json!({
+                    "email": user.email,
+                    "reason": "captcha_failed"
+                }),
+            )
+            .await;
+            return HttpResponse::BadRequest().json(json!({
+                "error": "CAPTCHA verification failed"
+            }));
+        }
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::ServiceUnavailable().json(json!({
+                "error": "Could not verify CAPTCHA"
+            }));
+        }
+    }
+
+    let created = match auth.register(&user).await {
+        Ok(created) => created,
         Err(e) => {
-            log::error!("Error hashing password: {}", e);
+            if !matches!(e, AuthError::Validation(_)) {
+                audit::record(
+                    &pool,
+                    None,
+                    AuthEventType::Register,
+                    Outcome::Failure,
+                    &context,
+                    json!({
+                        "email": user.email
+                    }),
+                )
+                .await;
+            }
+            return auth_error_response(&e);
+        }
+    };
+
+    audit::record(
+        &pool,
+        Some(created.id),
+        AuthEventType::Register,
+        Outcome::Success,
+        &context,
+        json!({}),
+    )
+    .await;
+
+    HttpResponse::Created().json(json!({
+        "id": created.id,
+        "email": created.email,
+        "name": created.name
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/accept-invite",
+    request_body = AcceptInvite,
+    responses(
+        (status = 200, description = "Invitation accepted; the user can now log in", body = User),
+        (status = 400, description = "Invalid input"),
+        (status = 404, description = "Invalid or expired invitation"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "auth"
+)]
+async fn accept_invite(
+    req: HttpRequest,
+    body: web::Json<AcceptInvite>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+    tenant: web::Data<Tenant>,
+) -> impl Responder {
+    let invitation = match user_invitations::find_pending(&pool, &tenant.id, &body.token).await {
+        Ok(Some(invitation)) => invitation,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "Invalid or expired invitation"
+            }));
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
             return HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
+                "error": "Database error"
             }));
         }
     };
-    
-    let result = match sqlx::query_as::<_, (i64,)>(
-        "INSERT INTO users (email, password, name) VALUES ($1, $2, $3) RETURNING id",
+
+    // Mismas reglas que el registro abierto
+    let new_user = NewUser {
+        email: invitation.email.clone(),
+        password: body.password.clone(),
+        name: body.name.clone(),
+        captcha_token: None,
+    };
+    let password_hash = match auth.new_user_password_hash(&new_user).await {
+        Ok(hash) => hash,
+        Err(e) => return auth_error_response(&e),
+    };
+
+    let user = match user_invitations::accept(
+        &pool,
+        &tenant.id,
+        invitation.id,
+        &new_user.name,
+        &password_hash,
     )
-    .bind(&user.email)
-    .bind(&hashed_password)
-    .bind(&user.name)
-    .fetch_one(&**pool)
-    .await {
-        Ok(record) => record,
+    .await
+    {
+        Ok(user) => user,
+        Err(InviteError::InvalidInvitation) => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "Invalid or expired invitation"
+            }));
+        }
         Err(e) => {
-            log::error!("Error en la base de datos: {:?}", e);
+            log::error!("{}", e);
             return HttpResponse::InternalServerError().json(json!({
-                "error": format!("Database error: {:?}", e)
+                "error": "Database error"
             }));
         }
     };
 
-    HttpResponse::Created().json(json!({
-        "id": result.0,
+    audit::record(
+        &pool,
+        Some(user.id),
+        AuthEventType::Register,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "method": "invitation",
+            "invitation_id": invitation.id
+        }),
+    )
+    .await;
+
+    HttpResponse::Ok().json(json!({
+        "id": user.id,
         "email": user.email,
         "name": user.name
     }))
@@ -79,126 +371,71 @@ async fn register(
 #[utoipa::path(
     post,
     path = "/auth/login",
+    params(LoginQuery),
     request_body = LoginUser,
     responses(
         (status = 200, description = "Login successful", body = TokenResponse),
+        (status = 202, description = "Second factor required; code sent, complete at /auth/mfa/verify"),
         (status = 401, description = "Invalid credentials"),
+        (status = 403, description = "Password expired; change it with /auth/password/expired"),
         (status = 500, description = "Internal server error")
     ),
     tag = "auth"
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn login(
+    req: HttpRequest,
+    query: web::Query<LoginQuery>,
     credentials: web::Json<LoginUser>,
     pool: web::Data<PgPool>,
-    redis_client: web::Data<redis::Client>,
+    auth: web::Data<AuthService>,
+    otp: web::Data<OtpService>,
+    cookies: web::Data<CookieSettings>,
     jwt_secret: web::Data<String>,
 ) -> impl Responder {
-    let user = match sqlx::query_as::<_, User>(
-        "SELECT id, email, password, name FROM users WHERE email = $1",
-    )
-    .bind(&credentials.email)
-    .fetch_optional(&**pool)
-    .await {
-        Ok(user) => user,
+    let context = RequestContext::from_request(&req);
+
+    let (user, session) = match auth.login(&credentials).await {
+        Ok(LoginOutcome::Authenticated(user, session)) => (user, session),
+        Ok(LoginOutcome::MfaRequired(user, factor, amr)) => {
+            return mfa::challenge_response(&pool, &otp, &context, &user, &factor, &amr).await;
+        }
         Err(e) => {
-            log::error!("Database error: {}", e);
-            return HttpResponse::InternalServerError().json(json!({
-                "error": "Database error"
-            }));
+            let (user_id, reason) = match &e {
+                AuthError::UnknownEmail => (None, "unknown_email"),
+                AuthError::InvalidPassword { user_id } => (Some(*user_id), "invalid_password"),
+                AuthError::PasswordExpired { user_id } => (Some(*user_id), "password_expired"),
+                _ => return auth_error_response(&e),
+            };
+            audit::record(
+                &pool,
+                user_id,
+                AuthEventType::Login,
+                Outcome::Failure,
+                &context,
+                json!({
+                    "reason": reason,
+                    "email": credentials.email
+                }),
+            )
+            .await;
+            return auth_error_response(&e);
         }
     };
 
-    match user {
-        Some(user) => {
-            if verify(&credentials.password, &user.password).unwrap_or(false) {
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as i64;
-                
-                let session_id = Uuid::new_v4().to_string();
-                let claims = TokenClaims {
-                    sub: user.id,
-                    exp: now + 3600, // 1 hora
-                };
-
-                let token = match encode(
-                    &Header::default(),
-                    &claims,
-                    &EncodingKey::from_secret(jwt_secret.as_bytes()),
-                ) {
-                    Ok(token) => token,
-                    Err(e) => {
-                        log::error!("Error generating token: {}", e);
-                        return HttpResponse::InternalServerError().json(json!({
-                            "error": "Error generating token"
-                        }));
-                    }
-                };
-
-                // Almacenar sesión en Redis
-                let mut conn = match redis_client.get_connection() {
-                    Ok(conn) => conn,
-                    Err(e) => {
-                        log::error!("Redis connection error: {}", e);
-                        return HttpResponse::InternalServerError().json(json!({
-                            "error": "Redis connection error"
-                        }));
-                    }
-                };
-
-                // Almacenar información de la sesión
-                let session_data = json!({
-                    "user_id": user.id,
-                    "email": user.email,
-                    "name": user.name,
-                    "token": token.clone(),
-                    "created_at": now,
-                    "expires_at": now + 3600
-                }).to_string();
-
-                if let Err(e) = conn.set_ex::<String, String, ()>(
-                    format!("session:{}", session_id),
-                    session_data,
-                    3600
-                ) {
-                    log::error!("Redis error: {}", e);
-                    return HttpResponse::InternalServerError().json(json!({
-                        "error": "Redis error"
-                    }));
-                }
-
-                // Almacenar la relación usuario-sesión
-                if let Err(e) = conn.set_ex::<String, String, ()>(
-                    format!("user_session:{}", user.id),
-                    session_id.clone(),
-                    3600
-                ) {
-                    log::error!("Redis error: {}", e);
-                    return HttpResponse::InternalServerError().json(json!({
-                        "error": "Redis error"
-                    }));
-                }
+    audit::record(
+        &pool,
+        Some(user.id),
+        AuthEventType::Login,
+        Outcome::Success,
+        &context,
+        json!({
+            "method": "password"
+        }),
+    )
+    .await;
 
-                HttpResponse::Ok().json(json!({
-                    "session_id": session_id,
-                    "token": token,
-                    "user": {
-                        "id": user.id,
-                        "email": user.email,
-                        "name": user.name
-                    }
-                }))
-            } else {
-                HttpResponse::Unauthorized().json(json!({
-                    "error": "Invalid credentials"
-                }))
-            }
-        }
-        None => HttpResponse::Unauthorized().json(json!({
-            "error": "Invalid credentials"
-        })),
-    }
+    login_response(&user, &session, query.mode.unwrap_or_default(), &cookies, &jwt_secret)
 }
 
 #[utoipa::path(
@@ -211,59 +448,419 @@ pub async fn login(
     tag = "auth"
 )]
 pub async fn logout(
-    req: actix_web::HttpRequest,
-    redis_client: web::Data<redis::Client>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+    cookies: web::Data<CookieSettings>,
 ) -> impl Responder {
-    let user_id = match req.extensions().get::<i64>() {
-        Some(id) => *id,
-        None => {
-            log::error!("User ID not found in request extensions");
-            return HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }));
+    let session = match current_session(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+
+    // Solo se cierra la sesión de este token; las demás siguen activas
+    if let Err(e) = auth.logout(&session.id).await {
+        return auth_error_response(&e);
+    }
+
+    let context = RequestContext::from_request(&req);
+    audit::record(
+        &pool,
+        Some(session.user_id),
+        AuthEventType::Logout,
+        Outcome::Success,
+        &context,
+        json!({ "session_id": session.id }),
+    )
+    .await;
+
+    let mut response = HttpResponse::Ok();
+    if req.cookie(&cookies.session_name).is_some() {
+        for cookie in cookies.removal_cookies() {
+            response.cookie(cookie);
+        }
+    }
+    response.json(json!({
+        "message": "Successfully logged out"
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/password",
+    request_body = ChangePassword,
+    responses(
+        (status = 200, description = "Password changed; other sessions are revoked"),
+        (status = 400, description = "Invalid input"),
+        (status = 401, description = "Unauthorized or wrong current password"),
+        (status = 403, description = "Recent authentication required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "auth"
+)]
+pub async fn change_password(
+    req: HttpRequest,
+    change: web::Json<ChangePassword>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+) -> impl Responder {
+    let session = match require_recent_auth(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+    let context = RequestContext::from_request(&req);
+
+    let revoked = match auth.change_password(&session, &change).await {
+        Ok(revoked) => revoked,
+        Err(e) => {
+            if matches!(e, AuthError::InvalidPassword { .. }) {
+                audit::record(
+                    &pool,
+                    Some(session.user_id),
+                    AuthEventType::PasswordChanged,
+                    Outcome::Failure,
+                    &context,
+                    json!({
+                        "reason": "invalid_password"
+                    }),
+                )
+                .await;
+            }
+            return auth_error_response(&e);
         }
     };
 
-    let mut conn = match redis_client.get_connection() {
-        Ok(conn) => conn,
+    audit::record(
+        &pool,
+        Some(session.user_id),
+        AuthEventType::PasswordChanged,
+        Outcome::Success,
+        &context,
+        json!({
+            "revoked_sessions": revoked
+        }),
+    )
+    .await;
+
+    HttpResponse::Ok().json(json!({
+        "message": "Password changed",
+        "revoked_sessions": revoked
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/password/expired",
+    params(LoginQuery),
+    request_body = ExpiredPasswordChange,
+    responses(
+        (status = 200, description = "Password changed and session started", body = TokenResponse),
+        (status = 202, description = "Password changed; second factor required at /auth/mfa/verify"),
+        (status = 400, description = "Invalid input or password rejected by the policy"),
+        (status = 401, description = "Invalid credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "auth"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn change_expired_password(
+    req: HttpRequest,
+    query: web::Query<LoginQuery>,
+    change: web::Json<ExpiredPasswordChange>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+    otp: web::Data<OtpService>,
+    cookies: web::Data<CookieSettings>,
+    jwt_secret: web::Data<String>,
+) -> impl Responder {
+    let context = RequestContext::from_request(&req);
+
+    let outcome = match auth.change_expired_password(&change).await {
+        Ok(outcome) => outcome,
         Err(e) => {
-            log::error!("Redis connection error: {}", e);
-            return HttpResponse::InternalServerError().json(json!({
-                "error": "Redis connection error"
+            let user_id = match &e {
+                AuthError::UnknownEmail => None,
+                AuthError::InvalidPassword { user_id } => Some(*user_id),
+                _ => return auth_error_response(&e),
+            };
+            audit::record(
+                &pool,
+                user_id,
+                AuthEventType::PasswordChanged,
+                Outcome::Failure,
+                &context,
+                json!({
+                    "reason": "invalid_credentials",
+                    "email": change.email
+                }),
+            )
+            .await;
+            return auth_error_response(&e);
+        }
+    };
+    let user = match &outcome {
+        LoginOutcome::Authenticated(user, _) | LoginOutcome::MfaRequired(user, _, _) => user,
+    };
+
+    audit::record(
+        &pool,
+        Some(user.id),
+        AuthEventType::PasswordChanged,
+        Outcome::Success,
+        &context,
+        json!({
+            "reason": "expired"
+        }),
+    )
+    .await;
+
+    let (user, session) = match outcome {
+        LoginOutcome::Authenticated(user, session) => (user, session),
+        LoginOutcome::MfaRequired(user, factor, amr) => {
+            return mfa::challenge_response(&pool, &otp, &context, &user, &factor, &amr).await;
+        }
+    };
+    audit::record(
+        &pool,
+        Some(user.id),
+        AuthEventType::Login,
+        Outcome::Success,
+        &context,
+        json!({
+            "method": "password_change"
+        }),
+    )
+    .await;
+
+    login_response(&user, &session, query.mode.unwrap_or_default(), &cookies, &jwt_secret)
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/reauthenticate",
+    request_body = Reauthenticate,
+    responses(
+        (status = 200, description = "Identity confirmed; a new token with a fresh auth_time replaces the current one"),
+        (status = 400, description = "Neither or both of password and code given"),
+        (status = 401, description = "Unauthorized, wrong password or invalid code"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "auth"
+)]
+pub async fn reauthenticate(
+    req: HttpRequest,
+    body: web::Json<Reauthenticate>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+    otp: web::Data<OtpService>,
+    cookies: web::Data<CookieSettings>,
+) -> impl Responder {
+    let session = match current_session(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+    let context = RequestContext::from_request(&req);
+    let failure = |reason: &'static str| {
+        audit::record(
+            &pool,
+            Some(session.user_id),
+            AuthEventType::Reauthenticated,
+            Outcome::Failure,
+            &context,
+            json!({
+                "reason": reason
+            }),
+        )
+    };
+
+    let (result, method) = match (&body.password, &body.code) {
+        (Some(password), None) => (auth.reauthenticate(&session, password).await, AMR_PASSWORD),
+        // Código de `POST /auth/reauthenticate/otp`: las cuentas sin contraseña
+        (None, Some(code)) => {
+            if let Err(e) = otp.verify_reauth(&session.id, code).await {
+                if matches!(e, OtpError::InvalidCode { .. } | OtpError::Expired) {
+                    failure("invalid_otp").await;
+                }
+                return mfa::otp_error_response(&e);
+            }
+            (auth.confirm_reauthentication(&session, AMR_OTP).await, AMR_OTP)
+        }
+        _ => {
+            return HttpResponse::BadRequest().json(json!({
+                "error": "Provide either password or code"
             }));
         }
     };
 
-    // Obtener la sesión del usuario
-    let session_id: Option<String> = match conn.get(format!("user_session:{}", user_id)) {
+    let session = match result {
         Ok(session) => session,
         Err(e) => {
-            log::error!("Redis error: {}", e);
-            return HttpResponse::InternalServerError().json(json!({
-                "error": "Redis error"
-            }));
+            if matches!(e, AuthError::InvalidPassword { .. }) {
+                failure("invalid_password").await;
+            }
+            return auth_error_response(&e);
         }
     };
 
-    if let Some(session_id) = session_id {
-        // Eliminar la sesión
-        if let Err(e) = conn.del::<String, ()>(format!("session:{}", session_id)) {
-            log::error!("Redis error: {}", e);
+    audit::record(
+        &pool,
+        Some(session.user_id),
+        AuthEventType::Reauthenticated,
+        Outcome::Success,
+        &context,
+        json!({
+            "session_id": session.id,
+            "method": method
+        }),
+    )
+    .await;
+
+    let mode = match req.cookie(&cookies.session_name) {
+        Some(_) => SessionMode::Cookie,
+        None => SessionMode::Token,
+    };
+    reauthenticated_response(&session, mode, &cookies)
+}
+
+/// Respuesta de una reautenticación correcta. El token anterior deja de
+/// valer: en modo cookie se sustituye la cookie.
+pub(crate) fn reauthenticated_response(
+    session: &Session,
+    mode: SessionMode,
+    cookies: &CookieSettings,
+) -> HttpResponse {
+    match mode {
+        SessionMode::Cookie => {
+            let max_age = session.expires_at - session.last_seen_at;
+            HttpResponse::Ok()
+                .cookie(cookies.session_cookie(&session.token, max_age))
+                .json(json!({
+                    "auth_time": session.auth_time,
+                    "amr": session.amr
+                }))
+        }
+        SessionMode::Token => HttpResponse::Ok().json(json!({
+            "token": session.token,
+            "auth_time": session.auth_time,
+            "amr": session.amr
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/reauthenticate/otp",
+    responses(
+        (status = 202, description = "Code sent to the second factor or, without one, to the account email; confirm it at /auth/reauthenticate"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many codes requested"),
+        (status = 503, description = "One-time codes unavailable")
+    ),
+    tag = "auth"
+)]
+pub async fn send_reauth_code(
+    req: HttpRequest,
+    users: web::Data<dyn UserRepository>,
+    otp: web::Data<OtpService>,
+) -> impl Responder {
+    let session = match current_session(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+
+    let factor = match users.otp_factor(session.user_id).await {
+        Ok(Some(factor)) => factor,
+        Ok(None) => OtpFactor {
+            channel: OtpChannel::Email,
+            destination: session.email.clone(),
+        },
+        Err(e) => {
+            log::error!("Database error: {}", e);
             return HttpResponse::InternalServerError().json(json!({
-                "error": "Redis error"
+                "error": "Database error"
             }));
         }
+    };
+
+    match otp.start_reauth(&session.id, session.user_id, &factor).await {
+        Ok(()) => HttpResponse::Accepted().json(json!({
+            "message": "Code sent",
+            "channel": factor.channel,
+            "destination": factor.masked_destination(),
+            "expires_in": otp.ttl_seconds()
+        })),
+        Err(e) => mfa::otp_error_response(&e),
+    }
+}
 
-        // Eliminar la relación usuario-sesión
-        if let Err(e) = conn.del::<String, ()>(format!("user_session:{}", user_id)) {
-            log::error!("Redis error: {}", e);
+#[utoipa::path(
+    get,
+    path = "/auth/sessions",
+    responses(
+        (status = 200, description = "Active sessions of the current user, newest first", body = [SessionSummary]),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "auth"
+)]
+pub async fn list_sessions(req: HttpRequest, auth: web::Data<AuthService>) -> impl Responder {
+    let current = match current_session(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+
+    match auth.list_sessions(current.user_id).await {
+        Ok(list) => HttpResponse::Ok().json(
+            list.iter()
+                .map(|session| SessionSummary::new(session, &current.id))
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => auth_error_response(&e),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/auth/sessions",
+    responses(
+        (status = 200, description = "All sessions of the current user revoked, including this one"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "auth"
+)]
+pub async fn revoke_all_sessions(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+) -> impl Responder {
+    let user_id = match req.extensions().get::<i64>() {
+        Some(id) => *id,
+        None => {
+            log::error!("User ID not found in request extensions");
             return HttpResponse::InternalServerError().json(json!({
-                "error": "Redis error"
+                "error": "Internal server error"
             }));
         }
-    }
-    
+    };
+
+    let revoked = match auth.logout_all(user_id).await {
+        Ok(revoked) => revoked,
+        Err(e) => return auth_error_response(&e),
+    };
+
+    audit::record(
+        &pool,
+        Some(user_id),
+        AuthEventType::Logout,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({ "all_sessions": true, "revoked": revoked }),
+    )
+    .await;
+
     HttpResponse::Ok().json(json!({
-        "message": "Successfully logged out"
+        "message": "All sessions revoked",
+        "revoked": revoked
     }))
-} 
\ No newline at end of file
+}
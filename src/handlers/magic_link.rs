@@ -0,0 +1,247 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde_json::json;
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::config::magic_link::MagicLinkSettings;
+use crate::config::session::CookieSettings;
+use crate::handlers::auth::{auth_error_response, login_response};
+use crate::handlers::mfa;
+use crate::models::magic_link::{MagicLinkRequest, MagicLinkVerify};
+use crate::models::session::{LoginQuery, AMR_MAGIC_LINK};
+use crate::services::audit::{self, AuthEventType, Outcome, RequestContext};
+use crate::services::auth::{AuthError, AuthService, LoginOutcome};
+use crate::services::crypto::random_token;
+use crate::services::magic_link::{self, MagicLinkError};
+use crate::services::mailer::{EmailMessage, Mailer};
+use crate::services::otp::OtpService;
+use crate::services::users::UserRepository;
+
+/// Se monta dentro del scope `/auth` (ver `handlers::auth::config`).
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/magic-link")
+            .route("", web::post().to(request_link))
+            .route("/verify", web::post().to(verify_link)),
+    );
+}
+
+/// Busca al usuario, crea el enlace y lo envía. Se ejecuta fuera de la
+/// petición para que la respuesta no revele si el email existe.
+#[allow(clippy::too_many_arguments)]
+async fn send_link(
+    pool: web::Data<PgPool>,
+    users: web::Data<dyn UserRepository>,
+    mailer: web::Data<dyn Mailer>,
+    settings: web::Data<MagicLinkSettings>,
+    jwt_secret: web::Data<String>,
+    context: RequestContext,
+    email: String,
+    nonce: String,
+) {
+    let user = match users.find_by_email(&email).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return,
+        Err(e) => {
+            log::error!("Error looking up user for magic link: {}", e);
+            return;
+        }
+    };
+
+    let token = match magic_link::issue(&pool, &jwt_secret, user.id, &nonce, settings.ttl_seconds).await {
+        Ok(token) => token,
+        Err(e) => {
+            log::error!("Error creating magic link: {}", e);
+            return;
+        }
+    };
+
+    let message = EmailMessage {
+        to: user.email.clone(),
+        subject: "Your sign-in link".to_string(),
+        body: format!(
+            "Hi {},\n\nUse this link to sign in. It expires in {} minutes and only works \
+             in the browser where you requested it:\n\n{}?token={}\n\n\
+             If you did not request it, you can ignore this email.\n",
+            user.name,
+            settings.ttl_seconds / 60,
+            settings.url,
+            token
+        ),
+    };
+    if let Err(e) = mailer.send(&message).await {
+        log::error!("Error sending magic link: {}", e);
+        return;
+    }
+
+    audit::record(
+        &pool,
+        Some(user.id),
+        AuthEventType::MagicLinkRequested,
+        Outcome::Success,
+        &context,
+        json!({}),
+    )
+    .await;
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/magic-link",
+    request_body = MagicLinkRequest,
+    responses(
+        (status = 202, description = "If the email is registered, a sign-in link has been sent"),
+        (status = 400, description = "Invalid input")
+    ),
+    tag = "auth"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn request_link(
+    req: HttpRequest,
+    request: web::Json<MagicLinkRequest>,
+    pool: web::Data<PgPool>,
+    users: web::Data<dyn UserRepository>,
+    mailer: web::Data<dyn Mailer>,
+    settings: web::Data<MagicLinkSettings>,
+    cookies: web::Data<CookieSettings>,
+    jwt_secret: web::Data<String>,
+) -> impl Responder {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(json!({ "errors": errors }));
+    }
+
+    // El nonce solo viaja en la cookie: un enlace reenviado no sirve sin ella
+    let nonce = random_token(32);
+    let nonce_cookie = cookies.cookie(
+        settings.nonce_cookie.clone(),
+        nonce.clone(),
+        settings.ttl_seconds,
+        true,
+    );
+
+    actix_web::rt::spawn(send_link(
+        pool,
+        users,
+        mailer,
+        settings,
+        jwt_secret,
+        RequestContext::from_request(&req),
+        request.into_inner().email,
+        nonce,
+    ));
+
+    HttpResponse::Accepted().cookie(nonce_cookie).json(json!({
+        "message": "If the email is registered, a sign-in link has been sent"
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/magic-link/verify",
+    params(LoginQuery),
+    request_body = MagicLinkVerify,
+    responses(
+        (status = 200, description = "Login successful", body = TokenResponse),
+        (status = 202, description = "Second factor required; complete at /auth/mfa/verify"),
+        (status = 401, description = "Invalid, expired or already used link, or requested from another browser"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "auth"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn verify_link(
+    req: HttpRequest,
+    query: web::Query<LoginQuery>,
+    verify: web::Json<MagicLinkVerify>,
+    pool: web::Data<PgPool>,
+    users: web::Data<dyn UserRepository>,
+    auth: web::Data<AuthService>,
+    otp: web::Data<OtpService>,
+    settings: web::Data<MagicLinkSettings>,
+    cookies: web::Data<CookieSettings>,
+    jwt_secret: web::Data<String>,
+) -> impl Responder {
+    let context = RequestContext::from_request(&req);
+    let invalid = || {
+        HttpResponse::Unauthorized().json(json!({
+            "error": "Invalid or expired magic link"
+        }))
+    };
+
+    let nonce = match req.cookie(&settings.nonce_cookie) {
+        Some(cookie) => cookie.value().to_string(),
+        None => return invalid(),
+    };
+
+    let user_id = match magic_link::consume(&pool, &jwt_secret, &verify.token, &nonce).await {
+        Ok(user_id) => user_id,
+        Err(MagicLinkError::Invalid) => {
+            audit::record(
+                &pool,
+                None,
+                AuthEventType::Login,
+                Outcome::Failure,
+                &context,
+                json!({
+                    "method": "magic_link",
+                    "reason": "invalid_link"
+                }),
+            )
+            .await;
+            return invalid();
+        }
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+
+    let user = match users.find_by_id(user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return auth_error_response(&AuthError::UserNotFound),
+        Err(e) => return auth_error_response(&AuthError::Repository(e)),
+    };
+
+    // El enlace sustituye a la contraseña, no al segundo factor
+    let (user, session) = match auth.begin_session(user, vec![AMR_MAGIC_LINK.to_string()]).await {
+        Ok(LoginOutcome::Authenticated(user, session)) => (user, session),
+        Ok(LoginOutcome::MfaRequired(user, factor, amr)) => {
+            let mut response = mfa::challenge_response(&pool, &otp, &context, &user, &factor, &amr).await;
+            clear_nonce_cookie(&mut response, &settings, &cookies);
+            return response;
+        }
+        Err(e) => return auth_error_response(&e),
+    };
+
+    audit::record(
+        &pool,
+        Some(user.id),
+        AuthEventType::Login,
+        Outcome::Success,
+        &context,
+        json!({
+            "method": "magic_link"
+        }),
+    )
+    .await;
+
+    let mut response = login_response(
+        &user,
+        &session,
+        query.mode.unwrap_or_default(),
+        &cookies,
+        &jwt_secret,
+    );
+    clear_nonce_cookie(&mut response, &settings, &cookies);
+    response
+}
+
+/// El nonce ya no sirve una vez consumido el enlace.
+fn clear_nonce_cookie(response: &mut HttpResponse, settings: &MagicLinkSettings, cookies: &CookieSettings) {
+    let removal = cookies.cookie(settings.nonce_cookie.clone(), String::new(), 0, true);
+    if let Err(e) = response.add_cookie(&removal) {
+        log::error!("Error clearing magic link nonce cookie: {}", e);
+    }
+}
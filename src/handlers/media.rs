@@ -0,0 +1,39 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde_json::json;
+
+use crate::services::blob_store::{BlobError, BlobStore};
+
+/// Prefijos del `BlobStore` que son públicos. El resto (las exportaciones
+/// de datos) solo se descarga por su endpoint autenticado.
+const PUBLIC_PREFIXES: &[&str] = &["avatars/"];
+
+/// Ficheros del `BlobStore` (avatares). Se monta fuera de los tenants: las
+/// claves ya llevan el tenant y no son adivinables.
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/media/{key:.*}", web::get().to(get_blob));
+}
+
+pub async fn get_blob(key: web::Path<String>, blobs: web::Data<dyn BlobStore>) -> impl Responder {
+    if !PUBLIC_PREFIXES.iter().any(|prefix| key.starts_with(prefix)) {
+        return HttpResponse::NotFound().json(json!({
+            "error": "Not found"
+        }));
+    }
+
+    match blobs.get(&key).await {
+        Ok(Some(blob)) => HttpResponse::Ok()
+            .content_type(blob.content_type)
+            // Cada subida usa claves nuevas, así que el contenido nunca cambia
+            .insert_header(("Cache-Control", "public, max-age=31536000, immutable"))
+            .body(blob.data),
+        Ok(None) | Err(BlobError::InvalidKey) => HttpResponse::NotFound().json(json!({
+            "error": "Not found"
+        })),
+        Err(e) => {
+            log::error!("{}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error"
+            }))
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use serde_json::json;
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::config::session::CookieSettings;
+use crate::handlers::auth::{auth_error_response, current_session, login_response};
+use crate::middleware::auth::{require_recent_auth, validator};
+use crate::models::otp::{ConfirmOtp, EnrollOtp, MfaResend, MfaVerify, OtpChannel, OtpFactor};
+use crate::models::session::LoginQuery;
+use crate::models::user::User;
+use crate::services::audit::{self, AuthEventType, Outcome, RequestContext};
+use crate::services::auth::AuthService;
+use crate::services::otp::{OtpError, OtpService};
+use crate::services::users::UserRepository;
+
+/// Se monta dentro del scope `/auth` (ver `handlers::auth::config`).
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::with_fn(validator);
+
+    cfg.service(
+        web::scope("/mfa")
+            .route("/verify", web::post().to(verify))
+            .route("/resend", web::post().to(resend))
+            .service(
+                web::scope("/otp")
+                    .wrap(auth)
+                    .route("", web::post().to(enroll_otp))
+                    .route("", web::delete().to(disable_otp))
+                    .route("/confirm", web::post().to(confirm_otp)),
+            ),
+    );
+}
+
+/// Traduce los errores de `OtpService` a respuestas HTTP.
+pub(crate) fn otp_error_response(e: &OtpError) -> HttpResponse {
+    match e {
+        OtpError::InvalidCode { remaining_attempts } => HttpResponse::Unauthorized().json(json!({
+            "error": "Invalid code",
+            "remaining_attempts": remaining_attempts
+        })),
+        OtpError::Expired => HttpResponse::Unauthorized().json(json!({
+            "error": "Code expired or too many attempts"
+        })),
+        OtpError::RateLimited { retry_after } => HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", retry_after.to_string()))
+            .json(json!({
+                "error": "Too many codes requested",
+                "retry_after": retry_after
+            })),
+        OtpError::Unavailable | OtpError::ChannelUnavailable(_) => {
+            log::error!("{}", e);
+            HttpResponse::ServiceUnavailable().json(json!({
+                "error": "Second factor temporarily unavailable"
+            }))
+        }
+        OtpError::Send(_) => {
+            log::error!("{}", e);
+            HttpResponse::BadGateway().json(json!({
+                "error": "Could not deliver the code"
+            }))
+        }
+        OtpError::Redis(_) => {
+            log::error!("{}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Redis error"
+            }))
+        }
+    }
+}
+
+/// Paso pendiente de MFA compartido por todos los métodos de login: envía el
+/// código y devuelve el `mfa_token` para `POST /auth/mfa/verify`.
+pub(crate) async fn challenge_response(
+    pool: &PgPool,
+    otp: &OtpService,
+    context: &RequestContext,
+    user: &User,
+    factor: &OtpFactor,
+    amr: &[String],
+) -> HttpResponse {
+    let mfa_token = match otp.start_login(user.id, factor, amr).await {
+        Ok(mfa_token) => mfa_token,
+        Err(e) => return otp_error_response(&e),
+    };
+
+    audit::record(
+        pool,
+        Some(user.id),
+        AuthEventType::MfaChallenge,
+        Outcome::Success,
+        context,
+        json!({
+            "channel": factor.channel
+        }),
+    )
+    .await;
+
+    HttpResponse::Accepted().json(json!({
+        "mfa_required": true,
+        "mfa_token": mfa_token,
+        "channel": factor.channel,
+        "destination": factor.masked_destination(),
+        "expires_in": otp.ttl_seconds()
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/verify",
+    params(LoginQuery),
+    request_body = MfaVerify,
+    responses(
+        (status = 200, description = "Second factor verified; login successful", body = TokenResponse),
+        (status = 401, description = "Invalid or expired code"),
+        (status = 503, description = "Second factor temporarily unavailable")
+    ),
+    tag = "auth"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn verify(
+    req: HttpRequest,
+    query: web::Query<LoginQuery>,
+    body: web::Json<MfaVerify>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+    otp: web::Data<OtpService>,
+    cookies: web::Data<CookieSettings>,
+    jwt_secret: web::Data<String>,
+) -> impl Responder {
+    let context = RequestContext::from_request(&req);
+
+    let (user_id, amr) = match otp.verify_login(&body.mfa_token, &body.code).await {
+        Ok(result) => result,
+        Err(e) => {
+            if matches!(e, OtpError::InvalidCode { .. } | OtpError::Expired) {
+                audit::record(
+                    &pool,
+                    None,
+                    AuthEventType::Login,
+                    Outcome::Failure,
+                    &context,
+                    json!({
+                        "reason": "invalid_otp"
+                    }),
+                )
+                .await;
+            }
+            return otp_error_response(&e);
+        }
+    };
+
+    let (user, session) = match auth.complete_mfa(user_id, amr).await {
+        Ok(result) => result,
+        Err(e) => return auth_error_response(&e),
+    };
+
+    audit::record(
+        &pool,
+        Some(user.id),
+        AuthEventType::Login,
+        Outcome::Success,
+        &context,
+        json!({
+            "method": "otp"
+        }),
+    )
+    .await;
+
+    login_response(&user, &session, query.mode.unwrap_or_default(), &cookies, &jwt_secret)
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/resend",
+    request_body = MfaResend,
+    responses(
+        (status = 202, description = "A new code has been sent"),
+        (status = 401, description = "Login step expired; start again"),
+        (status = 429, description = "Too many codes requested")
+    ),
+    tag = "auth"
+)]
+pub async fn resend(body: web::Json<MfaResend>, otp: web::Data<OtpService>) -> impl Responder {
+    match otp.resend_login(&body.mfa_token).await {
+        Ok(()) => HttpResponse::Accepted().json(json!({
+            "message": "Code sent",
+            "expires_in": otp.ttl_seconds()
+        })),
+        Err(e) => otp_error_response(&e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/otp",
+    request_body = EnrollOtp,
+    responses(
+        (status = 202, description = "Confirmation code sent to the destination"),
+        (status = 400, description = "Invalid input"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Recent authentication required (`reauth_required`)"),
+        (status = 429, description = "Too many codes requested")
+    ),
+    tag = "auth"
+)]
+pub async fn enroll_otp(
+    req: HttpRequest,
+    body: web::Json<EnrollOtp>,
+    otp: web::Data<OtpService>,
+    users: web::Data<dyn UserRepository>,
+) -> impl Responder {
+    let session = match require_recent_auth(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+    if let Err(errors) = body.validate() {
+        return HttpResponse::BadRequest().json(json!({ "errors": errors }));
+    }
+
+    let destination = match (body.channel, body.normalized_phone()) {
+        (OtpChannel::Email, _) => session.email.clone(),
+        (OtpChannel::Sms, Some(phone)) => phone,
+        // Sin número explícito se usa el teléfono verificado del perfil
+        (OtpChannel::Sms, None) => match users.profile(session.user_id).await {
+            Ok(Some(profile)) if profile.phone_verified_at.is_some() => {
+                profile.phone_number.unwrap_or_default()
+            }
+            Ok(_) => {
+                return HttpResponse::BadRequest().json(json!({
+                    "error": "A phone number is required for SMS"
+                }));
+            }
+            Err(e) => {
+                log::error!("Database error: {}", e);
+                return HttpResponse::InternalServerError().json(json!({
+                    "error": "Database error"
+                }));
+            }
+        },
+    };
+    if !otp.supports(body.channel) {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("Channel {} is not available", body.channel)
+        }));
+    }
+
+    let factor = OtpFactor {
+        channel: body.channel,
+        destination,
+    };
+    match otp.start_enrollment(session.user_id, &factor).await {
+        Ok(()) => HttpResponse::Accepted().json(json!({
+            "message": "Confirmation code sent",
+            "destination": factor.masked_destination(),
+            "expires_in": otp.ttl_seconds()
+        })),
+        Err(e) => otp_error_response(&e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/otp/confirm",
+    request_body = ConfirmOtp,
+    responses(
+        (status = 200, description = "Second factor enabled"),
+        (status = 401, description = "Unauthorized or invalid code")
+    ),
+    tag = "auth"
+)]
+pub async fn confirm_otp(
+    req: HttpRequest,
+    body: web::Json<ConfirmOtp>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+    otp: web::Data<OtpService>,
+) -> impl Responder {
+    let session = match current_session(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+
+    let factor = match otp.confirm_enrollment(session.user_id, &body.code).await {
+        Ok(factor) => factor,
+        Err(e) => return otp_error_response(&e),
+    };
+    if let Err(e) = auth.enable_otp_factor(session.user_id, &factor).await {
+        return auth_error_response(&e);
+    }
+
+    audit::record(
+        &pool,
+        Some(session.user_id),
+        AuthEventType::MfaEnabled,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "channel": factor.channel
+        }),
+    )
+    .await;
+
+    HttpResponse::Ok().json(json!({
+        "message": "Second factor enabled",
+        "channel": factor.channel,
+        "destination": factor.masked_destination()
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/auth/mfa/otp",
+    responses(
+        (status = 200, description = "Second factor disabled"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Recent authentication required (`reauth_required`)"),
+        (status = 404, description = "No second factor enabled")
+    ),
+    tag = "auth"
+)]
+pub async fn disable_otp(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+) -> impl Responder {
+    let session = match require_recent_auth(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+
+    match auth.disable_otp_factor(session.user_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "No second factor enabled"
+            }));
+        }
+        Err(e) => return auth_error_response(&e),
+    }
+
+    audit::record(
+        &pool,
+        Some(session.user_id),
+        AuthEventType::MfaDisabled,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({}),
+    )
+    .await;
+
+    HttpResponse::Ok().json(json!({
+        "message": "Second factor disabled"
+    }))
+}
@@ -1,2 +1,10 @@
+pub mod admin;
+pub mod api_keys;
 pub mod auth;
-pub mod profile; 
\ No newline at end of file
+pub mod magic_link;
+pub mod media;
+pub mod mfa;
+pub mod oauth;
+pub mod oidc;
+pub mod organizations;
+pub mod profile;
\ No newline at end of file
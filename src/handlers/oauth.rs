@@ -0,0 +1,260 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::config::oauth::OAuthClients;
+use crate::config::session::SessionSettings;
+use crate::config::tenants::Tenant;
+use crate::middleware::auth::{verify_api_key, verify_session_token, ApiKeyError, SessionError};
+use crate::services::api_key;
+use crate::services::audit::{self, AuthEventType, Outcome, RequestContext};
+use crate::services::session::SessionStore;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/oauth")
+            .route("/introspect", web::post().to(introspect))
+            .route("/revoke", web::post().to(revoke)),
+    );
+}
+
+/// Cuerpo `application/x-www-form-urlencoded` de RFC 7662 y RFC 7009.
+///
+/// El cliente puede autenticarse con HTTP Basic o con `client_id`/`client_secret` en el formulario.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TokenRequest {
+    pub token: String,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    /// Última autenticación del usuario en la sesión (login o reautenticación)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amr: Option<Vec<String>>,
+}
+
+fn authenticate_client(
+    clients: &OAuthClients,
+    basic: Option<&BasicAuth>,
+    form: &TokenRequest,
+) -> Result<String, HttpResponse> {
+    let credentials = match basic {
+        Some(basic) => Some((basic.user_id(), basic.password().unwrap_or_default())),
+        None => form
+            .client_id
+            .as_deref()
+            .map(|id| (id, form.client_secret.as_deref().unwrap_or_default())),
+    };
+
+    match credentials {
+        Some((client_id, client_secret)) if clients.authenticate(client_id, client_secret) => {
+            Ok(client_id.to_string())
+        }
+        _ => Err(HttpResponse::Unauthorized()
+            .append_header(("WWW-Authenticate", "Basic realm=\"oauth\""))
+            .json(json!({
+                "error": "invalid_client"
+            }))),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/introspect",
+    request_body(content = TokenRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Token introspection result (RFC 7662)", body = IntrospectionResponse),
+        (status = 401, description = "Invalid client credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "oauth"
+)]
+pub async fn introspect(
+    basic: Option<BasicAuth>,
+    form: web::Form<TokenRequest>,
+    clients: web::Data<OAuthClients>,
+    pool: web::Data<PgPool>,
+    sessions: web::Data<dyn SessionStore>,
+    settings: web::Data<SessionSettings>,
+    tenant: web::Data<Tenant>,
+) -> impl Responder {
+    if let Err(response) = authenticate_client(&clients, basic.as_ref(), &form) {
+        return response;
+    }
+
+    log::debug!("Introspecting token (hint: {:?})", form.token_type_hint);
+
+    // Este servicio no emite refresh tokens: cualquier token que no sea una API key
+    // se trata como token de acceso y, si no corresponde a una sesión, es inactivo.
+    if api_key::parse_prefix(&form.token).is_some() {
+        return match verify_api_key(&pool, &tenant.id, &form.token).await {
+            Ok(stored) => HttpResponse::Ok().json(IntrospectionResponse {
+                active: true,
+                scope: Some(stored.scopes.join(" ")),
+                token_type: Some("ApiKey".to_string()),
+                exp: stored.expires_at.map(|exp| exp.timestamp()),
+                sub: Some(stored.user_id.to_string()),
+                ..Default::default()
+            }),
+            Err(ApiKeyError::Database(e)) => {
+                log::error!("Database error: {}", e);
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "Database error"
+                }))
+            }
+            Err(_) => HttpResponse::Ok().json(IntrospectionResponse::default()),
+        };
+    }
+
+    match verify_session_token(&tenant, sessions.get_ref(), &settings, &form.token).await {
+        Ok(active) => HttpResponse::Ok().json(IntrospectionResponse {
+            active: true,
+            username: Some(active.session.email),
+            token_type: Some("Bearer".to_string()),
+            exp: Some(active.claims.exp),
+            iat: Some(active.session.created_at),
+            sub: Some(active.claims.sub.to_string()),
+            iss: active.claims.iss,
+            auth_time: Some(active.session.auth_time),
+            amr: Some(active.session.amr),
+            ..Default::default()
+        }),
+        Err(e @ SessionError::Store) => {
+            HttpResponse::InternalServerError().json(json!({
+                "error": e.message()
+            }))
+        }
+        Err(e) => {
+            log::debug!("Inactive token on introspection: {}", e.message());
+            HttpResponse::Ok().json(IntrospectionResponse::default())
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/revoke",
+    request_body(content = TokenRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Token revoked or already invalid (RFC 7009)"),
+        (status = 401, description = "Invalid client credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "oauth"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn revoke(
+    req: HttpRequest,
+    basic: Option<BasicAuth>,
+    form: web::Form<TokenRequest>,
+    clients: web::Data<OAuthClients>,
+    pool: web::Data<PgPool>,
+    sessions: web::Data<dyn SessionStore>,
+    settings: web::Data<SessionSettings>,
+    tenant: web::Data<Tenant>,
+) -> impl Responder {
+    let client_id = match authenticate_client(&clients, basic.as_ref(), &form) {
+        Ok(client_id) => client_id,
+        Err(response) => return response,
+    };
+    let context = RequestContext::from_request(&req);
+
+    log::debug!("Revoking token (hint: {:?})", form.token_type_hint);
+
+    // RFC 7009: un token inválido o desconocido también responde 200
+    if api_key::parse_prefix(&form.token).is_some() {
+        return match verify_api_key(&pool, &tenant.id, &form.token).await {
+            Ok(stored) => {
+                match sqlx::query("UPDATE api_keys SET revoked_at = now() WHERE id = $1")
+                    .bind(stored.id)
+                    .execute(&**pool)
+                    .await
+                {
+                    Ok(_) => {
+                        audit::record(
+                            &pool,
+                            Some(stored.user_id),
+                            AuthEventType::ApiKeyRevoked,
+                            Outcome::Success,
+                            &context,
+                            json!({
+                                "api_key_id": stored.id,
+                                "client_id": client_id
+                            }),
+                        )
+                        .await;
+                        HttpResponse::Ok().finish()
+                    }
+                    Err(e) => {
+                        log::error!("Database error: {}", e);
+                        HttpResponse::InternalServerError().json(json!({
+                            "error": "Database error"
+                        }))
+                    }
+                }
+            }
+            Err(ApiKeyError::Database(e)) => {
+                log::error!("Database error: {}", e);
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "Database error"
+                }))
+            }
+            Err(_) => HttpResponse::Ok().finish(),
+        };
+    }
+
+    let active = match verify_session_token(&tenant, sessions.get_ref(), &settings, &form.token).await {
+        Ok(active) => active,
+        Err(e @ SessionError::Store) => {
+            return HttpResponse::InternalServerError().json(json!({
+                "error": e.message()
+            }));
+        }
+        Err(_) => return HttpResponse::Ok().finish(),
+    };
+
+    if let Err(e) = sessions.revoke(&active.session.id).await {
+        log::error!("Session store error: {}", e);
+        return HttpResponse::InternalServerError().json(json!({
+            "error": "Session store error"
+        }));
+    }
+
+    log::info!("Session {} revoked via /oauth/revoke", active.session.id);
+    audit::record(
+        &pool,
+        Some(active.claims.sub),
+        AuthEventType::SessionRevoked,
+        Outcome::Success,
+        &context,
+        json!({
+            "session_id": active.session.id,
+            "client_id": client_id
+        }),
+    )
+    .await;
+    HttpResponse::Ok().finish()
+}
@@ -0,0 +1,523 @@
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::config::oidc::{OidcProvider, OidcProviders, ProviderProtocol};
+use crate::config::registration::RegistrationSettings;
+use crate::config::session::CookieSettings;
+use crate::handlers::auth::{
+    auth_error_response, current_session, login_response, reauthenticated_response,
+};
+use crate::handlers::mfa;
+use crate::middleware::auth::validator;
+use crate::models::session::{LoginQuery, SessionMode, AMR_OIDC};
+use crate::models::user::User;
+use crate::services::audit::{self, AuthEventType, Outcome, RequestContext};
+use crate::services::crypto::random_token;
+use crate::services::auth::{AuthError, AuthService, LoginOutcome};
+use crate::services::oidc::{self, AuthorizationState, ExternalIdentity};
+use crate::services::otp::OtpService;
+use crate::services::registration;
+use crate::services::users::{RepositoryError, UserRepository};
+
+const STATE_TTL_SECONDS: i64 = 600;
+const STATE_COOKIE: &str = "oidc_state";
+
+/// Se da por reciente un login en el proveedor hecho desde poco antes de la
+/// redirección, con este margen para relojes desajustados.
+const CLOCK_SKEW_SECONDS: i64 = 60;
+
+/// Se monta dentro del scope `/auth` (ver `handlers::auth::config`).
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::with_fn(validator);
+
+    cfg.service(
+        web::scope("/oidc")
+            .route("/{provider}/authorize", web::get().to(authorize))
+            .route("/{provider}/callback", web::get().to(callback))
+            .route("/{provider}/reauthenticate", web::post().to(reauthenticate).wrap(auth)),
+    );
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/{provider}/authorize",
+    params(("provider" = String, Path, description = "Configured provider name"), LoginQuery),
+    responses(
+        (status = 302, description = "Redirect to the provider authorization endpoint"),
+        (status = 404, description = "Unknown provider"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "auth"
+)]
+pub async fn authorize(
+    path: web::Path<String>,
+    query: web::Query<LoginQuery>,
+    providers: web::Data<OidcProviders>,
+    cookies: web::Data<CookieSettings>,
+    jwt_secret: web::Data<String>,
+) -> impl Responder {
+    let provider = match providers.get(&path) {
+        Some(provider) => provider,
+        None => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "Unknown provider"
+            }));
+        }
+    };
+
+    // El modo de sesión viaja en el state hasta el callback
+    let mut auth_state = AuthorizationState::new(&provider.name, STATE_TTL_SECONDS);
+    auth_state.mode = query.mode.unwrap_or_default();
+    let url = match oidc::authorization_url(provider, &auth_state) {
+        Ok(url) => url,
+        Err(e) => {
+            log::error!("Error building authorization URL for {}: {}", provider.name, e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error"
+            }));
+        }
+    };
+
+    // El state, el nonce y el verificador PKCE vuelven en la cookie firmada
+    HttpResponse::Found()
+        .append_header(("Location", url))
+        .cookie(state_cookie(&cookies, auth_state.seal(&jwt_secret), STATE_TTL_SECONDS))
+        .finish()
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/oidc/{provider}/reauthenticate",
+    params(("provider" = String, Path, description = "Configured OIDC provider name")),
+    responses(
+        (status = 200, description = "Authorization URL to open; the callback confirms the identity and returns the reissued token"),
+        (status = 400, description = "The provider cannot confirm a recent login (OAuth2 without id_token)"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Unknown provider"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "auth"
+)]
+pub async fn reauthenticate(
+    req: HttpRequest,
+    path: web::Path<String>,
+    providers: web::Data<OidcProviders>,
+    cookies: web::Data<CookieSettings>,
+    jwt_secret: web::Data<String>,
+) -> impl Responder {
+    let session = match current_session(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+    let provider = match providers.get(&path) {
+        Some(provider) => provider,
+        None => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "Unknown provider"
+            }));
+        }
+    };
+    // Sin id_token no hay `auth_time` que demuestre un login reciente
+    if matches!(provider.protocol, ProviderProtocol::OAuth2) {
+        return HttpResponse::BadRequest().json(json!({
+            "error": "Provider cannot confirm a recent login"
+        }));
+    }
+
+    let mut auth_state = AuthorizationState::new(&provider.name, STATE_TTL_SECONDS);
+    auth_state.reauth_session = Some(session.id);
+    auth_state.mode = match req.cookie(&cookies.session_name) {
+        Some(_) => SessionMode::Cookie,
+        None => SessionMode::Token,
+    };
+    let url = match oidc::authorization_url(provider, &auth_state) {
+        Ok(url) => url,
+        Err(e) => {
+            log::error!("Error building authorization URL for {}: {}", provider.name, e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error"
+            }));
+        }
+    };
+
+    // La petición llega por fetch con el token: se devuelve la URL en vez de redirigir
+    HttpResponse::Ok()
+        .cookie(state_cookie(&cookies, auth_state.seal(&jwt_secret), STATE_TTL_SECONDS))
+        .json(json!({
+            "authorization_url": url
+        }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/{provider}/callback",
+    params(
+        ("provider" = String, Path, description = "Configured provider name"),
+        ("code" = String, Query, description = "Authorization code"),
+        ("state" = String, Query, description = "State issued by /authorize")
+    ),
+    responses(
+        (status = 200, description = "Login successful"),
+        (status = 400, description = "Invalid or expired state"),
+        (status = 403, description = "Provider email not verified"),
+        (status = 409, description = "Email already registered and cannot be linked"),
+        (status = 502, description = "Provider error"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "auth"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn callback(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<CallbackQuery>,
+    providers: web::Data<OidcProviders>,
+    http: web::Data<reqwest::Client>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+    users: web::Data<dyn UserRepository>,
+    otp: web::Data<OtpService>,
+    registration: web::Data<RegistrationSettings>,
+    cookies: web::Data<CookieSettings>,
+    jwt_secret: web::Data<String>,
+) -> impl Responder {
+    let provider = match providers.get(&path) {
+        Some(provider) => provider,
+        None => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "Unknown provider"
+            }));
+        }
+    };
+
+    // El state es de un solo uso: la cookie se borra con cualquier resultado
+    let auth_state = req
+        .cookie(STATE_COOKIE)
+        .and_then(|cookie| AuthorizationState::open(cookie.value(), &jwt_secret));
+
+    let mut response = match (&query.error, &query.code, &query.state) {
+        (Some(error), _, _) => {
+            log::error!("Provider {} returned error: {}", provider.name, error);
+            HttpResponse::BadRequest().json(json!({
+                "error": "Authorization denied by provider"
+            }))
+        }
+        (None, Some(code), Some(state)) => match auth_state {
+            Some(auth_state) if auth_state.provider == provider.name && auth_state.state == *state => {
+                let context = RequestContext::from_request(&req);
+                match &auth_state.reauth_session {
+                    Some(session_id) => {
+                        confirm_identity(
+                            &context,
+                            provider,
+                            code,
+                            &auth_state,
+                            session_id,
+                            &http,
+                            &pool,
+                            &auth,
+                            users.get_ref(),
+                            &cookies,
+                        )
+                        .await
+                    }
+                    None => {
+                        sign_in(
+                            &context,
+                            provider,
+                            code,
+                            &auth_state,
+                            &http,
+                            &pool,
+                            &auth,
+                            users.get_ref(),
+                            &otp,
+                            &registration,
+                            &cookies,
+                            &jwt_secret,
+                        )
+                        .await
+                    }
+                }
+            }
+            _ => HttpResponse::BadRequest().json(json!({
+                "error": "Invalid or expired state"
+            })),
+        },
+        _ => HttpResponse::BadRequest().json(json!({
+            "error": "Missing code or state"
+        })),
+    };
+
+    if let Err(e) = response.add_cookie(&state_cookie(&cookies, String::new(), 0)) {
+        log::error!("Error clearing OIDC state cookie: {}", e);
+    }
+    response
+}
+
+/// Canjea el código, resuelve el usuario local y abre la sesión (o pide el segundo factor).
+#[allow(clippy::too_many_arguments)]
+async fn sign_in(
+    context: &RequestContext,
+    provider: &OidcProvider,
+    code: &str,
+    auth_state: &AuthorizationState,
+    http: &reqwest::Client,
+    pool: &PgPool,
+    auth: &AuthService,
+    users: &dyn UserRepository,
+    otp: &OtpService,
+    registration: &RegistrationSettings,
+    cookies: &CookieSettings,
+    jwt_secret: &str,
+) -> HttpResponse {
+    let identity = match oidc::fetch_identity(http, provider, code, auth_state).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            log::error!("OIDC error with provider {}: {}", provider.name, e);
+            return HttpResponse::BadGateway().json(json!({
+                "error": "Could not authenticate with provider"
+            }));
+        }
+    };
+
+    let user = match find_or_link_user(users, auth, &provider.name, &identity, registration).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+
+    let (user, session) = match auth.begin_session(user, vec![AMR_OIDC.to_string()]).await {
+        Ok(LoginOutcome::Authenticated(user, session)) => (user, session),
+        Ok(LoginOutcome::MfaRequired(user, factor, amr)) => {
+            return mfa::challenge_response(pool, otp, context, &user, &factor, &amr).await;
+        }
+        Err(e) => return auth_error_response(&e),
+    };
+
+    audit::record(
+        pool,
+        Some(user.id),
+        AuthEventType::Login,
+        Outcome::Success,
+        context,
+        json!({
+            "method": "oidc",
+            "provider": provider.name
+        }),
+    )
+    .await;
+
+    login_response(&user, &session, auth_state.mode, cookies, jwt_secret)
+}
+
+/// Reautenticación con el proveedor: la identidad debe estar vinculada al
+/// usuario de la sesión y el id_token debe traer un login posterior a la
+/// redirección.
+#[allow(clippy::too_many_arguments)]
+async fn confirm_identity(
+    context: &RequestContext,
+    provider: &OidcProvider,
+    code: &str,
+    auth_state: &AuthorizationState,
+    session_id: &str,
+    http: &reqwest::Client,
+    pool: &PgPool,
+    auth: &AuthService,
+    users: &dyn UserRepository,
+    cookies: &CookieSettings,
+) -> HttpResponse {
+    let session = match auth.find_session(session_id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return auth_error_response(&AuthError::SessionNotFound),
+        Err(e) => return auth_error_response(&e),
+    };
+
+    let identity = match oidc::fetch_identity(http, provider, code, auth_state).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            log::error!("OIDC error with provider {}: {}", provider.name, e);
+            return HttpResponse::BadGateway().json(json!({
+                "error": "Could not authenticate with provider"
+            }));
+        }
+    };
+
+    let linked = match users.find_by_identity(&provider.name, &identity.subject).await {
+        Ok(linked) => linked,
+        Err(e) => return repository_error(e),
+    };
+    let fresh = identity
+        .auth_time
+        .is_some_and(|auth_time| auth_time >= auth_state.issued_at - CLOCK_SKEW_SECONDS);
+
+    let reason = match linked {
+        Some(user) if user.id != session.user_id => Some("identity_not_linked"),
+        None => Some("identity_not_linked"),
+        Some(_) if !fresh => Some("stale_provider_login"),
+        Some(_) => None,
+    };
+    if let Some(reason) = reason {
+        audit::record(
+            pool,
+            Some(session.user_id),
+            AuthEventType::Reauthenticated,
+            Outcome::Failure,
+            context,
+            json!({
+                "reason": reason,
+                "provider": provider.name
+            }),
+        )
+        .await;
+        return match reason {
+            "stale_provider_login" => HttpResponse::Unauthorized().json(json!({
+                "error": "Provider did not confirm a recent login"
+            })),
+            _ => HttpResponse::Forbidden().json(json!({
+                "error": "External identity is not linked to this account"
+            })),
+        };
+    }
+
+    let session = match auth.confirm_reauthentication(&session, AMR_OIDC).await {
+        Ok(session) => session,
+        Err(e) => return auth_error_response(&e),
+    };
+
+    audit::record(
+        pool,
+        Some(session.user_id),
+        AuthEventType::Reauthenticated,
+        Outcome::Success,
+        context,
+        json!({
+            "session_id": session.id,
+            "method": AMR_OIDC,
+            "provider": provider.name
+        }),
+    )
+    .await;
+
+    reauthenticated_response(&session, auth_state.mode, cookies)
+}
+
+/// Resuelve el usuario local del tenant para una identidad externa.
+///
+/// Orden: identidad ya vinculada, usuario existente con el mismo correo
+/// verificado, o un usuario nuevo sin contraseña utilizable (solo si el
+/// registro abierto está activado y el dominio del correo es admitido).
+async fn find_or_link_user(
+    users: &dyn UserRepository,
+    auth: &AuthService,
+    provider: &str,
+    identity: &ExternalIdentity,
+    settings: &RegistrationSettings,
+) -> Result<User, HttpResponse> {
+    let linked = users
+        .find_by_identity(provider, &identity.subject)
+        .await
+        .map_err(repository_error)?;
+
+    if let Some(user) = linked {
+        return Ok(user);
+    }
+
+    let email = match (&identity.email, identity.email_verified) {
+        (Some(email), true) => email,
+        (Some(_), false) => {
+            return Err(HttpResponse::Forbidden().json(json!({
+                "error": "Provider email is not verified"
+            })));
+        }
+        (None, _) => {
+            return Err(HttpResponse::BadRequest().json(json!({
+                "error": "Provider did not return an email"
+            })));
+        }
+    };
+
+    let existing = users.find_by_email(email).await.map_err(repository_error)?;
+
+    let user = match existing {
+        Some(user) => user,
+        None if !settings.self_registration => {
+            return Err(HttpResponse::Forbidden().json(json!({
+                "error": "Self-registration is disabled"
+            })));
+        }
+        None => {
+            if let Err(rejection) = registration::check_email_domain(settings, email) {
+                return Err(HttpResponse::Forbidden().json(json!({
+                    "error": rejection.to_string()
+                })));
+            }
+
+            // Contraseña aleatoria: la cuenta solo puede entrar vía el proveedor
+            let unusable_password = auth
+                .hash_password(&random_token(48))
+                .await
+                .map_err(|e| auth_error_response(&e))?;
+            let name = identity.name.clone().unwrap_or_else(|| email.clone());
+
+            let user = users
+                .create_with_identity(email, &unusable_password, &name, provider, &identity.subject)
+                .await
+                .map_err(repository_error)?;
+
+            log::info!("Registered user {} through {}", user.id, provider);
+            return Ok(user);
+        }
+    };
+
+    let linked = users
+        .link_identity(user.id, provider, &identity.subject, email)
+        .await
+        .map_err(repository_error)?;
+
+    if !linked {
+        // El usuario ya tiene otra cuenta de este proveedor vinculada
+        return Err(HttpResponse::Conflict().json(json!({
+            "error": "Account is already linked to a different identity from this provider"
+        })));
+    }
+
+    log::info!("Linked {} identity to user {}", provider, user.id);
+    Ok(user)
+}
+
+/// HttpOnly y `SameSite=Lax` siempre: el callback llega como navegación desde
+/// el proveedor y con `Strict` el navegador no enviaría la cookie.
+fn state_cookie(cookies: &CookieSettings, value: String, max_age: i64) -> Cookie<'static> {
+    let mut cookie = cookies.cookie(STATE_COOKIE.to_string(), value, max_age, true);
+    cookie.set_same_site(SameSite::Lax);
+    cookie
+}
+
+/// Los conflictos vienen de otra petición que vinculó o registró lo mismo a la vez.
+fn repository_error(e: RepositoryError) -> HttpResponse {
+    match e {
+        RepositoryError::IdentityTaken => HttpResponse::Conflict().json(json!({
+            "error": "External identity is already linked to another account"
+        })),
+        RepositoryError::EmailTaken => HttpResponse::Conflict().json(json!({
+            "error": "Email already registered"
+        })),
+        e => {
+            log::error!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }))
+        }
+    }
+}
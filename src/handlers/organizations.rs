@@ -0,0 +1,477 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use serde_json::json;
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::config::organizations::InvitationSettings;
+use crate::config::session::CookieSettings;
+use crate::handlers::auth::{auth_error_response, current_session};
+use crate::middleware::auth::validator;
+use crate::middleware::organization::{AnyMember, OrgAccess, OrgAdmin, OrgOwner};
+use crate::models::organization::{
+    AcceptInvitation, NewInvitation, NewOrganization, OrgRole, SwitchOrganization, UpdateMemberRole,
+};
+use crate::services::audit::{self, AuthEventType, Outcome, RequestContext};
+use crate::services::auth::AuthService;
+use crate::services::mailer::{EmailMessage, Mailer};
+use crate::services::organizations::{self, OrgError};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::with_fn(validator);
+
+    cfg.service(
+        web::scope("/organizations")
+            .wrap(auth)
+            .route("", web::post().to(create_organization))
+            .route("", web::get().to(list_organizations))
+            .route("/switch", web::post().to(switch_organization))
+            .route("/invitations/accept", web::post().to(accept_invitation))
+            .route("/current/members", web::get().to(list_members))
+            .route("/current/members/{user_id}", web::put().to(update_member_role))
+            .route("/current/members/{user_id}", web::delete().to(remove_member))
+            .route("/current/invitations", web::post().to(create_invitation))
+            .route("/current/invitations", web::get().to(list_invitations)),
+    );
+}
+
+fn org_error_response(e: &OrgError) -> HttpResponse {
+    match e {
+        OrgError::InvalidInvitation => HttpResponse::NotFound().json(json!({
+            "error": "Invalid or expired invitation"
+        })),
+        OrgError::EmailMismatch => HttpResponse::Forbidden().json(json!({
+            "error": "Invitation was sent to a different email"
+        })),
+        OrgError::LastOwner => HttpResponse::Conflict().json(json!({
+            "error": "An organization must keep at least one owner"
+        })),
+        OrgError::Database(_) => {
+            log::error!("{}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }))
+        }
+    }
+}
+
+fn database_error(e: sqlx::Error) -> HttpResponse {
+    log::error!("Database error: {}", e);
+    HttpResponse::InternalServerError().json(json!({
+        "error": "Database error"
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/organizations",
+    request_body = NewOrganization,
+    responses(
+        (status = 201, description = "Organization created; the caller is its owner", body = Organization),
+        (status = 400, description = "Invalid input"),
+        (status = 401, description = "Unauthorized")
+    ),
+    tag = "organizations"
+)]
+pub async fn create_organization(
+    req: HttpRequest,
+    body: web::Json<NewOrganization>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let session = match current_session(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+    if let Err(errors) = body.validate() {
+        return HttpResponse::BadRequest().json(json!({ "errors": errors }));
+    }
+
+    let context = RequestContext::from_request(&req);
+    let organization =
+        match organizations::create(&pool, &context.tenant_id, session.user_id, body.name.trim()).await {
+            Ok(organization) => organization,
+            Err(e) => return database_error(e),
+        };
+
+    audit::record(
+        &pool,
+        Some(session.user_id),
+        AuthEventType::OrganizationCreated,
+        Outcome::Success,
+        &context,
+        json!({
+            "org_id": organization.id
+        }),
+    )
+    .await;
+
+    HttpResponse::Created().json(organization)
+}
+
+#[utoipa::path(
+    get,
+    path = "/organizations",
+    responses(
+        (status = 200, description = "Organizations of the current user with their role", body = [Organization]),
+        (status = 401, description = "Unauthorized")
+    ),
+    tag = "organizations"
+)]
+pub async fn list_organizations(req: HttpRequest, pool: web::Data<PgPool>) -> impl Responder {
+    let session = match current_session(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+
+    match organizations::list_for_user(&pool, session.user_id).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(e) => database_error(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/organizations/switch",
+    request_body = SwitchOrganization,
+    responses(
+        (status = 200, description = "Active organization changed; use the new token from now on"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not a member of the organization")
+    ),
+    tag = "organizations"
+)]
+pub async fn switch_organization(
+    req: HttpRequest,
+    body: web::Json<SwitchOrganization>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthService>,
+    cookies: web::Data<CookieSettings>,
+) -> impl Responder {
+    let session = match current_session(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+
+    let role = match body.org_id {
+        Some(org_id) => match organizations::role(&pool, org_id, session.user_id).await {
+            Ok(Some(role)) => Some(role),
+            Ok(None) => {
+                return HttpResponse::Forbidden().json(json!({
+                    "error": "Not a member of the organization"
+                }));
+            }
+            Err(e) => return database_error(e),
+        },
+        None => None,
+    };
+
+    let session = match auth.switch_organization(&session, body.org_id).await {
+        Ok(session) => session,
+        Err(e) => return auth_error_response(&e),
+    };
+
+    // En modo cookie el token nuevo sustituye a la cookie; el CSRF va atado
+    // al id de sesión, que no cambia
+    if req.cookie(&cookies.session_name).is_some() {
+        let max_age = session.expires_at - session.last_seen_at;
+        return HttpResponse::Ok()
+            .cookie(cookies.session_cookie(&session.token, max_age))
+            .json(json!({
+                "session_id": session.id,
+                "org_id": session.org_id,
+                "role": role
+            }));
+    }
+
+    HttpResponse::Ok().json(json!({
+        "session_id": session.id,
+        "token": session.token,
+        "org_id": session.org_id,
+        "role": role
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/organizations/invitations/accept",
+    request_body = AcceptInvitation,
+    responses(
+        (status = 200, description = "Invitation accepted; the user is now a member"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Invitation was sent to a different email"),
+        (status = 404, description = "Invalid or expired invitation")
+    ),
+    tag = "organizations"
+)]
+pub async fn accept_invitation(
+    req: HttpRequest,
+    body: web::Json<AcceptInvitation>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let session = match current_session(&req) {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+
+    let context = RequestContext::from_request(&req);
+    let (org_id, role) = match organizations::accept(
+        &pool,
+        &context.tenant_id,
+        &body.token,
+        session.user_id,
+        &session.email,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(e) => return org_error_response(&e),
+    };
+
+    audit::record(
+        &pool,
+        Some(session.user_id),
+        AuthEventType::MemberJoined,
+        Outcome::Success,
+        &context,
+        json!({
+            "org_id": org_id,
+            "role": role
+        }),
+    )
+    .await;
+
+    HttpResponse::Ok().json(json!({
+        "org_id": org_id,
+        "role": role
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/organizations/current/members",
+    responses(
+        (status = 200, description = "Members of the active organization", body = [Member]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "No active organization or not a member")
+    ),
+    tag = "organizations"
+)]
+pub async fn list_members(access: OrgAccess<AnyMember>, pool: web::Data<PgPool>) -> impl Responder {
+    match organizations::members(&pool, access.org_id).await {
+        Ok(members) => HttpResponse::Ok().json(members),
+        Err(e) => database_error(e),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/organizations/current/members/{user_id}",
+    params(("user_id" = i64, Path, description = "Member user id")),
+    request_body = UpdateMemberRole,
+    responses(
+        (status = 200, description = "Role updated"),
+        (status = 403, description = "Only owners can change roles"),
+        (status = 404, description = "Not a member"),
+        (status = 409, description = "The organization would be left without owners")
+    ),
+    tag = "organizations"
+)]
+pub async fn update_member_role(
+    req: HttpRequest,
+    access: OrgAccess<OrgOwner>,
+    path: web::Path<i64>,
+    body: web::Json<UpdateMemberRole>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let user_id = path.into_inner();
+
+    match organizations::update_role(&pool, access.org_id, user_id, body.role).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "Member not found"
+            }));
+        }
+        Err(e) => return org_error_response(&e),
+    }
+
+    audit::record(
+        &pool,
+        Some(access.user_id),
+        AuthEventType::MemberRoleChanged,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "org_id": access.org_id,
+            "member_id": user_id,
+            "role": body.role
+        }),
+    )
+    .await;
+
+    HttpResponse::Ok().json(json!({
+        "user_id": user_id,
+        "role": body.role
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/organizations/current/members/{user_id}",
+    params(("user_id" = i64, Path, description = "Member user id; your own id to leave")),
+    responses(
+        (status = 200, description = "Member removed"),
+        (status = 403, description = "Insufficient organization role"),
+        (status = 404, description = "Not a member"),
+        (status = 409, description = "The organization would be left without owners")
+    ),
+    tag = "organizations"
+)]
+pub async fn remove_member(
+    req: HttpRequest,
+    access: OrgAccess<AnyMember>,
+    path: web::Path<i64>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let user_id = path.into_inner();
+
+    // Cualquiera puede salir; quitar a otros exige admin, y a un owner, ser owner
+    if user_id != access.user_id {
+        let target = match organizations::role(&pool, access.org_id, user_id).await {
+            Ok(Some(role)) => role,
+            Ok(None) => {
+                return HttpResponse::NotFound().json(json!({
+                    "error": "Member not found"
+                }));
+            }
+            Err(e) => return database_error(e),
+        };
+        if access.role < OrgRole::Admin || target > access.role {
+            return HttpResponse::Forbidden().json(json!({
+                "error": "Insufficient organization role"
+            }));
+        }
+    }
+
+    match organizations::remove_member(&pool, access.org_id, user_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "Member not found"
+            }));
+        }
+        Err(e) => return org_error_response(&e),
+    }
+
+    audit::record(
+        &pool,
+        Some(access.user_id),
+        AuthEventType::MemberRemoved,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "org_id": access.org_id,
+            "member_id": user_id
+        }),
+    )
+    .await;
+
+    HttpResponse::Ok().json(json!({
+        "message": "Member removed"
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/organizations/current/invitations",
+    request_body = NewInvitation,
+    responses(
+        (status = 201, description = "Invitation created and emailed", body = Invitation),
+        (status = 400, description = "Invalid input"),
+        (status = 403, description = "Insufficient organization role")
+    ),
+    tag = "organizations"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_invitation(
+    req: HttpRequest,
+    access: OrgAccess<OrgAdmin>,
+    body: web::Json<NewInvitation>,
+    pool: web::Data<PgPool>,
+    mailer: web::Data<dyn Mailer>,
+    settings: web::Data<InvitationSettings>,
+) -> impl Responder {
+    if let Err(errors) = body.validate() {
+        return HttpResponse::BadRequest().json(json!({ "errors": errors }));
+    }
+    // Nadie concede más privilegios de los que tiene
+    if body.role > access.role {
+        return HttpResponse::Forbidden().json(json!({
+            "error": "Cannot invite with a role higher than your own"
+        }));
+    }
+
+    let (invitation, token) = match organizations::invite(
+        &pool,
+        access.org_id,
+        access.user_id,
+        &body.email,
+        body.role,
+        settings.ttl,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(e) => return database_error(e),
+    };
+
+    let message = EmailMessage {
+        to: invitation.email.clone(),
+        subject: "You have been invited to an organization".to_string(),
+        body: format!(
+            "You have been invited to join an organization as {}.\n\n\
+             Sign in or create an account with this email address, then open:\n\n{}?token={}\n\n\
+             The invitation expires on {}.\n",
+            invitation.role,
+            settings.url,
+            token,
+            invitation.expires_at.format("%Y-%m-%d %H:%M UTC")
+        ),
+    };
+    if let Err(e) = mailer.send(&message).await {
+        // La invitación queda creada; puede volver a enviarse creando otra
+        log::error!("Error sending invitation {}: {}", invitation.id, e);
+    }
+
+    audit::record(
+        &pool,
+        Some(access.user_id),
+        AuthEventType::MemberInvited,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "org_id": access.org_id,
+            "invitation_id": invitation.id,
+            "role": invitation.role
+        }),
+    )
+    .await;
+
+    HttpResponse::Created().json(invitation)
+}
+
+#[utoipa::path(
+    get,
+    path = "/organizations/current/invitations",
+    responses(
+        (status = 200, description = "Invitations of the active organization, newest first", body = [Invitation]),
+        (status = 403, description = "Insufficient organization role")
+    ),
+    tag = "organizations"
+)]
+pub async fn list_invitations(access: OrgAccess<OrgAdmin>, pool: web::Data<PgPool>) -> impl Responder {
+    match organizations::invitations(&pool, access.org_id).await {
+        Ok(invitations) => HttpResponse::Ok().json(invitations),
+        Err(e) => database_error(e),
+    }
+}
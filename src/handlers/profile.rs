@@ -1,18 +1,48 @@
-use actix_web::{web, HttpResponse, Responder, HttpMessage};
+use actix_multipart::form::MultipartForm;
+use actix_web::{web, HttpResponse, Responder};
 use actix_web_httpauth::middleware::HttpAuthentication;
+use chrono::Utc;
 use serde_json::json;
 use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
 
-use crate::middleware::auth::validator;
-use crate::models::user::User;
+use crate::config::profile::ProfileSettings;
+use crate::config::session::CookieSettings;
+use crate::config::tenants::Tenant;
+use crate::middleware::auth::{authenticate, current_user_id, require_recent_auth, require_scope};
+use crate::models::auth_event::{page_limit, ActivityQuery, AuthEvent};
+use crate::models::data_export::{DataExport, ExportStatus};
+use crate::models::otp::{ConfirmOtp, OtpChannel, OtpFactor};
+use crate::models::profile::{normalize_phone, AvatarUpload, UpdateProfile};
+use crate::handlers::auth::auth_error_response;
+use crate::handlers::mfa::otp_error_response;
+use crate::services::audit::{self, AuthEventType, Outcome, RequestContext};
+use crate::services::auth::AuthService;
+use crate::services::avatar::{self, AvatarError};
+use crate::services::blob_store::BlobStore;
+use crate::services::data_export;
+use crate::services::otp::OtpService;
+use crate::services::profile;
+use crate::services::users::{RepositoryError, UserRepository};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
-    let auth = HttpAuthentication::bearer(validator);
+    let auth = HttpAuthentication::with_fn(authenticate);
     
     cfg.service(
         web::scope("/profile")
             .wrap(auth)
-            .route("", web::get().to(get_profile)),
+            .route("", web::get().to(get_profile))
+            .route("", web::patch().to(update_profile))
+            .route("", web::delete().to(delete_account))
+            .route("/avatar", web::put().to(upload_avatar))
+            .route("/avatar", web::delete().to(delete_avatar))
+            .route("/phone/verification", web::post().to(send_phone_code))
+            .route("/phone/verification/confirm", web::post().to(confirm_phone))
+            .route("/activity", web::get().to(get_activity))
+            .route("/export", web::post().to(request_export))
+            .route("/export/{id}", web::get().to(get_export))
+            .route("/export/{id}/download", web::get().to(download_export)),
     );
 }
 
@@ -20,8 +50,9 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     get,
     path = "/profile",
     responses(
-        (status = 200, description = "Profile retrieved successfully", body = User),
+        (status = 200, description = "Profile retrieved successfully", body = Profile),
         (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -29,36 +60,193 @@ pub fn config(cfg: &mut web::ServiceConfig) {
 )]
 pub async fn get_profile(
     req: actix_web::HttpRequest,
+    users: web::Data<dyn UserRepository>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "profile:read") {
+        return response;
+    }
+    let user_id = match current_user_id(&req) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match users.profile(user_id).await {
+        Ok(Some(profile)) => HttpResponse::Ok().json(profile),
+        Ok(None) => {
+            log::error!("User not found with ID: {}", user_id);
+            HttpResponse::NotFound().json(json!({
+                "error": "User not found"
+            }))
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/profile",
+    request_body = UpdateProfile,
+    responses(
+        (status = 200, description = "Profile updated", body = Profile),
+        (status = 400, description = "Invalid input or metadata rejected by the schema"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "profile"
+)]
+pub async fn update_profile(
+    req: actix_web::HttpRequest,
+    body: web::Json<UpdateProfile>,
+    users: web::Data<dyn UserRepository>,
+    settings: web::Data<ProfileSettings>,
+    blobs: web::Data<dyn BlobStore>,
     pool: web::Data<PgPool>,
 ) -> impl Responder {
-    log::debug!("Getting profile for request");
-    log::debug!("Request extensions: {:?}", req.extensions().get::<i64>());
-    
-    let user_id = match req.extensions().get::<i64>() {
-        Some(id) => {
-            log::debug!("Found user ID in extensions: {}", id);
-            *id
-        },
-        None => {
-            log::error!("User ID not found in request extensions");
+    if let Err(response) = require_scope(&req, "profile:write") {
+        return response;
+    }
+    let user_id = match current_user_id(&req) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+    if let Err(errors) = body.validate() {
+        return HttpResponse::BadRequest().json(json!({ "errors": errors }));
+    }
+
+    let mut update = body.into_inner();
+    if let Some(Some(phone)) = &mut update.phone_number {
+        if let Some(normalized) = normalize_phone(phone) {
+            *phone = normalized;
+        }
+    }
+
+    // El perfil actual hace falta para fusionar `metadata` y para borrar el avatar subido
+    let current = if update.metadata.is_some() || update.avatar_url.is_some() {
+        match users.profile(user_id).await {
+            Ok(Some(profile)) => Some(profile),
+            Ok(None) => {
+                return HttpResponse::NotFound().json(json!({
+                    "error": "User not found"
+                }));
+            }
+            Err(e) => {
+                log::error!("Database error: {}", e);
+                return HttpResponse::InternalServerError().json(json!({
+                    "error": "Database error"
+                }));
+            }
+        }
+    } else {
+        None
+    };
+
+    if let (Some(patch), Some(current)) = (&update.metadata, &current) {
+        let mut metadata = current.metadata.clone();
+        profile::merge_patch(&mut metadata, patch);
+        if let Err(errors) = profile::validate_metadata(&settings, &metadata) {
+            return HttpResponse::BadRequest().json(json!({
+                "error": "Invalid metadata",
+                "details": errors
+            }));
+        }
+        update.metadata = Some(metadata);
+    }
+
+    let updated = match users.update_profile(user_id, &update).await {
+        Ok(Some(profile)) => profile,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "User not found"
+            }));
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
             return HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
+                "error": "Database error"
             }));
         }
     };
 
-    log::debug!("Querying database for user with ID: {}", user_id);
-    
-    let user = match sqlx::query_as::<_, User>(
-        "SELECT id, email, password, name FROM users WHERE id = $1",
+    // Una URL externa (o `null`) sustituye al avatar subido
+    if update.avatar_url.is_some() {
+        if let Some(key) = current.as_ref().and_then(|p| p.avatar_key.as_deref()) {
+            delete_avatar_files(blobs.get_ref(), key).await;
+        }
+    }
+
+    let fields: Vec<&str> = [
+        ("name", update.name.is_some()),
+        ("avatar_url", update.avatar_url.is_some()),
+        ("locale", update.locale.is_some()),
+        ("timezone", update.timezone.is_some()),
+        ("phone_number", update.phone_number.is_some()),
+        ("metadata", update.metadata.is_some()),
+    ]
+    .iter()
+    .filter(|(_, changed)| *changed)
+    .map(|(field, _)| *field)
+    .collect();
+    audit::record(
+        &pool,
+        Some(user_id),
+        AuthEventType::ProfileUpdated,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "fields": fields
+        }),
     )
-    .bind(user_id)
-    .fetch_optional(&**pool)
-    .await {
-        Ok(user) => {
-            log::debug!("Database query completed");
-            user
-        },
+    .await;
+
+    HttpResponse::Ok().json(updated)
+}
+
+/// Borra los ficheros de un avatar sustituido; un fallo solo deja ficheros huérfanos.
+async fn delete_avatar_files(blobs: &dyn BlobStore, key: &str) {
+    if let Err(e) = blobs.delete_prefix(key).await {
+        log::warn!("Could not delete avatar files {}: {}", key, e);
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/profile",
+    responses(
+        (status = 204, description = "Account deleted; every session is closed"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope or recent authentication required"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "The user is the only owner of an organization with other members"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "profile"
+)]
+pub async fn delete_account(
+    req: actix_web::HttpRequest,
+    users: web::Data<dyn UserRepository>,
+    auth: web::Data<AuthService>,
+    blobs: web::Data<dyn BlobStore>,
+    pool: web::Data<PgPool>,
+    tenant: web::Data<Tenant>,
+    cookies: web::Data<CookieSettings>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "profile:write") {
+        return response;
+    }
+    let user_id = match require_recent_auth(&req) {
+        Ok(session) => session.user_id,
+        Err(response) => return response,
+    };
+
+    let avatar_key = match users.profile(user_id).await {
+        Ok(profile) => profile.and_then(|profile| profile.avatar_key),
         Err(e) => {
             log::error!("Database error: {}", e);
             return HttpResponse::InternalServerError().json(json!({
@@ -67,20 +255,634 @@ pub async fn get_profile(
         }
     };
 
-    match user {
-        Some(user) => {
-            log::debug!("User found: {:?}", user);
-            HttpResponse::Ok().json(json!({
-                "id": user.id,
-                "email": user.email,
-                "name": user.name
-            }))
-        },
-        None => {
-            log::error!("User not found with ID: {}", user_id);
-            HttpResponse::NotFound().json(json!({
+    // Borra al usuario y encola `user.deleted` en la misma transacción
+    match users.delete(user_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::NotFound().json(json!({
                 "error": "User not found"
+            }));
+        }
+        Err(RepositoryError::SoleOwner) => {
+            return HttpResponse::Conflict().json(json!({
+                "error": "Transfer the ownership of your organizations before deleting the account"
+            }));
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    }
+
+    // Las sesiones pueden vivir fuera de Postgres (Redis) y no caen en cascada
+    if let Err(e) = auth.logout_all(user_id).await {
+        log::error!("Could not revoke the sessions of deleted user {}: {}", user_id, e);
+        return auth_error_response(&e);
+    }
+    if let Some(key) = avatar_key {
+        delete_avatar_files(blobs.get_ref(), &key).await;
+    }
+    let exports = format!("exports/{}/{}", tenant.id, user_id);
+    if let Err(e) = blobs.delete_prefix(&exports).await {
+        log::warn!("Could not delete data exports {}: {}", exports, e);
+    }
+
+    audit::record(
+        &pool,
+        Some(user_id),
+        AuthEventType::AccountDeleted,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({}),
+    )
+    .await;
+
+    let mut response = HttpResponse::NoContent();
+    if req.cookie(&cookies.session_name).is_some() {
+        for cookie in cookies.removal_cookies() {
+            response.cookie(cookie);
+        }
+    }
+    response.finish()
+}
+
+#[utoipa::path(
+    put,
+    path = "/profile/avatar",
+    request_body(content = AvatarUpload, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Avatar stored; the profile includes its URL and thumbnails", body = Profile),
+        (status = 400, description = "Missing file, unsupported type or invalid image"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope"),
+        (status = 413, description = "File too large"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "profile"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_avatar(
+    req: actix_web::HttpRequest,
+    form: MultipartForm<AvatarUpload>,
+    users: web::Data<dyn UserRepository>,
+    blobs: web::Data<dyn BlobStore>,
+    settings: web::Data<ProfileSettings>,
+    tenant: web::Data<Tenant>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "profile:write") {
+        return response;
+    }
+    let user_id = match current_user_id(&req) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let previous = match users.profile(user_id).await {
+        Ok(Some(profile)) => profile.avatar_key,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "User not found"
+            }));
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+
+    let upload = form.into_inner().avatar;
+    let stored = match avatar::process(
+        blobs.get_ref(),
+        &settings,
+        &tenant.id,
+        user_id,
+        upload.content_type.as_ref().map(|mime| mime.essence_str()),
+        upload.data.to_vec(),
+    )
+    .await
+    {
+        Ok(stored) => stored,
+        Err(e @ AvatarError::TooLarge { .. }) => {
+            return HttpResponse::PayloadTooLarge().json(json!({
+                "error": e.to_string()
+            }));
+        }
+        Err(e @ (AvatarError::UnsupportedType | AvatarError::InvalidImage(_))) => {
+            return HttpResponse::BadRequest().json(json!({
+                "error": e.to_string()
+            }));
+        }
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Could not store avatar"
+            }));
+        }
+    };
+
+    let updated = match users.set_avatar(user_id, Some(&stored)).await {
+        Ok(Some(profile)) => profile,
+        result => {
+            if let Err(e) = result {
+                log::error!("Database error: {}", e);
+            }
+            delete_avatar_files(blobs.get_ref(), &stored.key).await;
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+    if let Some(key) = previous {
+        delete_avatar_files(blobs.get_ref(), &key).await;
+    }
+
+    audit::record(
+        &pool,
+        Some(user_id),
+        AuthEventType::ProfileUpdated,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "fields": ["avatar_url"]
+        }),
+    )
+    .await;
+
+    HttpResponse::Ok().json(updated)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/profile/avatar",
+    responses(
+        (status = 200, description = "Avatar removed", body = Profile),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "profile"
+)]
+pub async fn delete_avatar(
+    req: actix_web::HttpRequest,
+    users: web::Data<dyn UserRepository>,
+    blobs: web::Data<dyn BlobStore>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "profile:write") {
+        return response;
+    }
+    let user_id = match current_user_id(&req) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let previous = match users.profile(user_id).await {
+        Ok(Some(profile)) => profile.avatar_key,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "User not found"
+            }));
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+
+    let updated = match users.set_avatar(user_id, None).await {
+        Ok(Some(profile)) => profile,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "User not found"
+            }));
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+    if let Some(key) = previous {
+        delete_avatar_files(blobs.get_ref(), &key).await;
+    }
+
+    audit::record(
+        &pool,
+        Some(user_id),
+        AuthEventType::ProfileUpdated,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "fields": ["avatar_url"]
+        }),
+    )
+    .await;
+
+    HttpResponse::Ok().json(updated)
+}
+
+#[utoipa::path(
+    post,
+    path = "/profile/phone/verification",
+    responses(
+        (status = 202, description = "Verification code sent by SMS"),
+        (status = 400, description = "No phone number on the profile or SMS not available"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope"),
+        (status = 409, description = "Phone number already verified"),
+        (status = 429, description = "Too many codes requested")
+    ),
+    tag = "profile"
+)]
+pub async fn send_phone_code(
+    req: actix_web::HttpRequest,
+    users: web::Data<dyn UserRepository>,
+    otp: web::Data<OtpService>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "profile:write") {
+        return response;
+    }
+    let user_id = match current_user_id(&req) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let profile = match users.profile(user_id).await {
+        Ok(Some(profile)) => profile,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "User not found"
+            }));
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+    let phone = match (&profile.phone_number, profile.phone_verified_at) {
+        (None, _) => {
+            return HttpResponse::BadRequest().json(json!({
+                "error": "No phone number on the profile"
+            }));
+        }
+        (Some(_), Some(_)) => {
+            return HttpResponse::Conflict().json(json!({
+                "error": "Phone number already verified"
+            }));
+        }
+        (Some(phone), None) => phone,
+    };
+    if !otp.supports(OtpChannel::Sms) {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("Channel {} is not available", OtpChannel::Sms)
+        }));
+    }
+
+    match otp.start_phone_verification(user_id, phone).await {
+        Ok(()) => HttpResponse::Accepted().json(json!({
+            "message": "Verification code sent",
+            "destination": OtpFactor {
+                channel: OtpChannel::Sms,
+                destination: phone.clone(),
+            }
+            .masked_destination(),
+            "expires_in": otp.ttl_seconds()
+        })),
+        Err(e) => otp_error_response(&e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/profile/phone/verification/confirm",
+    request_body = ConfirmOtp,
+    responses(
+        (status = 200, description = "Phone number verified"),
+        (status = 401, description = "Unauthorized or invalid code"),
+        (status = 403, description = "Insufficient scope"),
+        (status = 409, description = "Number changed since the code was sent, or verified by another account")
+    ),
+    tag = "profile"
+)]
+pub async fn confirm_phone(
+    req: actix_web::HttpRequest,
+    body: web::Json<ConfirmOtp>,
+    users: web::Data<dyn UserRepository>,
+    otp: web::Data<OtpService>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "profile:write") {
+        return response;
+    }
+    let user_id = match current_user_id(&req) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let phone = match otp.confirm_phone_verification(user_id, &body.code).await {
+        Ok(phone) => phone,
+        Err(e) => return otp_error_response(&e),
+    };
+
+    match users.mark_phone_verified(user_id, &phone).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::Conflict().json(json!({
+                "error": "Phone number changed; request a new code"
+            }));
+        }
+        Err(RepositoryError::PhoneTaken) => {
+            return HttpResponse::Conflict().json(json!({
+                "error": "Phone number already verified by another account"
+            }));
+        }
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    }
+
+    audit::record(
+        &pool,
+        Some(user_id),
+        AuthEventType::PhoneVerified,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({}),
+    )
+    .await;
+
+    HttpResponse::Ok().json(json!({
+        "message": "Phone number verified",
+        "phone_number": phone
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/profile/activity",
+    params(ActivityQuery),
+    responses(
+        (status = 200, description = "Security events of the current user, newest first", body = [AuthEvent]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "profile"
+)]
+pub async fn get_activity(
+    req: actix_web::HttpRequest,
+    query: web::Query<ActivityQuery>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "profile:read") {
+        return response;
+    }
+    let user_id = match current_user_id(&req) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match sqlx::query_as::<_, AuthEvent>(
+        "SELECT id, user_id, event_type, outcome, ip, user_agent, details, created_at \
+         FROM auth_events WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3",
+    )
+    .bind(user_id)
+    .bind(page_limit(query.limit))
+    .bind(query.offset.unwrap_or(0).max(0))
+    .fetch_all(&**pool)
+    .await
+    {
+        Ok(events) => HttpResponse::Ok().json(events),
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
             }))
-        },
+        }
+    }
+}
+
+/// Rellena la ruta de descarga de una exportación lista.
+fn with_download_url(mut export: DataExport, tenant: &Tenant) -> DataExport {
+    if export.status == ExportStatus::Ready {
+        export.download_url = Some(format!(
+            "{}/profile/export/{}/download",
+            tenant.path_prefix, export.id
+        ));
+    }
+    export
+}
+
+/// Exportación del usuario actual, o la respuesta de error si no existe o ha caducado.
+async fn find_export(pool: &PgPool, user_id: i64, id: Uuid) -> Result<DataExport, HttpResponse> {
+    match data_export::find(pool, user_id, id).await {
+        Ok(Some(export)) if export.is_expired(Utc::now()) => Err(HttpResponse::Gone().json(json!({
+            "error": "Data export expired; request a new one"
+        }))),
+        Ok(Some(export)) => Ok(export),
+        Ok(None) => Err(HttpResponse::NotFound().json(json!({
+            "error": "Data export not found"
+        }))),
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            Err(HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            })))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/profile/export",
+    responses(
+        (status = 202, description = "Export started; poll its status until it is ready", body = DataExport),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope or recent authentication required"),
+        (status = 409, description = "An export is already in progress"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "profile"
+)]
+pub async fn request_export(
+    req: actix_web::HttpRequest,
+    pool: web::Data<PgPool>,
+    settings: web::Data<ProfileSettings>,
+    tenant: web::Data<Tenant>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "profile:read") {
+        return response;
+    }
+    // El archivo incluye el historial de accesos: solo desde una sesión reciente
+    let user_id = match require_recent_auth(&req) {
+        Ok(session) => session.user_id,
+        Err(response) => return response,
+    };
+
+    let export = match data_export::create(&pool, &tenant.id, user_id, settings.export_ttl).await {
+        Ok(Some(export)) => export,
+        Ok(None) => {
+            return match data_export::pending(&pool, user_id).await {
+                Ok(pending) => HttpResponse::Conflict().json(json!({
+                    "error": "A data export is already in progress",
+                    "export_id": pending.map(|export| export.id)
+                })),
+                Err(e) => {
+                    log::error!("Database error: {}", e);
+                    HttpResponse::InternalServerError().json(json!({
+                        "error": "Database error"
+                    }))
+                }
+            };
+        }
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            }));
+        }
+    };
+
+    audit::record(
+        &pool,
+        Some(user_id),
+        AuthEventType::DataExportRequested,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "export_id": export.id
+        }),
+    )
+    .await;
+
+    HttpResponse::Accepted().json(export)
+}
+
+#[utoipa::path(
+    get,
+    path = "/profile/export/{id}",
+    params(("id" = Uuid, Path, description = "Export ID")),
+    responses(
+        (status = 200, description = "Export status; `download_url` is set once it is ready", body = DataExport),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope"),
+        (status = 404, description = "Export not found"),
+        (status = 410, description = "Export expired"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "profile"
+)]
+pub async fn get_export(
+    req: actix_web::HttpRequest,
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    tenant: web::Data<Tenant>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "profile:read") {
+        return response;
+    }
+    let user_id = match current_user_id(&req) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match find_export(&pool, user_id, path.into_inner()).await {
+        Ok(export) => HttpResponse::Ok().json(with_download_url(export, &tenant)),
+        Err(response) => response,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/profile/export/{id}/download",
+    params(("id" = Uuid, Path, description = "Export ID")),
+    responses(
+        (status = 200, description = "ZIP archive with one JSON file per data source", content_type = "application/zip"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient scope or recent authentication required"),
+        (status = 404, description = "Export not found"),
+        (status = 409, description = "Export not ready or failed"),
+        (status = 410, description = "Export expired"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "profile"
+)]
+pub async fn download_export(
+    req: actix_web::HttpRequest,
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    blobs: web::Data<dyn BlobStore>,
+) -> impl Responder {
+    if let Err(response) = require_scope(&req, "profile:read") {
+        return response;
     }
-} 
\ No newline at end of file
+    let user_id = match require_recent_auth(&req) {
+        Ok(session) => session.user_id,
+        Err(response) => return response,
+    };
+
+    let export = match find_export(&pool, user_id, path.into_inner()).await {
+        Ok(export) => export,
+        Err(response) => return response,
+    };
+    let key = match (export.status, &export.blob_key) {
+        (ExportStatus::Ready, Some(key)) => key,
+        (status, _) => {
+            return HttpResponse::Conflict().json(json!({
+                "error": "Data export is not ready",
+                "status": status
+            }));
+        }
+    };
+
+    let archive = match blobs.get(key).await {
+        Ok(Some(blob)) => blob,
+        Ok(None) => {
+            log::error!("Data export {} has no archive at {}", export.id, key);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Data export archive not found"
+            }));
+        }
+        Err(e) => {
+            log::error!("{}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error"
+            }));
+        }
+    };
+
+    audit::record(
+        &pool,
+        Some(user_id),
+        AuthEventType::DataExportDownloaded,
+        Outcome::Success,
+        &RequestContext::from_request(&req),
+        json!({
+            "export_id": export.id
+        }),
+    )
+    .await;
+
+    HttpResponse::Ok()
+        .content_type(archive.content_type)
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"data-export-{}.zip\"", export.id),
+        ))
+        .insert_header(("Cache-Control", "no-store"))
+        .body(archive.data)
+}
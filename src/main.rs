@@ -1,6 +1,12 @@
-use actix_web::{web, App, HttpServer};
+// Los handlers devuelven `HttpResponse` como variante de error de sus helpers.
+#![allow(clippy::result_large_err)]
+
+use actix_multipart::form::MultipartFormConfig;
+use actix_multipart::MultipartError;
+use actix_web::{guard, web, App, HttpResponse, HttpServer};
 use dotenv::dotenv;
 use std::env;
+use std::sync::Arc;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -9,65 +15,391 @@ mod handlers;
 mod models;
 mod services;
 mod middleware;
+#[cfg(test)]
+mod tests;
+
+use config::redis::RedisPool;
+use config::tenants::Tenant;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         handlers::auth::register,
+        handlers::auth::accept_invite,
         handlers::auth::login,
         handlers::auth::logout,
-        handlers::profile::get_profile
+        handlers::auth::change_password,
+        handlers::auth::change_expired_password,
+        handlers::auth::reauthenticate,
+        handlers::auth::send_reauth_code,
+        handlers::auth::list_sessions,
+        handlers::auth::revoke_all_sessions,
+        handlers::magic_link::request_link,
+        handlers::magic_link::verify_link,
+        handlers::mfa::verify,
+        handlers::mfa::resend,
+        handlers::mfa::enroll_otp,
+        handlers::mfa::confirm_otp,
+        handlers::mfa::disable_otp,
+        handlers::oidc::authorize,
+        handlers::oidc::callback,
+        handlers::oidc::reauthenticate,
+        handlers::organizations::create_organization,
+        handlers::organizations::list_organizations,
+        handlers::organizations::switch_organization,
+        handlers::organizations::accept_invitation,
+        handlers::organizations::list_members,
+        handlers::organizations::update_member_role,
+        handlers::organizations::remove_member,
+        handlers::organizations::create_invitation,
+        handlers::organizations::list_invitations,
+        handlers::profile::get_profile,
+        handlers::profile::update_profile,
+        handlers::profile::delete_account,
+        handlers::profile::upload_avatar,
+        handlers::profile::delete_avatar,
+        handlers::profile::send_phone_code,
+        handlers::profile::confirm_phone,
+        handlers::profile::get_activity,
+        handlers::profile::request_export,
+        handlers::profile::get_export,
+        handlers::profile::download_export,
+        handlers::api_keys::create_api_key,
+        handlers::api_keys::list_api_keys,
+        handlers::api_keys::revoke_api_key,
+        handlers::oauth::introspect,
+        handlers::oauth::revoke,
+        handlers::admin::list_auth_events,
+        handlers::admin::create_invitation,
+        handlers::admin::create_webhook,
+        handlers::admin::list_webhooks,
+        handlers::admin::deactivate_webhook,
+        handlers::admin::list_webhook_deliveries
     ),
     components(
-        schemas(models::user::User, models::user::NewUser, models::user::LoginUser)
+        schemas(
+            models::user::User,
+            models::user::NewUser,
+            models::user::LoginUser,
+            models::user::ChangePassword,
+            models::user::ExpiredPasswordChange,
+            models::user::Reauthenticate,
+            models::profile::Profile,
+            models::profile::UpdateProfile,
+            models::profile::AvatarUpload,
+            models::data_export::DataExport,
+            models::user_invitation::NewUserInvitation,
+            models::user_invitation::UserInvitation,
+            models::user_invitation::AcceptInvite,
+            models::session::SessionSummary,
+            models::magic_link::MagicLinkRequest,
+            models::magic_link::MagicLinkVerify,
+            models::otp::OtpChannel,
+            models::otp::EnrollOtp,
+            models::otp::ConfirmOtp,
+            models::otp::MfaVerify,
+            models::otp::MfaResend,
+            models::organization::OrgRole,
+            models::organization::Organization,
+            models::organization::Member,
+            models::organization::Invitation,
+            models::organization::NewOrganization,
+            models::organization::NewInvitation,
+            models::organization::AcceptInvitation,
+            models::organization::SwitchOrganization,
+            models::organization::UpdateMemberRole,
+            models::api_key::ApiKey,
+            models::api_key::NewApiKey,
+            models::api_key::CreatedApiKey,
+            handlers::oauth::TokenRequest,
+            handlers::oauth::IntrospectionResponse,
+            models::auth_event::AuthEvent,
+            models::webhook::WebhookEndpoint,
+            models::webhook::NewWebhookEndpoint,
+            models::webhook::CreatedWebhookEndpoint,
+            models::webhook::WebhookDelivery
+        )
     ),
     tags(
         (name = "auth", description = "Authentication endpoints"),
-        (name = "profile", description = "User profile endpoints")
+        (name = "profile", description = "User profile endpoints"),
+        (name = "organizations", description = "Organization and membership endpoints"),
+        (name = "api-keys", description = "Personal API key endpoints"),
+        (name = "oauth", description = "Token introspection and revocation endpoints"),
+        (name = "admin", description = "Administration endpoints")
     )
 )]
 struct ApiDoc;
 
+/// Límites de las subidas `multipart/form-data` (avatares) con errores en JSON.
+fn multipart_config(profile: &config::profile::ProfileSettings) -> MultipartFormConfig {
+    // Margen para las cabeceras de la parte; el tamaño del fichero se comprueba después
+    let limit = profile.avatar_max_bytes + 64 * 1024;
+    MultipartFormConfig::default()
+        .total_limit(limit)
+        .memory_limit(limit)
+        .error_handler(|e, _| {
+            let response = match &e {
+                MultipartError::Payload(_) => HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                    "error": "File too large"
+                })),
+                _ => HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("Invalid multipart body: {}", e)
+                })),
+            };
+            actix_web::error::InternalError::from_response(e, response).into()
+        })
+}
+
+/// Dependencias de un tenant. Se registran en su scope, así que los
+/// handlers y el middleware las reciben sin saber de tenants.
+#[derive(Clone)]
+struct TenantApp {
+    tenant: web::Data<Tenant>,
+    redis: Option<web::Data<RedisPool>>,
+    sessions: web::Data<dyn services::session::SessionStore>,
+    users: web::Data<dyn services::users::UserRepository>,
+    auth: web::Data<services::auth::AuthService>,
+    session_settings: web::Data<config::session::SessionSettings>,
+    cookie_settings: web::Data<config::session::CookieSettings>,
+    magic_link: web::Data<config::magic_link::MagicLinkSettings>,
+    invitations: web::Data<config::organizations::InvitationSettings>,
+    registration: web::Data<config::registration::RegistrationSettings>,
+    profile: web::Data<config::profile::ProfileSettings>,
+    multipart: MultipartFormConfig,
+    captcha: web::Data<dyn services::captcha::CaptchaVerifier>,
+    oidc: web::Data<config::oidc::OidcProviders>,
+    otp: web::Data<services::otp::OtpService>,
+    oauth_clients: web::Data<config::oauth::OAuthClients>,
+    webhooks: config::webhooks::WebhookSettings,
+    jwt_secret: web::Data<String>,
+}
+
+impl TenantApp {
+    fn build(
+        tenant: Tenant,
+        redis_pool: Option<&RedisPool>,
+        pool: &sqlx::PgPool,
+        mailer: Arc<dyn services::mailer::Mailer>,
+        http: &reqwest::Client,
+    ) -> Result<Self, String> {
+        // Las claves de sesiones y OTP quedan bajo el prefijo del tenant
+        let redis_pool = redis_pool.map(|redis| redis.namespaced(tenant.redis_namespace()));
+
+        let sessions = services::session::build_store(&tenant.id, redis_pool.as_ref(), pool)
+            .map_err(|e| format!("Invalid session store configuration: {}", e))?;
+        let users: Arc<dyn services::users::UserRepository> =
+            Arc::new(services::users::PgUserRepository::new(pool.clone(), tenant.id.clone()));
+
+        Self::with_stores(tenant, redis_pool, sessions, users, mailer, http)
+    }
+
+    /// Resto de dependencias del tenant sobre unos stores ya creados; las
+    /// pruebas lo usan con los stores en memoria. Falla si la configuración
+    /// del tenant no es válida.
+    fn with_stores(
+        tenant: Tenant,
+        redis_pool: Option<RedisPool>,
+        sessions: Arc<dyn services::session::SessionStore>,
+        users: Arc<dyn services::users::UserRepository>,
+        mailer: Arc<dyn services::mailer::Mailer>,
+        http: &reqwest::Client,
+    ) -> Result<Self, String> {
+        let session_settings = config::session::load_settings(&tenant);
+        let auth_service = services::auth::AuthService::new(
+            users.clone(),
+            sessions.clone(),
+            session_settings.clone(),
+            config::password::load_settings(&tenant),
+            config::password::load_policy(&tenant),
+            tenant.jwt_secret.clone(),
+            tenant.jwt_issuer.clone(),
+        );
+
+        let otp_settings = config::otp::load_settings(&tenant);
+        let otp_store = redis_pool.clone().map(|redis| {
+            Arc::new(services::otp::RedisOtpStore::new(redis)) as Arc<dyn services::otp::OtpStore>
+        });
+        let otp_service = services::otp::OtpService::new(
+            otp_store,
+            services::otp::build_senders(&otp_settings, mailer, http.clone()),
+            otp_settings,
+            tenant.jwt_secret.clone(),
+        );
+
+        let registration = config::registration::load_settings(&tenant)?;
+        let captcha = services::captcha::build_verifier(&registration, http.clone())?;
+        let profile = config::profile::load_settings(&tenant)?;
+
+        Ok(TenantApp {
+            redis: redis_pool.map(web::Data::new),
+            sessions: web::Data::from(sessions),
+            users: web::Data::from(users),
+            auth: web::Data::new(auth_service),
+            session_settings: web::Data::new(session_settings),
+            cookie_settings: web::Data::new(config::session::load_cookie_settings(&tenant)),
+            magic_link: web::Data::new(config::magic_link::load_settings(&tenant)),
+            invitations: web::Data::new(config::organizations::load_settings(&tenant)),
+            captcha: web::Data::from(captcha),
+            registration: web::Data::new(registration),
+            multipart: multipart_config(&profile),
+            profile: web::Data::new(profile),
+            oidc: web::Data::new(config::oidc::load_providers(&tenant)?),
+            otp: web::Data::new(otp_service),
+            oauth_clients: web::Data::new(config::oauth::load_clients(&tenant)),
+            webhooks: config::webhooks::load_settings(&tenant),
+            jwt_secret: web::Data::new(tenant.jwt_secret.clone()),
+            tenant: web::Data::new(tenant),
+        })
+    }
+
+    /// Scope con todas las rutas de la API para este tenant: bajo su prefijo
+    /// o, si se resuelve por host, con un guard de sus hosts.
+    fn scope(&self) -> actix_web::Scope {
+        let scope = web::scope(&self.tenant.path_prefix);
+        let scope = match self.tenant.hosts.split_first() {
+            Some((first, rest)) => {
+                let hosts = rest
+                    .iter()
+                    .fold(guard::Any(guard::Host(first)), |hosts, host| hosts.or(guard::Host(host)));
+                scope.guard(hosts)
+            }
+            None => scope,
+        };
+        let scope = match &self.redis {
+            Some(redis) => scope.app_data(redis.clone()),
+            None => scope,
+        };
+
+        scope
+            .app_data(self.tenant.clone())
+            .app_data(self.sessions.clone())
+            .app_data(self.users.clone())
+            .app_data(self.auth.clone())
+            .app_data(self.session_settings.clone())
+            .app_data(self.cookie_settings.clone())
+            .app_data(self.magic_link.clone())
+            .app_data(self.invitations.clone())
+            .app_data(self.registration.clone())
+            .app_data(self.captcha.clone())
+            .app_data(self.profile.clone())
+            .app_data(self.multipart.clone())
+            .app_data(self.oidc.clone())
+            .app_data(self.otp.clone())
+            .app_data(self.oauth_clients.clone())
+            .app_data(self.jwt_secret.clone())
+            .configure(handlers::auth::config)
+            .configure(handlers::profile::config)
+            .configure(handlers::organizations::config)
+            .configure(handlers::api_keys::config)
+            .configure(handlers::oauth::config)
+            .configure(handlers::admin::config)
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let redis_url = env::var("REDIS_URL").expect("REDIS_URL must be set");
-    let jwt_secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-
-    log::debug!("Connecting to Redis at: {}", redis_url);
-    let redis_client = config::redis::create_redis_client(&redis_url);
-    
-    // Verificar la conexión a Redis
-    match redis_client.get_connection() {
-        Ok(_) => log::info!("Redis connection test successful"),
-        Err(e) => {
-            log::error!("Failed to connect to Redis: {}", e);
-            panic!("Could not connect to Redis");
+    let redis_url = env::var("REDIS_URL").ok();
+    let tenants = config::tenants::load_tenants();
+
+    // Redis es opcional cuando las sesiones se guardan en Postgres o en memoria
+    let redis_pool = match redis_url {
+        Some(redis_url) => {
+            log::debug!("Connecting to Redis at: {}", redis_url);
+            // Verificar la conexión a Redis
+            match config::redis::create_redis_pool(&redis_url).await {
+                Ok(redis_pool) => {
+                    log::info!("Redis connection test successful");
+                    Some(redis_pool)
+                }
+                Err(e) => {
+                    log::error!("Failed to connect to Redis: {}", e);
+                    panic!("Could not connect to Redis");
+                }
+            }
         }
-    }
+        None => {
+            log::warn!("REDIS_URL not set; one-time codes (MFA, phone verification) will be unavailable");
+            None
+        }
+    };
 
     let pool = config::database::create_pool(&database_url).await;
 
-    let app_data = web::Data::new(redis_client.clone());
+    let mailer = match services::mailer::build_mailer() {
+        Ok(mailer) => mailer,
+        Err(e) => panic!("Invalid mailer configuration: {}", e),
+    };
+
+    let http = reqwest::Client::new();
+    let mut tenant_apps = Vec::new();
+    for tenant in tenants {
+        let tenant_id = tenant.id.clone();
+        match TenantApp::build(tenant, redis_pool.as_ref(), &pool, mailer.clone(), &http) {
+            Ok(tenant_app) => tenant_apps.push(tenant_app),
+            Err(e) => {
+                log::error!("Invalid configuration for tenant {}: {}", tenant_id, e);
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("invalid configuration for tenant {}: {}", tenant_id, e),
+                ));
+            }
+        }
+    }
+
+    let mailer_data: web::Data<dyn services::mailer::Mailer> = web::Data::from(mailer);
+    let blob_store_data: web::Data<dyn services::blob_store::BlobStore> =
+        match services::blob_store::build_blob_store() {
+            Ok(store) => web::Data::from(store),
+            Err(e) => panic!("Invalid blob store configuration: {}", e),
+        };
+    let trusted_proxies = match config::proxies::load_trusted_proxies() {
+        Ok(proxies) => web::Data::new(proxies),
+        Err(e) => panic!("Invalid proxy configuration: {}", e),
+    };
     let pool_data = web::Data::new(pool.clone());
-    let jwt_data = web::Data::new(jwt_secret.clone());
+    let http_data = web::Data::new(http);
+
+    // Un despachador de webhooks y un generador de exportaciones por tenant,
+    // cada uno con su configuración
+    for tenant_app in &tenant_apps {
+        services::webhooks::spawn_dispatcher(
+            pool.clone(),
+            http_data.get_ref().clone(),
+            tenant_app.tenant.id.clone(),
+            tenant_app.webhooks.clone(),
+        );
+        services::data_export::spawn_worker(
+            pool.clone(),
+            tenant_app.sessions.clone().into_inner(),
+            blob_store_data.clone().into_inner(),
+            tenant_app.tenant.id.clone(),
+            tenant_app.profile.export_max_attempts,
+        );
+    }
 
     HttpServer::new(move || {
-        App::new()
+        let app = App::new()
             .app_data(pool_data.clone())
-            .app_data(app_data.clone())
-            .app_data(jwt_data.clone())
+            .app_data(mailer_data.clone())
+            .app_data(http_data.clone())
+            .app_data(blob_store_data.clone())
+            .app_data(trusted_proxies.clone())
+            .configure(handlers::media::config)
             .service(
                 SwaggerUi::new("/swagger-ui/{_:.*}")
                     .url("/api-docs/openapi.json", ApiDoc::openapi()),
-            )
-            .configure(handlers::auth::config)
-            .configure(handlers::profile::config)
+            );
+
+        tenant_apps.iter().fold(app, |app, tenant_app| {
+            app.service(tenant_app.scope())
+        })
     })
     .bind(("127.0.0.1", 3000))?
     .run()
     .await
-} 
\ No newline at end of file
+}
@@ -1,20 +1,199 @@
-use actix_web::{dev::ServiceRequest, Error, HttpMessage};
+use actix_web::{dev::Payload, dev::ServiceRequest, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use actix_web::http::Method;
 use actix_web::web;
 use actix_web_httpauth::extractors::bearer::BearerAuth;
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{decode, DecodingKey, Validation};
-use redis::Commands;
 use serde_json::json;
+use sqlx::PgPool;
+use std::future::{ready, Ready};
+use subtle::ConstantTimeEq;
 
+use crate::config::session::{CookieSettings, SessionSettings};
+use crate::config::tenants::Tenant;
+use crate::models::api_key::ApiKeyScopes;
+use crate::models::session::Session;
 use crate::models::user::TokenClaims;
+use crate::services::{api_key, crypto};
+use crate::services::session::SessionStore;
+
+
+/// Sesión activa asociada a un token de acceso.
+#[derive(Debug)]
+pub struct ActiveSession {
+    pub claims: TokenClaims,
+    pub session: Session,
+}
+
+/// Motivos por los que un token de acceso no es válido.
+#[derive(Debug)]
+pub enum SessionError {
+    InvalidToken,
+    Store,
+    SessionNotFound,
+    TokenMismatch,
+    IdleTimeout,
+    Expired,
+}
+
+impl SessionError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            SessionError::InvalidToken => "Invalid token",
+            SessionError::Store => "Session store error",
+            SessionError::SessionNotFound => "Session not found",
+            SessionError::TokenMismatch => "Invalid or expired session",
+            SessionError::IdleTimeout => "Session expired due to inactivity",
+            SessionError::Expired => "Session expired",
+        }
+    }
+}
+
+/// Valida el JWT con las claves y el emisor del tenant y comprueba que siga
+/// siendo el token de su sesión en el store y que la sesión no haya superado
+/// el idle timeout ni el límite absoluto.
+pub async fn verify_session_token(
+    tenant: &Tenant,
+    sessions: &dyn SessionStore,
+    settings: &SessionSettings,
+    token: &str,
+) -> Result<ActiveSession, SessionError> {
+    let mut validation = Validation::default();
+    if let Some(issuer) = &tenant.jwt_issuer {
+        validation.set_issuer(&[issuer]);
+    }
+
+    let token_data = match decode::<TokenClaims>(
+        token,
+        &DecodingKey::from_secret(tenant.jwt_secret.as_bytes()),
+        &validation,
+    ) {
+        Ok(data) => {
+            log::debug!("Token decoded successfully for user: {}", data.claims.sub);
+            data
+        },
+        Err(e) => {
+            log::error!("Token decode error: {}", e);
+            return Err(SessionError::InvalidToken);
+        }
+    };
+
+    log::debug!("Looking for session: {}", token_data.claims.sid);
+
+    let session = match sessions.get(&token_data.claims.sid).await {
+        Ok(Some(session)) => session,
+        Ok(None) => {
+            log::error!("Session not found: {}", token_data.claims.sid);
+            return Err(SessionError::SessionNotFound);
+        }
+        Err(e) => {
+            log::error!("Session store error: {}", e);
+            return Err(SessionError::Store);
+        }
+    };
+
+    if session.user_id != token_data.claims.sub || session.token != token {
+        log::error!("Token mismatch");
+        return Err(SessionError::TokenMismatch);
+    }
+
+    // El store ya caduca las sesiones por TTL; esto cubre el margen entre touches
+    let now = Utc::now().timestamp();
+    if now >= session.expires_at {
+        log::debug!("Session {} reached its absolute timeout", session.id);
+        return Err(SessionError::Expired);
+    }
+    if now - session.last_seen_at >= settings.idle_timeout {
+        log::debug!("Session {} reached its idle timeout", session.id);
+        return Err(SessionError::IdleTimeout);
+    }
+
+    log::debug!("Token validation successful");
+    Ok(ActiveSession {
+        claims: token_data.claims,
+        session,
+    })
+}
+
+/// Registra actividad en la sesión si pasó `touch_interval` desde el último registro.
+///
+/// Un fallo aquí no invalida la petición: como mucho la sesión caduca antes.
+async fn touch_session(sessions: &dyn SessionStore, settings: &SessionSettings, session: &mut Session) {
+    let now = Utc::now().timestamp();
+    if now - session.last_seen_at < settings.touch_interval {
+        return;
+    }
+
+    session.last_seen_at = now;
+    match sessions
+        .touch(session, settings.ttl_seconds(now, session.expires_at))
+        .await
+    {
+        Ok(true) => log::debug!("Session {} extended", session.id),
+        Ok(false) => log::debug!("Session {} disappeared before touch", session.id),
+        Err(e) => log::error!("Session store error on touch: {}", e),
+    }
+}
+
+/// Cabecera con la que los clientes en modo cookie devuelven el token CSRF.
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// Token de sesión recibido en `Authorization: Bearer` o en la cookie de sesión.
+#[derive(Clone)]
+pub enum SessionCredentials {
+    Bearer(BearerAuth),
+    Cookie(String),
+}
+
+impl FromRequest for SessionCredentials {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        // La cabecera tiene prioridad: un cliente con token no depende de cookies
+        if !req.headers().contains_key(actix_web::http::header::AUTHORIZATION) {
+            let cookie = req
+                .app_data::<web::Data<CookieSettings>>()
+                .and_then(|settings| req.cookie(&settings.session_name));
+            if let Some(cookie) = cookie {
+                return ready(Ok(SessionCredentials::Cookie(cookie.value().to_string())));
+            }
+        }
+
+        ready(
+            BearerAuth::from_request(req, payload)
+                .into_inner()
+                .map(SessionCredentials::Bearer)
+                .map_err(Error::from),
+        )
+    }
+}
+
+/// Con la sesión en cookie, el navegador la envía solo; las peticiones que
+/// cambian estado deben demostrar que vienen de nuestro frontend.
+fn check_csrf(req: &ServiceRequest, jwt_secret: &str, session: &Session) -> bool {
+    if matches!(
+        *req.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return true;
+    }
+
+    req.headers()
+        .get(CSRF_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|token| crypto::verify_csrf_token(jwt_secret, &session.id, token))
+        .unwrap_or(false)
+}
 
 pub async fn validator(
     req: ServiceRequest,
-    credentials: BearerAuth,
+    credentials: SessionCredentials,
 ) -> Result<ServiceRequest, (Error, ServiceRequest)> {
-    let jwt_secret = match req.app_data::<web::Data<String>>() {
-        Some(secret) => secret.get_ref().clone(),
+    let tenant = match req.app_data::<web::Data<Tenant>>() {
+        Some(tenant) => tenant.clone(),
         None => {
-            log::error!("JWT_SECRET not found in app_data");
+            log::error!("Tenant not found in app_data");
             return Err((
                 actix_web::error::ErrorInternalServerError(json!({
                     "error": "Internal server error"
@@ -24,10 +203,10 @@ pub async fn validator(
         }
     };
     
-    let redis_client = match req.app_data::<web::Data<redis::Client>>() {
-        Some(client) => client.get_ref().clone(),
+    let sessions = match req.app_data::<web::Data<dyn SessionStore>>() {
+        Some(sessions) => sessions.clone(),
         None => {
-            log::error!("Redis client not found in app_data");
+            log::error!("Session store not found in app_data");
             return Err((
                 actix_web::error::ErrorInternalServerError(json!({
                     "error": "Internal server error"
@@ -37,142 +216,311 @@ pub async fn validator(
         }
     };
 
-    let token = credentials.token();
-    log::debug!("Validating token: {}", token);
-    
-    let token_data = match decode::<TokenClaims>(
-        token,
-        &DecodingKey::from_secret(jwt_secret.as_bytes()),
-        &Validation::default(),
-    ) {
-        Ok(data) => {
-            log::debug!("Token decoded successfully for user: {}", data.claims.sub);
-            data
-        },
-        Err(e) => {
-            log::error!("Token decode error: {}", e);
+    let settings = match req.app_data::<web::Data<SessionSettings>>() {
+        Some(settings) => settings.clone(),
+        None => {
+            log::error!("Session settings not found in app_data");
             return Err((
-                actix_web::error::ErrorUnauthorized(json!({
-                    "error": "Invalid token"
+                actix_web::error::ErrorInternalServerError(json!({
+                    "error": "Internal server error"
                 })),
                 req,
             ));
         }
     };
 
-    let mut conn = match redis_client.get_connection() {
-        Ok(conn) => {
-            log::debug!("Redis connection established");
-            conn
-        },
-        Err(e) => {
-            log::error!("Redis connection error: {}", e);
+    let (token, from_cookie) = match &credentials {
+        SessionCredentials::Bearer(bearer) => (bearer.token(), false),
+        SessionCredentials::Cookie(token) => (token.as_str(), true),
+    };
+
+    match verify_session_token(&tenant, sessions.get_ref(), &settings, token).await {
+        Ok(active) if from_cookie && !check_csrf(&req, &tenant.jwt_secret, &active.session) => {
+            log::error!("Missing or invalid CSRF token for session {}", active.session.id);
+            Err((
+                actix_web::error::ErrorForbidden(json!({
+                    "error": "Invalid CSRF token"
+                })),
+                req,
+            ))
+        }
+        Ok(mut active) => {
+            touch_session(sessions.get_ref(), &settings, &mut active.session).await;
+            log::debug!("Inserting user ID into extensions: {}", active.claims.sub);
+            req.extensions_mut().insert(active.claims.sub);
+            req.extensions_mut().insert(active.session);
+            Ok(req)
+        }
+        Err(e) => Err((
+            actix_web::error::ErrorUnauthorized(json!({
+                "error": e.message()
+            })),
+            req,
+        )),
+    }
+}
+
+/// Credenciales aceptadas por `authenticate`: sesión (token o cookie) o API key.
+pub enum Credentials {
+    Session(SessionCredentials),
+    ApiKey(String),
+}
+
+impl FromRequest for Credentials {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        if let Some(key) = req.headers().get("X-API-Key").and_then(|v| v.to_str().ok()) {
+            return ready(Ok(Credentials::ApiKey(key.trim().to_string())));
+        }
+
+        if let Some(authorization) = req
+            .headers()
+            .get(actix_web::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+        {
+            if let Some(key) = authorization.strip_prefix("ApiKey ") {
+                return ready(Ok(Credentials::ApiKey(key.trim().to_string())));
+            }
+        }
+
+        ready(
+            SessionCredentials::from_request(req, payload)
+                .into_inner()
+                .map(Credentials::Session),
+        )
+    }
+}
+
+/// Autenticación para rutas que aceptan tanto sesiones como API keys.
+pub async fn authenticate(
+    req: ServiceRequest,
+    credentials: Credentials,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    match credentials {
+        Credentials::Session(credentials) => validator(req, credentials).await,
+        Credentials::ApiKey(key) => api_key_validator(req, &key).await,
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct StoredApiKey {
+    pub id: uuid::Uuid,
+    pub user_id: i64,
+    key_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub enum ApiKeyError {
+    Invalid,
+    Expired,
+    Database(sqlx::Error),
+}
+
+/// Busca una API key activa por su prefijo y verifica el hash y la caducidad.
+///
+/// Solo valen las claves de usuarios del tenant de la petición.
+pub async fn verify_api_key(pool: &PgPool, tenant_id: &str, key: &str) -> Result<StoredApiKey, ApiKeyError> {
+    let prefix = api_key::parse_prefix(key).ok_or(ApiKeyError::Invalid)?;
+
+    let stored = match sqlx::query_as::<_, StoredApiKey>(
+        "SELECT k.id, k.user_id, k.key_hash, k.scopes, k.expires_at FROM api_keys k \
+         JOIN users u ON u.id = k.user_id \
+         WHERE k.prefix = $1 AND k.revoked_at IS NULL AND u.tenant_id = $2",
+    )
+    .bind(prefix)
+    .bind(tenant_id)
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(Some(stored)) => stored,
+        Ok(None) => {
+            log::error!("API key not found for prefix: {}", prefix);
+            return Err(ApiKeyError::Invalid);
+        }
+        Err(e) => return Err(ApiKeyError::Database(e)),
+    };
+
+    let hash = api_key::hash_key(key);
+    if !bool::from(hash.as_bytes().ct_eq(stored.key_hash.as_bytes())) {
+        log::error!("API key hash mismatch for prefix: {}", prefix);
+        return Err(ApiKeyError::Invalid);
+    }
+
+    if matches!(stored.expires_at, Some(expires_at) if expires_at <= Utc::now()) {
+        log::error!("Expired API key: {}", stored.id);
+        return Err(ApiKeyError::Expired);
+    }
+
+    Ok(stored)
+}
+
+pub async fn api_key_validator(
+    req: ServiceRequest,
+    key: &str,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    let pool = match req.app_data::<web::Data<PgPool>>() {
+        Some(pool) => pool.clone(),
+        None => {
+            log::error!("Database pool not found in app_data");
             return Err((
-                actix_web::error::ErrorUnauthorized(json!({
-                    "error": "Redis connection error"
+                actix_web::error::ErrorInternalServerError(json!({
+                    "error": "Internal server error"
                 })),
                 req,
             ));
         }
     };
 
-    // Obtener la sesión del usuario
-    let user_session_key = format!("user_session:{}", token_data.claims.sub);
-    log::debug!("Looking for user session with key: {}", user_session_key);
-    
-    let session_id: Option<String> = match conn.get(&user_session_key) {
-        Ok(session) => {
-            log::debug!("Found session ID: {:?}", session);
-            session
-        },
-        Err(e) => {
-            log::error!("Redis error getting user session: {}", e);
+    let tenant = match req.app_data::<web::Data<Tenant>>() {
+        Some(tenant) => tenant.clone(),
+        None => {
+            log::error!("Tenant not found in app_data");
             return Err((
-                actix_web::error::ErrorUnauthorized(json!({
-                    "error": "Redis error"
+                actix_web::error::ErrorInternalServerError(json!({
+                    "error": "Internal server error"
                 })),
                 req,
             ));
         }
     };
 
-    match session_id {
-        Some(session_id) => {
-            // Verificar la sesión
-            let session_key = format!("session:{}", session_id);
-            log::debug!("Looking for session data with key: {}", session_key);
-            
-            let session_data: Option<String> = match conn.get(&session_key) {
-                Ok(data) => {
-                    log::debug!("Found session data");
-                    data
-                },
-                Err(e) => {
-                    log::error!("Redis error getting session data: {}", e);
-                    return Err((
-                        actix_web::error::ErrorUnauthorized(json!({
-                            "error": "Redis error"
-                        })),
-                        req,
-                    ));
-                }
-            };
-
-            match session_data {
-                Some(data) => {
-                    let session: serde_json::Value = match serde_json::from_str(&data) {
-                        Ok(session) => {
-                            log::debug!("Session data parsed successfully");
-                            session
-                        },
-                        Err(e) => {
-                            log::error!("Error parsing session data: {}", e);
-                            return Err((
-                                actix_web::error::ErrorUnauthorized(json!({
-                                    "error": "Invalid session data"
-                                })),
-                                req,
-                            ));
-                        }
-                    };
-
-                    let session_token = session["token"].as_str();
-                    log::debug!("Comparing tokens - Session: {:?}, Request: {}", session_token, token);
-                    
-                    if session_token == Some(token) {
-                        log::debug!("Token validation successful");
-                        log::debug!("Inserting user ID into extensions: {}", token_data.claims.sub);
-                        req.extensions_mut().insert(token_data.claims.sub as i64);
-                        Ok(req)
-                    } else {
-                        log::error!("Token mismatch");
-                        Err((
-                            actix_web::error::ErrorUnauthorized(json!({
-                                "error": "Invalid or expired session"
-                            })),
-                            req,
-                        ))
-                    }
-                }
-                None => {
-                    log::error!("Session data not found");
-                    Err((
-                        actix_web::error::ErrorUnauthorized(json!({
-                            "error": "Session not found"
-                        })),
-                        req,
-                    ))
-                },
-            }
+    let stored = match verify_api_key(&pool, &tenant.id, key).await {
+        Ok(stored) => stored,
+        Err(ApiKeyError::Invalid) => {
+            return Err((
+                actix_web::error::ErrorUnauthorized(json!({
+                    "error": "Invalid API key"
+                })),
+                req,
+            ));
         }
-        None => {
-            log::error!("No active session found for user");
-            Err((
+        Err(ApiKeyError::Expired) => {
+            return Err((
                 actix_web::error::ErrorUnauthorized(json!({
-                    "error": "No active session"
+                    "error": "API key expired"
                 })),
                 req,
-            ))
-        },
+            ));
+        }
+        Err(ApiKeyError::Database(e)) => {
+            log::error!("Database error: {}", e);
+            return Err((
+                actix_web::error::ErrorInternalServerError(json!({
+                    "error": "Database error"
+                })),
+                req,
+            ));
+        }
+    };
+
+    if let Err(e) = sqlx::query("UPDATE api_keys SET last_used_at = now() WHERE id = $1")
+        .bind(stored.id)
+        .execute(&**pool)
+        .await
+    {
+        log::error!("Error updating API key last_used_at: {}", e);
+    }
+
+    log::debug!("API key {} validated for user {}", stored.id, stored.user_id);
+    req.extensions_mut().insert(stored.user_id);
+    req.extensions_mut().insert(ApiKeyScopes(stored.scopes));
+    Ok(req)
+}
+
+/// Id del usuario autenticado que el middleware dejó en las extensiones.
+pub fn current_user_id(req: &HttpRequest) -> Result<i64, HttpResponse> {
+    match req.extensions().get::<i64>() {
+        Some(id) => Ok(*id),
+        None => {
+            log::error!("User ID not found in request extensions");
+            Err(HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error"
+            })))
+        }
+    }
+}
+
+/// Comprueba que una petición autenticada con API key tenga el scope indicado.
+///
+/// Las peticiones con token de sesión tienen acceso completo.
+pub fn require_scope(req: &HttpRequest, scope: &str) -> Result<(), HttpResponse> {
+    match req.extensions().get::<ApiKeyScopes>() {
+        Some(ApiKeyScopes(scopes)) if !scopes.iter().any(|s| s == scope) => {
+            Err(HttpResponse::Forbidden().json(json!({
+                "error": "Insufficient scope",
+                "required_scope": scope
+            })))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Exige una autenticación reciente para operaciones sensibles y devuelve la sesión.
+///
+/// Si el login o la última reautenticación tienen más de `reauth_max_age`
+/// segundos, el cliente debe llamar a `POST /auth/reauthenticate`. Las API
+/// keys no pueden reautenticarse, así que no sirven para estas rutas.
+pub fn require_recent_auth(req: &HttpRequest) -> Result<Session, HttpResponse> {
+    let session = match req.extensions().get::<Session>() {
+        Some(session) => session.clone(),
+        None => {
+            return Err(HttpResponse::Forbidden().json(json!({
+                "error": "This operation requires an interactive session",
+                "reauth_required": true
+            })));
+        }
+    };
+
+    let max_age = match req.app_data::<web::Data<SessionSettings>>() {
+        Some(settings) => settings.reauth_max_age,
+        None => {
+            log::error!("Session settings not found in app_data");
+            return Err(HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error"
+            })));
+        }
+    };
+
+    if !session.authenticated_within(max_age, Utc::now().timestamp()) {
+        return Err(HttpResponse::Forbidden().json(json!({
+            "error": "Recent authentication required",
+            "reauth_required": true,
+            "max_age": max_age
+        })));
+    }
+    Ok(session)
+}
+
+/// Comprueba que el usuario autenticado sea administrador y devuelve su id.
+///
+/// Las rutas de administración no se pueden usar con API keys.
+pub async fn require_admin(req: &HttpRequest, pool: &PgPool) -> Result<i64, HttpResponse> {
+    let user_id = current_user_id(req)?;
+
+    if req.extensions().get::<ApiKeyScopes>().is_some() {
+        return Err(HttpResponse::Forbidden().json(json!({
+            "error": "API keys cannot be used for admin endpoints"
+        })));
+    }
+
+    match sqlx::query_as::<_, (bool,)>("SELECT is_admin FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+    {
+        Ok(Some((true,))) => Ok(user_id),
+        Ok(_) => Err(HttpResponse::Forbidden().json(json!({
+            "error": "Admin privileges required"
+        }))),
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            Err(HttpResponse::InternalServerError().json(json!({
+                "error": "Database error"
+            })))
+        }
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,111 @@
+use actix_web::{dev::Payload, web, Error, FromRequest, HttpMessage, HttpRequest};
+use serde_json::json;
+use sqlx::PgPool;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+use crate::models::organization::OrgRole;
+use crate::models::session::Session;
+use crate::services::organizations;
+
+/// Rol mínimo que exige un `OrgAccess`.
+pub trait RequiredRole {
+    const ROLE: OrgRole;
+}
+
+/// Cualquier miembro de la organización.
+pub struct AnyMember;
+/// `admin` u `owner`.
+pub struct OrgAdmin;
+/// Solo `owner`.
+pub struct OrgOwner;
+
+impl RequiredRole for AnyMember {
+    const ROLE: OrgRole = OrgRole::Member;
+}
+
+impl RequiredRole for OrgAdmin {
+    const ROLE: OrgRole = OrgRole::Admin;
+}
+
+impl RequiredRole for OrgOwner {
+    const ROLE: OrgRole = OrgRole::Owner;
+}
+
+/// Extractor para rutas de la organización activa de la sesión.
+///
+/// Requiere el middleware de sesión (`validator`) y comprueba el rol en la
+/// base de datos en cada petición, así que quitar a alguien de la
+/// organización surte efecto aunque su token siga llevando el `org_id`.
+pub struct OrgAccess<R: RequiredRole = AnyMember> {
+    pub org_id: i64,
+    pub user_id: i64,
+    pub role: OrgRole,
+    _required: PhantomData<R>,
+}
+
+impl<R: RequiredRole + 'static> FromRequest for OrgAccess<R> {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let session = req.extensions().get::<Session>().cloned();
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+
+        Box::pin(async move {
+            let (session, pool) = match (session, pool) {
+                (Some(session), Some(pool)) => (session, pool),
+                (None, _) => {
+                    return Err(actix_web::error::ErrorForbidden(json!({
+                        "error": "Organization routes require a session"
+                    })));
+                }
+                (_, None) => {
+                    log::error!("Database pool not found in app_data");
+                    return Err(actix_web::error::ErrorInternalServerError(json!({
+                        "error": "Internal server error"
+                    })));
+                }
+            };
+
+            let org_id = match session.org_id {
+                Some(org_id) => org_id,
+                None => {
+                    return Err(actix_web::error::ErrorForbidden(json!({
+                        "error": "No active organization; switch to one first"
+                    })));
+                }
+            };
+
+            let role = match organizations::role(&pool, org_id, session.user_id).await {
+                Ok(Some(role)) => role,
+                Ok(None) => {
+                    return Err(actix_web::error::ErrorForbidden(json!({
+                        "error": "Not a member of the active organization"
+                    })));
+                }
+                Err(e) => {
+                    log::error!("Database error: {}", e);
+                    return Err(actix_web::error::ErrorInternalServerError(json!({
+                        "error": "Database error"
+                    })));
+                }
+            };
+
+            if role < R::ROLE {
+                return Err(actix_web::error::ErrorForbidden(json!({
+                    "error": "Insufficient organization role",
+                    "required_role": R::ROLE
+                })));
+            }
+
+            Ok(OrgAccess {
+                org_id,
+                user_id: session.user_id,
+                role,
+                _required: PhantomData,
+            })
+        })
+    }
+}
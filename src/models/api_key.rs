@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Scopes que se pueden conceder a una API key.
+pub const API_KEY_SCOPES: &[&str] = &["profile:read", "profile:write", "api_keys:read", "api_keys:write"];
+
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct NewApiKey {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1))]
+    pub scopes: Vec<String>,
+    #[validate(range(min = 1, max = 365))]
+    pub expires_in_days: Option<i64>,
+}
+
+/// Respuesta de creación: la clave completa solo se muestra esta vez.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+/// Scopes de la API key usada en la petición actual.
+///
+/// Solo está presente en las extensiones cuando la petición se autenticó
+/// con una API key y no con un token de sesión.
+#[derive(Debug, Clone)]
+pub struct ApiKeyScopes(pub Vec<String>);
@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportStatus {
+    Pending,
+    Ready,
+    Failed,
+}
+
+impl ExportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportStatus::Pending => "pending",
+            ExportStatus::Ready => "ready",
+            ExportStatus::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for ExportStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ExportStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(ExportStatus::Pending),
+            "ready" => Ok(ExportStatus::Ready),
+            "failed" => Ok(ExportStatus::Failed),
+            other => Err(format!("Unknown export status: {}", other)),
+        }
+    }
+}
+
+impl TryFrom<String> for ExportStatus {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Exportación de los datos del usuario (`POST /profile/export`).
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct DataExport {
+    pub id: Uuid,
+    #[schema(value_type = String)]
+    #[sqlx(try_from = "String")]
+    pub status: ExportStatus,
+    /// Tamaño del ZIP cuando está listo
+    pub size_bytes: Option<i64>,
+    /// A partir de este momento el archivo se borra
+    pub expires_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// Ruta de descarga cuando está listo
+    #[sqlx(skip)]
+    pub download_url: Option<String>,
+    #[serde(skip)]
+    pub blob_key: Option<String>,
+}
+
+impl DataExport {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
@@ -1 +1,11 @@
-pub mod user; 
\ No newline at end of file
+pub mod api_key;
+pub mod auth_event;
+pub mod data_export;
+pub mod magic_link;
+pub mod organization;
+pub mod otp;
+pub mod profile;
+pub mod session;
+pub mod user;
+pub mod user_invitation;
+pub mod webhook;
@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// Rol dentro de una organización; el orden es el de privilegios.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OrgRole {
+    Member,
+    Admin,
+    Owner,
+}
+
+impl OrgRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrgRole::Member => "member",
+            OrgRole::Admin => "admin",
+            OrgRole::Owner => "owner",
+        }
+    }
+}
+
+impl fmt::Display for OrgRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OrgRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "member" => Ok(OrgRole::Member),
+            "admin" => Ok(OrgRole::Admin),
+            "owner" => Ok(OrgRole::Owner),
+            other => Err(format!("Unknown organization role: {}", other)),
+        }
+    }
+}
+
+impl TryFrom<String> for OrgRole {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct Organization {
+    pub id: i64,
+    pub name: String,
+    /// Rol del usuario actual
+    #[schema(value_type = String)]
+    #[sqlx(try_from = "String")]
+    pub role: OrgRole,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct Member {
+    pub user_id: i64,
+    pub email: String,
+    pub name: String,
+    #[schema(value_type = String)]
+    #[sqlx(try_from = "String")]
+    pub role: OrgRole,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct Invitation {
+    pub id: i64,
+    pub email: String,
+    #[schema(value_type = String)]
+    #[sqlx(try_from = "String")]
+    pub role: OrgRole,
+    pub invited_by: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct NewOrganization {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct NewInvitation {
+    #[validate(email)]
+    pub email: String,
+    pub role: OrgRole,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AcceptInvitation {
+    /// Token del enlace de invitación recibido por correo
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SwitchOrganization {
+    /// `null` para salir de cualquier organización
+    pub org_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateMemberRole {
+    pub role: OrgRole,
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::models::profile::{normalize_phone, validate_phone};
+
+/// Canal por el que se envían los códigos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OtpChannel {
+    Email,
+    Sms,
+}
+
+impl OtpChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OtpChannel::Email => "email",
+            OtpChannel::Sms => "sms",
+        }
+    }
+}
+
+impl fmt::Display for OtpChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OtpChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "email" => Ok(OtpChannel::Email),
+            "sms" => Ok(OtpChannel::Sms),
+            other => Err(format!("Unknown OTP channel: {}", other)),
+        }
+    }
+}
+
+/// Segundo factor activo de un usuario.
+#[derive(Debug, Clone)]
+pub struct OtpFactor {
+    pub channel: OtpChannel,
+    /// Email o teléfono en formato E.164
+    pub destination: String,
+}
+
+impl OtpFactor {
+    /// Destino parcialmente oculto para mostrarlo al usuario.
+    pub fn masked_destination(&self) -> String {
+        match self.channel {
+            OtpChannel::Email => match self.destination.split_once('@') {
+                Some((local, domain)) => {
+                    format!("{}***@{}", local.chars().next().unwrap_or('*'), domain)
+                }
+                None => "***".to_string(),
+            },
+            OtpChannel::Sms => {
+                let digits: Vec<char> = self.destination.chars().collect();
+                let visible = digits.len().saturating_sub(2);
+                format!("***{}", digits[visible..].iter().collect::<String>())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct EnrollOtp {
+    pub channel: OtpChannel,
+    /// Con `sms`, número internacional (`+34 600 12 34 56`, `0034600123456`);
+    /// se guarda en E.164. Si falta se usa el teléfono verificado del perfil
+    #[validate(custom = "validate_phone")]
+    pub phone: Option<String>,
+}
+
+impl EnrollOtp {
+    /// Teléfono en E.164, igual que el del perfil (ver `normalize_phone`).
+    pub fn normalized_phone(&self) -> Option<String> {
+        self.phone.as_deref().and_then(normalize_phone)
+    }
+}
+
+/// Teléfono en formato E.164: `+` y de 7 a 15 dígitos sin cero inicial.
+pub fn validate_e164(phone: &str) -> Result<(), ValidationError> {
+    let digits = match phone.strip_prefix('+') {
+        Some(digits) => digits,
+        None => return Err(ValidationError::new("e164")),
+    };
+    if (7..=15).contains(&digits.len())
+        && !digits.starts_with('0')
+        && digits.chars().all(|c| c.is_ascii_digit())
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("e164"))
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ConfirmOtp {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MfaVerify {
+    /// `mfa_token` devuelto por el login
+    pub mfa_token: String,
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MfaResend {
+    pub mfa_token: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enroll(phone: &str) -> EnrollOtp {
+        EnrollOtp {
+            channel: OtpChannel::Sms,
+            phone: Some(phone.to_string()),
+        }
+    }
+
+    #[test]
+    fn enrollment_phones_are_stored_in_e164() {
+        for phone in ["+34 600 12 34 56", "0034-600-123-456", "+34 (600) 123.456", "+34600123456"] {
+            let enroll = enroll(phone);
+            assert!(enroll.validate().is_ok(), "{} was rejected", phone);
+            assert_eq!(enroll.normalized_phone().as_deref(), Some("+34600123456"));
+        }
+        for phone in ["600123456", "+34 600 ABC 456", "+0034600123456", "+12345"] {
+            assert!(enroll(phone).validate().is_err(), "{} was accepted", phone);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use actix_multipart::form::{bytes::Bytes, MultipartForm};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::models::otp::validate_e164;
+
+/// Perfil del usuario tal como lo devuelve `GET /profile`.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct Profile {
+    pub id: i64,
+    pub email: String,
+    pub name: String,
+    /// Avatar subido (tamaño mayor) o URL externa
+    pub avatar_url: Option<String>,
+    /// URL de cada miniatura del avatar subido: `{"64": ..., "128": ..., "256": ...}`
+    #[schema(value_type = Option<Object>)]
+    pub avatar_thumbnails: Option<serde_json::Value>,
+    /// Prefijo de los ficheros del avatar subido en el `BlobStore`
+    #[serde(skip)]
+    pub avatar_key: Option<String>,
+    /// Etiqueta BCP 47 (`es-ES`)
+    pub locale: Option<String>,
+    /// Zona horaria IANA (`Europe/Madrid`)
+    pub timezone: Option<String>,
+    /// E.164 (`+34600123456`)
+    pub phone_number: Option<String>,
+    pub phone_verified_at: Option<DateTime<Utc>>,
+    /// Datos libres de la aplicación, validados con el JSON Schema del tenant
+    #[schema(value_type = Object)]
+    pub metadata: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+/// Distingue un campo ausente (`None`) de uno a `null` (`Some(None)`).
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Cambios de `PATCH /profile`: los campos ausentes no se tocan y `null`
+/// borra los opcionales.
+#[derive(Debug, Default, Deserialize, Validate, ToSchema)]
+pub struct UpdateProfile {
+    #[validate(length(min = 3, max = 255))]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    #[validate(url, length(max = 2048))]
+    pub avatar_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    #[validate(custom = "validate_locale")]
+    pub locale: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    #[validate(custom = "validate_timezone")]
+    pub timezone: Option<Option<String>>,
+    /// Se normaliza a E.164 (`+34 600 12 34 56` y `0034...` valen); cambiarlo
+    /// anula la verificación del anterior
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    #[validate(custom = "validate_phone")]
+    pub phone_number: Option<Option<String>>,
+    /// Se fusiona con el actual como JSON Merge Patch (RFC 7386): `null` borra la clave
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Cuerpo `multipart/form-data` de `PUT /profile/avatar`.
+#[derive(MultipartForm, ToSchema)]
+pub struct AvatarUpload {
+    /// Imagen PNG, JPEG o WebP
+    #[schema(value_type = String, format = Binary)]
+    pub avatar: Bytes,
+}
+
+/// Pasa un teléfono internacional a E.164 quitando espacios, guiones, puntos
+/// y paréntesis; el prefijo `00` equivale a `+`. Sin prefijo internacional
+/// no se puede saber el país, así que se rechaza.
+pub fn normalize_phone(phone: &str) -> Option<String> {
+    let compact: String = phone
+        .chars()
+        .filter(|c| !c.is_whitespace() && !"-.()".contains(*c))
+        .collect();
+    let normalized = match compact.strip_prefix("00") {
+        Some(rest) => format!("+{}", rest),
+        None => compact,
+    };
+    validate_e164(&normalized).ok().map(|_| normalized)
+}
+
+pub fn validate_phone(phone: &str) -> Result<(), ValidationError> {
+    match normalize_phone(phone) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("e164")),
+    }
+}
+
+/// Etiqueta BCP 47 simplificada: subetiquetas alfanuméricas separadas por
+/// `-`, la primera de 2 a 3 letras.
+pub fn validate_locale(locale: &str) -> Result<(), ValidationError> {
+    let mut parts = locale.split('-');
+    let language_ok = parts
+        .next()
+        .is_some_and(|l| (2..=3).contains(&l.len()) && l.chars().all(|c| c.is_ascii_alphabetic()));
+    if language_ok
+        && locale.len() <= 35
+        && parts.all(|p| (1..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()))
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("locale"))
+    }
+}
+
+/// Nombre IANA (`Europe/Madrid`, `America/Argentina/Buenos_Aires`) o `UTC`.
+/// Solo se comprueba la forma; no hay base de datos de zonas.
+pub fn validate_timezone(timezone: &str) -> Result<(), ValidationError> {
+    let valid = timezone.len() <= 64
+        && timezone.split('/').all(|part| {
+            !part.is_empty()
+                && part.starts_with(|c: char| c.is_ascii_alphabetic())
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || "_-+".contains(c))
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("timezone"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phones_are_normalized_to_e164() {
+        assert_eq!(normalize_phone(" +1 (415) 555-0132 ").as_deref(), Some("+14155550132"));
+        assert_eq!(normalize_phone("0044 20.7946.0958").as_deref(), Some("+442079460958"));
+        // Sin prefijo internacional no se sabe el país
+        assert_eq!(normalize_phone("415 555 0132"), None);
+        assert_eq!(normalize_phone("+1 415 555 01x2"), None);
+        assert_eq!(normalize_phone("+1234567890123456"), None);
+    }
+
+    #[test]
+    fn differently_formatted_numbers_are_the_same_phone() {
+        // La unicidad se comprueba sobre la forma normalizada
+        let formats = ["+34 600 12 34 56", "0034 600-123-456", "+34 (600) 123 456"];
+        let normalized: Vec<_> = formats.iter().map(|phone| normalize_phone(phone)).collect();
+        assert!(normalized.iter().all(|phone| phone.as_deref() == Some("+34600123456")));
+    }
+}
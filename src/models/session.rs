@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+// Valores de `amr` (métodos con los que se autenticó la sesión)
+pub const AMR_PASSWORD: &str = "password";
+pub const AMR_OTP: &str = "otp";
+pub const AMR_MAGIC_LINK: &str = "magic_link";
+pub const AMR_OIDC: &str = "oidc";
+
+/// Sesión de usuario tal como la guarda el `SessionStore`.
+///
+/// Los instantes son segundos Unix, igual que `exp` en el JWT.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub user_id: i64,
+    pub email: String,
+    pub name: String,
+    pub token: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    #[serde(default)]
+    pub last_seen_at: i64,
+    /// Última vez que el usuario demostró su identidad (login o reautenticación).
+    /// Las sesiones anteriores a este campo cuentan como no recientes.
+    #[serde(default)]
+    pub auth_time: i64,
+    #[serde(default)]
+    pub amr: Vec<String>,
+    /// Organización activa (ver `POST /organizations/switch`).
+    #[serde(default)]
+    pub org_id: Option<i64>,
+}
+
+impl Session {
+    /// La autenticación es más reciente que `max_age` segundos.
+    pub fn authenticated_within(&self, max_age: i64, now: i64) -> bool {
+        now - self.auth_time <= max_age
+    }
+}
+
+/// Vista pública de una sesión: nunca incluye el token.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionSummary {
+    pub id: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub last_seen_at: i64,
+    pub auth_time: i64,
+    pub amr: Vec<String>,
+    pub org_id: Option<i64>,
+    pub current: bool,
+}
+
+impl SessionSummary {
+    pub fn new(session: &Session, current_id: &str) -> Self {
+        Self {
+            id: session.id.clone(),
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+            last_seen_at: session.last_seen_at,
+            auth_time: session.auth_time,
+            amr: session.amr.clone(),
+            org_id: session.org_id,
+            current: session.id == current_id,
+        }
+    }
+}
+
+/// Cómo recibe el cliente la sesión al hacer login.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionMode {
+    /// Token en el cuerpo, enviado luego en `Authorization: Bearer`.
+    #[default]
+    Token,
+    /// Cookie `HttpOnly` más token CSRF para navegadores.
+    Cookie,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LoginQuery {
+    /// `token` (por defecto) o `cookie`
+    #[param(value_type = Option<String>)]
+    pub mode: Option<SessionMode>,
+}
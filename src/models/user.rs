@@ -3,7 +3,7 @@ use validator::Validate;
 use sqlx::FromRow;
 use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct User {
     pub id: i64,
     pub email: String,
@@ -16,10 +16,13 @@ pub struct User {
 pub struct NewUser {
     #[validate(email)]
     pub email: String,
-    #[validate(length(min = 8))]
+    /// Se valida con la política de contraseñas configurada
     pub password: String,
     #[validate(length(min = 3))]
     pub name: String,
+    /// Respuesta del widget CAPTCHA, si el tenant lo exige
+    #[serde(default, skip_serializing)]
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
@@ -31,6 +34,44 @@ pub struct LoginUser {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenClaims {
+    /// Emisor del tenant; los tokens de un tenant no valen en otro
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
     pub sub: i64,
+    pub sid: String,
     pub exp: i64,
-} 
\ No newline at end of file
+    /// Última autenticación: el login o la reautenticación que emitió el token
+    #[serde(default)]
+    pub auth_time: i64,
+    #[serde(default)]
+    pub amr: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<i64>,
+} 
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ChangePassword {
+    /// Obligatoria salvo que la sesión se haya autenticado también por otra
+    /// vía (código, enlace mágico o proveedor externo): cuentas sin contraseña
+    #[serde(default)]
+    pub current_password: Option<String>,
+    /// Se valida con la política de contraseñas configurada
+    pub new_password: String,
+}
+
+/// Cambio de contraseña obligatorio cuando la actual ha caducado y no hay sesión.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ExpiredPasswordChange {
+    #[validate(email)]
+    pub email: String,
+    pub current_password: String,
+    /// Se valida con la política de contraseñas configurada
+    pub new_password: String,
+}
+
+/// Reautenticación con la contraseña o con el código enviado por
+/// `POST /auth/reauthenticate/otp`; exactamente uno de los dos.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Reauthenticate {
+    pub password: Option<String>,
+    pub code: Option<String>,
+}
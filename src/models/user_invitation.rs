@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct NewUserInvitation {
+    #[validate(email)]
+    pub email: String,
+    /// Nombre provisional; el usuario lo elige al aceptar
+    #[validate(length(min = 3))]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct UserInvitation {
+    pub id: i64,
+    pub user_id: i64,
+    pub email: String,
+    pub invited_by: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Se valida con las mismas reglas que `NewUser`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AcceptInvite {
+    /// Token del enlace de invitación recibido por correo
+    pub token: String,
+    pub name: String,
+    pub password: String,
+}
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct WebhookEndpoint {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct NewWebhookEndpoint {
+    #[validate(url)]
+    pub url: String,
+    #[validate(length(min = 1))]
+    pub events: Vec<String>,
+    /// Secreto para la firma HMAC; si se omite se genera uno.
+    #[validate(length(min = 16, max = 128))]
+    pub secret: Option<String>,
+}
+
+/// Respuesta de creación: el secreto solo se muestra esta vez.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedWebhookEndpoint {
+    pub secret: String,
+    #[serde(flatten)]
+    pub endpoint: WebhookEndpoint,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub outbox_id: Uuid,
+    pub event_type: String,
+    pub attempt: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    pub created_at: DateTime<Utc>,
+}
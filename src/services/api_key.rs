@@ -0,0 +1,43 @@
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+/// Prefijo reconocible para que las claves se detecten en logs y escáneres de secretos.
+pub const KEY_PREFIX: &str = "rak";
+
+const LOOKUP_PREFIX_LEN: usize = 8;
+const SECRET_LEN: usize = 40;
+
+/// Genera una clave nueva y devuelve `(clave_completa, prefijo_de_búsqueda)`.
+pub fn generate() -> (String, String) {
+    let mut rng = rand::thread_rng();
+    let mut random = |len: usize| -> String {
+        (&mut rng)
+            .sample_iter(&Alphanumeric)
+            .take(len)
+            .map(char::from)
+            .collect()
+    };
+
+    let prefix = random(LOOKUP_PREFIX_LEN);
+    let secret = random(SECRET_LEN);
+    (format!("{}_{}_{}", KEY_PREFIX, prefix, secret), prefix)
+}
+
+/// Extrae el prefijo de búsqueda de una clave con formato `rak_<prefijo>_<secreto>`.
+pub fn parse_prefix(key: &str) -> Option<&str> {
+    let mut parts = key.splitn(3, '_');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(KEY_PREFIX), Some(prefix), Some(secret))
+            if prefix.len() == LOOKUP_PREFIX_LEN && secret.len() == SECRET_LEN =>
+        {
+            Some(prefix)
+        }
+        _ => None,
+    }
+}
+
+/// Las claves tienen entropía suficiente para que SHA-256 sea adecuado (no hace falta bcrypt).
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
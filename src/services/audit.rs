@@ -0,0 +1,153 @@
+use actix_web::{web, HttpRequest};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::net::IpAddr;
+
+use crate::config::proxies::TrustedProxies;
+use crate::config::tenants::{Tenant, DEFAULT_TENANT};
+
+/// Tipos de evento registrados en `auth_events`.
+#[derive(Debug, Clone, Copy)]
+pub enum AuthEventType {
+    Register,
+    Login,
+    Logout,
+    PasswordChanged,
+    MagicLinkRequested,
+    MfaChallenge,
+    MfaEnabled,
+    MfaDisabled,
+    Reauthenticated,
+    ProfileUpdated,
+    PhoneVerified,
+    DataExportRequested,
+    DataExportDownloaded,
+    AccountDeleted,
+    OrganizationCreated,
+    MemberInvited,
+    MemberJoined,
+    MemberRoleChanged,
+    MemberRemoved,
+    ApiKeyCreated,
+    ApiKeyRevoked,
+    SessionRevoked,
+    AdminAction,
+}
+
+impl AuthEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthEventType::Register => "register",
+            AuthEventType::Login => "login",
+            AuthEventType::Logout => "logout",
+            AuthEventType::PasswordChanged => "password.changed",
+            AuthEventType::MagicLinkRequested => "magic_link.requested",
+            AuthEventType::MfaChallenge => "mfa.challenge",
+            AuthEventType::MfaEnabled => "mfa.enabled",
+            AuthEventType::MfaDisabled => "mfa.disabled",
+            AuthEventType::Reauthenticated => "reauthenticated",
+            AuthEventType::ProfileUpdated => "profile.updated",
+            AuthEventType::PhoneVerified => "phone.verified",
+            AuthEventType::DataExportRequested => "data_export.requested",
+            AuthEventType::DataExportDownloaded => "data_export.downloaded",
+            AuthEventType::AccountDeleted => "account.deleted",
+            AuthEventType::OrganizationCreated => "organization.created",
+            AuthEventType::MemberInvited => "organization.member_invited",
+            AuthEventType::MemberJoined => "organization.member_joined",
+            AuthEventType::MemberRoleChanged => "organization.member_role_changed",
+            AuthEventType::MemberRemoved => "organization.member_removed",
+            AuthEventType::ApiKeyCreated => "api_key.created",
+            AuthEventType::ApiKeyRevoked => "api_key.revoked",
+            AuthEventType::SessionRevoked => "session.revoked",
+            AuthEventType::AdminAction => "admin.action",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+        }
+    }
+}
+
+/// Origen de la petición que genera el evento.
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    pub tenant_id: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl RequestContext {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        RequestContext {
+            tenant_id: req
+                .app_data::<web::Data<Tenant>>()
+                .map(|tenant| tenant.id.clone())
+                .unwrap_or_else(|| DEFAULT_TENANT.to_string()),
+            ip: client_ip(req).map(|ip| ip.to_string()),
+            user_agent: req
+                .headers()
+                .get(actix_web::http::header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+        }
+    }
+}
+
+/// IP del cliente. `X-Forwarded-For` solo cuenta si el par TCP está en
+/// `TRUSTED_PROXIES`; si no, cualquiera podría escribir la IP que quisiera.
+fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip();
+    let proxies = match req.app_data::<web::Data<TrustedProxies>>() {
+        Some(proxies) => proxies,
+        None => return Some(peer),
+    };
+
+    // Varias cabeceras equivalen a una con los valores unidos por comas
+    let forwarded_for = req
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    Some(proxies.client_ip(peer, Some(&forwarded_for)))
+}
+
+/// Añade un evento al registro de auditoría.
+///
+/// Un fallo al escribir el evento se registra en el log pero no interrumpe la petición.
+pub async fn record(
+    pool: &PgPool,
+    user_id: Option<i64>,
+    event_type: AuthEventType,
+    outcome: Outcome,
+    context: &RequestContext,
+    details: Value,
+) {
+    if let Err(e) = sqlx::query(
+        "INSERT INTO auth_events (tenant_id, user_id, event_type, outcome, ip, user_agent, details) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(&context.tenant_id)
+    .bind(user_id)
+    .bind(event_type.as_str())
+    .bind(outcome.as_str())
+    .bind(&context.ip)
+    .bind(&context.user_agent)
+    .bind(details)
+    .execute(pool)
+    .await
+    {
+        log::error!("Error recording auth event {}: {}", event_type.as_str(), e);
+    }
+}
@@ -1,10 +1,838 @@
 // Servicios de autenticación
-#[allow(dead_code)]
-pub struct AuthService;
+use jsonwebtoken::{encode, EncodingKey, Header};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::config::password::{PasswordPolicy, PasswordSettings};
+use crate::config::session::SessionSettings;
+use crate::models::otp::OtpFactor;
+use crate::models::session::{Session, AMR_OTP, AMR_PASSWORD};
+use crate::models::user::{
+    ChangePassword, ExpiredPasswordChange, LoginUser, NewUser, TokenClaims, User,
+};
+use crate::services::password::{self, PasswordError};
+use crate::services::password_policy;
+use crate::services::session::{SessionStore, SessionStoreError};
+use crate::services::users::{RepositoryError, UserRepository};
+
+#[derive(Debug)]
+pub enum AuthError {
+    Validation(ValidationErrors),
+    EmailTaken,
+    UnknownEmail,
+    InvalidPassword { user_id: i64 },
+    PasswordExpired { user_id: i64 },
+    UserNotFound,
+    SessionNotFound,
+    Hashing(PasswordError),
+    Token(jsonwebtoken::errors::Error),
+    Repository(RepositoryError),
+    Session(SessionStoreError),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Validation(e) => write!(f, "Validation error: {}", e),
+            AuthError::EmailTaken => write!(f, "Email already registered"),
+            AuthError::UnknownEmail => write!(f, "Unknown email"),
+            AuthError::InvalidPassword { user_id } => {
+                write!(f, "Invalid password for user {}", user_id)
+            }
+            AuthError::PasswordExpired { user_id } => {
+                write!(f, "Password expired for user {}", user_id)
+            }
+            AuthError::UserNotFound => write!(f, "User not found"),
+            AuthError::SessionNotFound => write!(f, "Session not found"),
+            AuthError::Hashing(e) => write!(f, "Password hashing error: {}", e),
+            AuthError::Token(e) => write!(f, "Error generating token: {}", e),
+            AuthError::Repository(e) => write!(f, "{}", e),
+            AuthError::Session(e) => write!(f, "Session store error: {}", e),
+        }
+    }
+}
+
+impl From<RepositoryError> for AuthError {
+    fn from(e: RepositoryError) -> Self {
+        match e {
+            RepositoryError::EmailTaken => AuthError::EmailTaken,
+            e => AuthError::Repository(e),
+        }
+    }
+}
+
+impl From<SessionStoreError> for AuthError {
+    fn from(e: SessionStoreError) -> Self {
+        AuthError::Session(e)
+    }
+}
+
+/// Resultado de un login con credenciales válidas.
+#[derive(Debug)]
+pub enum LoginOutcome {
+    Authenticated(User, Session),
+    /// El usuario tiene segundo factor: falta el paso de MFA antes de abrir sesión.
+    /// Incluye los métodos (`amr`) ya superados.
+    MfaRequired(User, OtpFactor, Vec<String>),
+}
+
+/// Reglas de registro, login, sesiones y contraseñas.
+///
+/// No conoce HTTP: los handlers traducen `AuthError` a respuestas y se
+/// encargan de auditoría y webhooks.
+pub struct AuthService {
+    users: Arc<dyn UserRepository>,
+    sessions: Arc<dyn SessionStore>,
+    settings: SessionSettings,
+    passwords: PasswordSettings,
+    policy: PasswordPolicy,
+    jwt_secret: String,
+    jwt_issuer: Option<String>,
+    /// Hash con los parámetros actuales que se verifica cuando el correo no
+    /// existe, para que el tiempo de respuesta no lo delate.
+    dummy_hash: OnceCell<String>,
+}
 
 impl AuthService {
-    #[allow(dead_code)]
-    pub fn new() -> Self {
-        Self
+    pub fn new(
+        users: Arc<dyn UserRepository>,
+        sessions: Arc<dyn SessionStore>,
+        settings: SessionSettings,
+        passwords: PasswordSettings,
+        policy: PasswordPolicy,
+        jwt_secret: String,
+        jwt_issuer: Option<String>,
+    ) -> Self {
+        Self {
+            users,
+            sessions,
+            settings,
+            passwords,
+            policy,
+            jwt_secret,
+            jwt_issuer,
+            dummy_hash: OnceCell::new(),
+        }
+    }
+
+    pub async fn register(&self, new_user: &NewUser) -> Result<User, AuthError> {
+        let password_hash = self.new_user_password_hash(new_user).await?;
+        let user = self
+            .users
+            .create(&new_user.email, &password_hash, &new_user.name)
+            .await?;
+        Ok(user)
+    }
+
+    /// Aplica las reglas de `NewUser` y la política de contraseñas, y devuelve
+    /// el hash de la contraseña. Lo usan el registro y la aceptación de invitaciones.
+    pub async fn new_user_password_hash(&self, new_user: &NewUser) -> Result<String, AuthError> {
+        new_user.validate().map_err(AuthError::Validation)?;
+        password_policy::check(
+            &self.policy,
+            "password",
+            &new_user.password,
+            &new_user.email,
+            &new_user.name,
+        )
+        .await
+        .map_err(AuthError::Validation)?;
+
+        self.hash_password(&new_user.password).await
+    }
+
+    /// Verifica las credenciales y abre una sesión nueva, o pide el segundo factor.
+    ///
+    /// Con la contraseña caducada no se abre sesión: hay que cambiarla con
+    /// `change_expired_password`.
+    pub async fn login(&self, credentials: &LoginUser) -> Result<LoginOutcome, AuthError> {
+        let user = self
+            .verify_credentials(&credentials.email, &credentials.password)
+            .await?;
+
+        if self.password_expired(&user).await? {
+            return Err(AuthError::PasswordExpired { user_id: user.id });
+        }
+
+        self.begin_session(user, vec![AMR_PASSWORD.to_string()]).await
+    }
+
+    /// Paso común a todos los métodos de login tras el primer factor.
+    ///
+    /// La caducidad de la contraseña no se comprueba aquí sino en `login`: el
+    /// enlace mágico y OIDC no usan la contraseña, y los usuarios creados por
+    /// OIDC tienen una aleatoria que no conocen, así que no podrían cambiarla.
+    pub async fn begin_session(&self, user: User, amr: Vec<String>) -> Result<LoginOutcome, AuthError> {
+        if let Some(factor) = self.users.otp_factor(user.id).await? {
+            return Ok(LoginOutcome::MfaRequired(user, factor, amr));
+        }
+
+        let session = self.start_session(&user, amr).await?;
+        Ok(LoginOutcome::Authenticated(user, session))
+    }
+
+    /// Abre la sesión una vez verificado el segundo factor.
+    pub async fn complete_mfa(
+        &self,
+        user_id: i64,
+        mut amr: Vec<String>,
+    ) -> Result<(User, Session), AuthError> {
+        let user = match self.users.find_by_id(user_id).await? {
+            Some(user) => user,
+            None => return Err(AuthError::UserNotFound),
+        };
+
+        amr.push(AMR_OTP.to_string());
+        let session = self.start_session(&user, amr).await?;
+        Ok((user, session))
+    }
+
+    /// Vuelve a pedir la contraseña en una sesión abierta (ver `confirm_reauthentication`).
+    pub async fn reauthenticate(&self, session: &Session, password: &str) -> Result<Session, AuthError> {
+        let user = match self.users.find_by_id(session.user_id).await? {
+            Some(user) => user,
+            None => return Err(AuthError::UserNotFound),
+        };
+
+        if !self.verify_password(password, &user.password).await? {
+            return Err(AuthError::InvalidPassword { user_id: user.id });
+        }
+
+        self.confirm_reauthentication(session, AMR_PASSWORD).await
+    }
+
+    /// El usuario acaba de demostrar su identidad con `method` (contraseña,
+    /// código o proveedor externo): renueva el `auth_time` de la sesión, añade
+    /// el método a sus `amr` y emite un token nuevo con ambos claims; el
+    /// anterior deja de ser válido.
+    pub async fn confirm_reauthentication(
+        &self,
+        session: &Session,
+        method: &str,
+    ) -> Result<Session, AuthError> {
+        let mut session = session.clone();
+        session.auth_time = chrono::Utc::now().timestamp();
+        // Los métodos ya superados (p. ej. `otp`) siguen valiendo
+        if !session.amr.iter().any(|m| m == method) {
+            session.amr.push(method.to_string());
+        }
+        self.reissue(session).await
+    }
+
+    /// Sesión abierta por su id; `None` si caducó o se revocó.
+    pub async fn find_session(&self, session_id: &str) -> Result<Option<Session>, AuthError> {
+        Ok(self.sessions.get(session_id).await?)
+    }
+
+    pub async fn enable_otp_factor(&self, user_id: i64, factor: &OtpFactor) -> Result<(), AuthError> {
+        Ok(self.users.set_otp_factor(user_id, factor).await?)
+    }
+
+    pub async fn disable_otp_factor(&self, user_id: i64) -> Result<bool, AuthError> {
+        Ok(self.users.remove_otp_factor(user_id).await?)
+    }
+
+    /// Cambia una contraseña caducada con las credenciales actuales y abre sesión.
+    pub async fn change_expired_password(
+        &self,
+        change: &ExpiredPasswordChange,
+    ) -> Result<LoginOutcome, AuthError> {
+        change.validate().map_err(AuthError::Validation)?;
+
+        let user = self
+            .verify_credentials(&change.email, &change.current_password)
+            .await?;
+        self.set_password(&user, "new_password", &change.new_password)
+            .await?;
+
+        self.begin_session(user, vec![AMR_PASSWORD.to_string()]).await
+    }
+
+    async fn verify_credentials(&self, email: &str, password: &str) -> Result<User, AuthError> {
+        let user = match self.users.find_by_email(email).await? {
+            Some(user) => user,
+            None => {
+                let dummy_hash = self
+                    .dummy_hash
+                    .get_or_try_init(|| async {
+                        self.hash_password(&Uuid::new_v4().to_string()).await
+                    })
+                    .await?;
+                self.verify_password(password, dummy_hash).await?;
+                return Err(AuthError::UnknownEmail);
+            }
+        };
+
+        if !self.verify_password(password, &user.password).await? {
+            return Err(AuthError::InvalidPassword { user_id: user.id });
+        }
+
+        if password::needs_rehash(&self.passwords, &user.password) {
+            self.rehash(&user, password).await;
+        }
+        Ok(user)
+    }
+
+    async fn password_expired(&self, user: &User) -> Result<bool, AuthError> {
+        let max_age = match self.policy.max_age {
+            Some(max_age) => max_age,
+            None => return Ok(false),
+        };
+
+        Ok(match self.users.password_changed_at(user.id).await? {
+            Some(changed_at) => changed_at + max_age <= chrono::Utc::now(),
+            None => false,
+        })
+    }
+
+    /// Emite el JWT y guarda la sesión de un usuario ya autenticado.
+    pub async fn start_session(&self, user: &User, amr: Vec<String>) -> Result<Session, AuthError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        // El JWT vive lo que el límite absoluto; la inactividad la controla el store
+        let expires_at = now + self.settings.absolute_timeout;
+
+        let session_id = Uuid::new_v4().to_string();
+        let claims = TokenClaims {
+            iss: self.jwt_issuer.clone(),
+            sub: user.id,
+            sid: session_id.clone(),
+            exp: expires_at,
+            auth_time: now,
+            amr: amr.clone(),
+            org_id: None,
+        };
+        let token = self.encode_token(&claims)?;
+
+        let session = Session {
+            id: session_id,
+            user_id: user.id,
+            email: user.email.clone(),
+            name: user.name.clone(),
+            token,
+            created_at: now,
+            expires_at,
+            last_seen_at: now,
+            auth_time: now,
+            amr,
+            org_id: None,
+        };
+
+        self.sessions
+            .create(&session, self.settings.ttl_seconds(now, expires_at))
+            .await?;
+        // El webhook `user.login` va en la transacción de `record_login`: si
+        // falla, la sesión se revoca para no abrir una sesión sin su evento
+        if let Err(e) = self.users.record_login(user.id, &session.amr).await {
+            if let Err(revoke_error) = self.sessions.revoke(&session.id).await {
+                log::error!("Could not revoke session {}: {}", session.id, revoke_error);
+            }
+            return Err(e.into());
+        }
+        Ok(session)
+    }
+
+    fn encode_token(&self, claims: &TokenClaims) -> Result<String, AuthError> {
+        encode(
+            &Header::default(),
+            claims,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        )
+        .map_err(AuthError::Token)
+    }
+
+    /// Cambia la organización activa de la sesión y emite un token nuevo con
+    /// su `org_id`; el anterior deja de ser válido.
+    ///
+    /// La pertenencia a la organización la comprueba quien llama.
+    pub async fn switch_organization(
+        &self,
+        session: &Session,
+        org_id: Option<i64>,
+    ) -> Result<Session, AuthError> {
+        let mut session = session.clone();
+        session.org_id = org_id;
+        self.reissue(session).await
+    }
+
+    /// Firma un token nuevo con los datos actuales de la sesión y lo guarda.
+    /// Si la sesión desapareció entretanto, no se resucita.
+    async fn reissue(&self, mut session: Session) -> Result<Session, AuthError> {
+        session.token = self.encode_token(&TokenClaims {
+            iss: self.jwt_issuer.clone(),
+            sub: session.user_id,
+            sid: session.id.clone(),
+            exp: session.expires_at,
+            auth_time: session.auth_time,
+            amr: session.amr.clone(),
+            org_id: session.org_id,
+        })?;
+
+        let now = chrono::Utc::now().timestamp();
+        session.last_seen_at = now;
+        if !self
+            .sessions
+            .update(&session, self.settings.ttl_seconds(now, session.expires_at))
+            .await?
+        {
+            return Err(AuthError::SessionNotFound);
+        }
+        Ok(session)
+    }
+
+    pub async fn logout(&self, session_id: &str) -> Result<bool, AuthError> {
+        Ok(self.sessions.revoke(session_id).await?)
+    }
+
+    pub async fn logout_all(&self, user_id: i64) -> Result<u64, AuthError> {
+        Ok(self.sessions.revoke_all_for_user(user_id).await?)
+    }
+
+    pub async fn list_sessions(&self, user_id: i64) -> Result<Vec<Session>, AuthError> {
+        Ok(self.sessions.list_for_user(user_id).await?)
+    }
+
+    /// Cambia la contraseña comprobando la actual y cierra las demás sesiones.
+    ///
+    /// Devuelve cuántas sesiones se revocaron.
+    pub async fn change_password(
+        &self,
+        current_session: &Session,
+        change: &ChangePassword,
+    ) -> Result<u64, AuthError> {
+        change.validate().map_err(AuthError::Validation)?;
+
+        let user = match self.users.find_by_id(current_session.user_id).await? {
+            Some(user) => user,
+            None => return Err(AuthError::UserNotFound),
+        };
+
+        match &change.current_password {
+            Some(current_password) => {
+                if !self.verify_password(current_password, &user.password).await? {
+                    return Err(AuthError::InvalidPassword { user_id: user.id });
+                }
+            }
+            // Las cuentas sin contraseña la fijan tras demostrar su identidad
+            // por otra vía; que sea reciente lo comprueba el handler
+            None if current_session.amr.iter().any(|method| method != AMR_PASSWORD) => {}
+            None => {
+                let mut errors = ValidationErrors::new();
+                errors.add("current_password", ValidationError::new("required"));
+                return Err(AuthError::Validation(errors));
+            }
+        }
+
+        self.set_password(&user, "new_password", &change.new_password)
+            .await?;
+
+        let mut revoked = 0;
+        for session in self.sessions.list_for_user(user.id).await? {
+            if session.id != current_session.id && self.sessions.revoke(&session.id).await? {
+                revoked += 1;
+            }
+        }
+        Ok(revoked)
+    }
+
+    /// Aplica la política y el historial, y guarda la nueva contraseña.
+    ///
+    /// Es el único camino para fijar una contraseña elegida por el usuario;
+    /// el servicio no tiene flujo de recuperación ("olvidé mi contraseña") y,
+    /// si se añade, debe pasar por aquí.
+    async fn set_password(
+        &self,
+        user: &User,
+        field: &'static str,
+        new_password: &str,
+    ) -> Result<(), AuthError> {
+        password_policy::check(&self.policy, field, new_password, &user.email, &user.name)
+            .await
+            .map_err(AuthError::Validation)?;
+
+        if self.recently_used(user, new_password).await? {
+            let mut errors = ValidationErrors::new();
+            let mut error = ValidationError::new("password_reused");
+            error.message = Some(
+                format!(
+                    "Password must differ from your last {} passwords",
+                    self.policy.history_size
+                )
+                .into(),
+            );
+            errors.add(field, error);
+            return Err(AuthError::Validation(errors));
+        }
+
+        let password_hash = self.hash_password(new_password).await?;
+        if !self.users.set_password(user.id, &password_hash).await? {
+            return Err(AuthError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    /// Compara con la contraseña actual y las del historial configurado.
+    async fn recently_used(&self, user: &User, new_password: &str) -> Result<bool, AuthError> {
+        if self.policy.history_size == 0 {
+            return Ok(false);
+        }
+
+        let mut hashes = self
+            .users
+            .recent_password_hashes(user.id, self.policy.history_size)
+            .await?;
+        // Cuentas anteriores al historial: la actual puede no estar anotada
+        if !hashes.contains(&user.password) {
+            hashes.insert(0, user.password.clone());
+        }
+
+        for stored in hashes {
+            if self.verify_password(new_password, &stored).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub async fn hash_password(&self, password: &str) -> Result<String, AuthError> {
+        password::hash_blocking(&self.passwords, password.to_string())
+            .await
+            .map_err(AuthError::Hashing)
+    }
+
+    async fn verify_password(&self, password: &str, stored: &str) -> Result<bool, AuthError> {
+        match password::verify_blocking(password.to_string(), stored.to_string()).await {
+            Ok(valid) => Ok(valid),
+            // Un hash ilegible no debe dejar entrar, pero tampoco tumbar el login
+            Err(PasswordError::Blocking) => Err(AuthError::Hashing(PasswordError::Blocking)),
+            Err(e) => {
+                log::error!("Unreadable password hash: {}", e);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Migra el hash al algoritmo y parámetros actuales tras un login correcto.
+    ///
+    /// Si falla, el login sigue adelante y se reintenta en el siguiente.
+    async fn rehash(&self, user: &User, password: &str) {
+        let password_hash = match self.hash_password(password).await {
+            Ok(password_hash) => password_hash,
+            Err(e) => {
+                log::error!("Error rehashing password for user {}: {}", user.id, e);
+                return;
+            }
+        };
+
+        match self.users.update_password(user.id, &password_hash).await {
+            Ok(_) => log::info!("Rehashed password for user {}", user.id),
+            Err(e) => log::error!("Error storing rehashed password for user {}: {}", user.id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::password::PasswordAlgorithm;
+    use crate::models::session::AMR_OIDC;
+    use crate::services::session::InMemorySessionStore;
+    use crate::services::users::InMemoryUserRepository;
+
+    const EMAIL: &str = "ada@example.com";
+    const PASSWORD: &str = "Copper-Lantern-Orbit-71";
+
+    struct Fixture {
+        auth: AuthService,
+        users: Arc<InMemoryUserRepository>,
+        sessions: Arc<InMemorySessionStore>,
+    }
+
+    fn fixture(max_age: Option<chrono::Duration>) -> Fixture {
+        let users = Arc::new(InMemoryUserRepository::default());
+        let sessions = Arc::new(InMemorySessionStore::default());
+        let auth = AuthService::new(
+            users.clone(),
+            sessions.clone(),
+            SessionSettings {
+                idle_timeout: 3600,
+                absolute_timeout: 12 * 3600,
+                touch_interval: 60,
+                reauth_max_age: 300,
+            },
+            // Coste mínimo de bcrypt para que las pruebas sean rápidas
+            PasswordSettings {
+                algorithm: PasswordAlgorithm::Bcrypt,
+                argon2_memory_kib: 19 * 1024,
+                argon2_iterations: 2,
+                argon2_parallelism: 1,
+                bcrypt_cost: 4,
+            },
+            PasswordPolicy {
+                min_length: 8,
+                max_length: 128,
+                min_score: 2,
+                breached_corpus_dir: None,
+                history_size: 3,
+                max_age,
+            },
+            "test-secret".to_string(),
+            None,
+        );
+        Fixture { auth, users, sessions }
+    }
+
+    async fn register(auth: &AuthService) -> User {
+        auth.register(&NewUser {
+            email: EMAIL.to_string(),
+            password: PASSWORD.to_string(),
+            name: "Ada Lovelace".to_string(),
+            captcha_token: None,
+        })
+        .await
+        .unwrap()
+    }
+
+    async fn login(auth: &AuthService, password: &str) -> Result<Session, AuthError> {
+        let credentials = LoginUser {
+            email: EMAIL.to_string(),
+            password: password.to_string(),
+        };
+        match auth.login(&credentials).await? {
+            LoginOutcome::Authenticated(_, session) => Ok(session),
+            LoginOutcome::MfaRequired(..) => panic!("unexpected MFA challenge"),
+        }
+    }
+
+    fn change(current: &str, new: &str) -> ChangePassword {
+        ChangePassword {
+            current_password: Some(current.to_string()),
+            new_password: new.to_string(),
+        }
+    }
+
+    fn error_code(e: AuthError, field: &str) -> String {
+        match e {
+            AuthError::Validation(errors) => errors.field_errors()[field][0].code.to_string(),
+            e => panic!("expected validation error, got {}", e),
+        }
+    }
+
+    #[actix_web::test]
+    async fn register_applies_the_policy_and_rejects_duplicates() {
+        let f = fixture(None);
+        let weak = f
+            .auth
+            .register(&NewUser {
+                email: EMAIL.to_string(),
+                password: "password".to_string(),
+                name: "Ada Lovelace".to_string(),
+                captcha_token: None,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error_code(weak, "password"), "password_too_weak");
+
+        let user = register(&f.auth).await;
+        assert_ne!(user.password, PASSWORD);
+
+        let again = f
+            .auth
+            .register(&NewUser {
+                email: EMAIL.to_string(),
+                password: PASSWORD.to_string(),
+                name: "Ada Lovelace".to_string(),
+                captcha_token: None,
+            })
+            .await;
+        assert!(matches!(again, Err(AuthError::EmailTaken)));
+    }
+
+    #[actix_web::test]
+    async fn unknown_emails_still_verify_a_hash() {
+        let f = fixture(None);
+        register(&f.auth).await;
+
+        let credentials = LoginUser {
+            email: "grace@example.com".to_string(),
+            password: PASSWORD.to_string(),
+        };
+        let outcome = f.auth.login(&credentials).await;
+        assert!(matches!(outcome, Err(AuthError::UnknownEmail)));
+        // El hash señuelo tiene los parámetros actuales, así que cuesta lo mismo
+        let dummy_hash = f.auth.dummy_hash.get().unwrap();
+        assert!(!password::needs_rehash(&f.auth.passwords, dummy_hash));
+    }
+
+    #[actix_web::test]
+    async fn emails_are_matched_case_insensitively() {
+        let f = fixture(None);
+        let user = f
+            .auth
+            .register(&NewUser {
+                email: "Ada@Example.COM".to_string(),
+                password: PASSWORD.to_string(),
+                name: "Ada Lovelace".to_string(),
+                captcha_token: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(user.email, EMAIL);
+
+        let again = f
+            .auth
+            .register(&NewUser {
+                email: EMAIL.to_string(),
+                password: PASSWORD.to_string(),
+                name: "Ada Lovelace".to_string(),
+                captcha_token: None,
+            })
+            .await;
+        assert!(matches!(again, Err(AuthError::EmailTaken)));
+
+        let outcome = f
+            .auth
+            .login(&LoginUser {
+                email: "ADA@example.com".to_string(),
+                password: PASSWORD.to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(matches!(outcome, LoginOutcome::Authenticated(u, _) if u.id == user.id));
+    }
+
+    #[actix_web::test]
+    async fn login_opens_a_session_and_logout_revokes_it() {
+        let f = fixture(None);
+        let user = register(&f.auth).await;
+
+        assert!(matches!(
+            login(&f.auth, "Wrong-Password-123").await,
+            Err(AuthError::InvalidPassword { .. })
+        ));
+
+        let session = login(&f.auth, PASSWORD).await.unwrap();
+        assert_eq!(session.user_id, user.id);
+        assert_eq!(session.amr, vec![AMR_PASSWORD.to_string()]);
+        let stored = f.sessions.get(&session.id).await.unwrap().unwrap();
+        assert_eq!(stored.token, session.token);
+        let profile = f.users.profile(user.id).await.unwrap().unwrap();
+        assert!(profile.last_login_at.is_some());
+
+        assert!(f.auth.logout(&session.id).await.unwrap());
+        assert!(f.sessions.get(&session.id).await.unwrap().is_none());
+        assert!(!f.auth.logout(&session.id).await.unwrap());
+    }
+
+    #[actix_web::test]
+    async fn change_password_checks_current_and_revokes_other_sessions() {
+        let f = fixture(None);
+        register(&f.auth).await;
+        let current = login(&f.auth, PASSWORD).await.unwrap();
+        let other = login(&f.auth, PASSWORD).await.unwrap();
+        let new_password = "Granite-Willow-Signal-48";
+
+        let wrong = f
+            .auth
+            .change_password(&current, &change("Wrong-Password-123", new_password))
+            .await;
+        assert!(matches!(wrong, Err(AuthError::InvalidPassword { .. })));
+
+        let revoked = f
+            .auth
+            .change_password(&current, &change(PASSWORD, new_password))
+            .await
+            .unwrap();
+        assert_eq!(revoked, 1);
+        assert!(f.sessions.get(&current.id).await.unwrap().is_some());
+        assert!(f.sessions.get(&other.id).await.unwrap().is_none());
+
+        assert!(login(&f.auth, PASSWORD).await.is_err());
+        assert!(login(&f.auth, new_password).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn change_password_rejects_recent_passwords() {
+        let f = fixture(None);
+        register(&f.auth).await;
+        let session = login(&f.auth, PASSWORD).await.unwrap();
+        let second = "Granite-Willow-Signal-48";
+        let third = "Marble-Falcon-Harbor-26";
+
+        f.auth
+            .change_password(&session, &change(PASSWORD, second))
+            .await
+            .unwrap();
+        let reused = f
+            .auth
+            .change_password(&session, &change(second, PASSWORD))
+            .await
+            .unwrap_err();
+        assert_eq!(error_code(reused, "new_password"), "password_reused");
+
+        // Con `history_size = 3` la primera sale del historial tras tres cambios
+        f.auth
+            .change_password(&session, &change(second, third))
+            .await
+            .unwrap();
+        f.auth
+            .change_password(&session, &change(third, "Velvet-Comet-Meadow-15"))
+            .await
+            .unwrap();
+        f.auth
+            .change_password(&session, &change("Velvet-Comet-Meadow-15", PASSWORD))
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn expired_password_blocks_login_until_changed() {
+        let f = fixture(Some(chrono::Duration::days(90)));
+        let user = register(&f.auth).await;
+        assert!(login(&f.auth, PASSWORD).await.is_ok());
+
+        f.users
+            .set_password_changed_at(user.id, chrono::Utc::now() - chrono::Duration::days(91));
+        assert!(matches!(
+            login(&f.auth, PASSWORD).await,
+            Err(AuthError::PasswordExpired { .. })
+        ));
+
+        let new_password = "Granite-Willow-Signal-48";
+        let outcome = f
+            .auth
+            .change_expired_password(&ExpiredPasswordChange {
+                email: EMAIL.to_string(),
+                current_password: PASSWORD.to_string(),
+                new_password: new_password.to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(matches!(outcome, LoginOutcome::Authenticated(..)));
+        assert!(login(&f.auth, new_password).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn reauthenticate_keeps_amr_and_reissues_the_token() {
+        let f = fixture(None);
+        let user = register(&f.auth).await;
+        let session = f
+            .auth
+            .start_session(&user, vec![AMR_OIDC.to_string()])
+            .await
+            .unwrap();
+
+        let renewed = f.auth.reauthenticate(&session, PASSWORD).await.unwrap();
+        assert_eq!(renewed.id, session.id);
+        assert_eq!(renewed.amr, vec![AMR_OIDC.to_string(), AMR_PASSWORD.to_string()]);
+        assert_ne!(renewed.token, session.token);
+        let stored = f.sessions.get(&session.id).await.unwrap().unwrap();
+        assert_eq!(stored.token, renewed.token);
     }
-} 
\ No newline at end of file
+}
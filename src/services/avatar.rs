@@ -0,0 +1,151 @@
+use actix_web::web;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::Cursor;
+
+use crate::config::profile::ProfileSettings;
+use crate::services::blob_store::{BlobError, BlobStore};
+use crate::services::crypto::random_token;
+
+#[derive(Debug)]
+pub enum AvatarError {
+    /// El tipo declarado o el contenido no es PNG, JPEG ni WebP.
+    UnsupportedType,
+    TooLarge { max_bytes: usize },
+    InvalidImage(String),
+    Storage(BlobError),
+    Blocking,
+}
+
+impl fmt::Display for AvatarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AvatarError::UnsupportedType => write!(f, "Avatar must be a PNG, JPEG or WebP image"),
+            AvatarError::TooLarge { max_bytes } => {
+                write!(f, "Avatar exceeds the maximum size of {} bytes", max_bytes)
+            }
+            AvatarError::InvalidImage(e) => write!(f, "Invalid image: {}", e),
+            AvatarError::Storage(e) => write!(f, "{}", e),
+            AvatarError::Blocking => write!(f, "Image processing was cancelled"),
+        }
+    }
+}
+
+impl From<BlobError> for AvatarError {
+    fn from(e: BlobError) -> Self {
+        AvatarError::Storage(e)
+    }
+}
+
+/// Avatar guardado: prefijo de sus ficheros y URL de cada tamaño.
+pub struct StoredAvatar {
+    pub key: String,
+    /// URL del tamaño mayor
+    pub url: String,
+    /// `{"64": url, "128": url, ...}`
+    pub thumbnails: Value,
+}
+
+fn declared_format(content_type: &str) -> Option<ImageFormat> {
+    match content_type {
+        "image/png" => Some(ImageFormat::Png),
+        "image/jpeg" => Some(ImageFormat::Jpeg),
+        "image/webp" => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+/// Decodifica, recorta al cuadrado central y genera cada tamaño en WebP.
+/// El trabajo de CPU va al pool de hilos bloqueantes.
+async fn render(
+    data: Vec<u8>,
+    format: ImageFormat,
+    sizes: Vec<u32>,
+    max_dimension: u32,
+) -> Result<Vec<(u32, Vec<u8>)>, AvatarError> {
+    web::block(move || {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(max_dimension);
+        limits.max_image_height = Some(max_dimension);
+
+        let mut reader = ImageReader::with_format(Cursor::new(data), format);
+        reader.limits(limits);
+        let image = reader
+            .decode()
+            .map_err(|e| AvatarError::InvalidImage(e.to_string()))?;
+
+        sizes
+            .into_iter()
+            .map(|size| {
+                let thumbnail = image.resize_to_fill(size, size, FilterType::Lanczos3);
+                // El codificador WebP solo admite RGB(A) de 8 bits
+                let thumbnail = DynamicImage::ImageRgba8(thumbnail.to_rgba8());
+                let mut encoded = Cursor::new(Vec::new());
+                thumbnail
+                    .write_to(&mut encoded, ImageFormat::WebP)
+                    .map_err(|e| AvatarError::InvalidImage(e.to_string()))?;
+                Ok((size, encoded.into_inner()))
+            })
+            .collect()
+    })
+    .await
+    .map_err(|_| AvatarError::Blocking)?
+}
+
+/// Valida la subida, genera las miniaturas y las guarda bajo un prefijo
+/// nuevo (`avatars/{tenant}/{user}/{aleatorio}`), así las URL nunca se
+/// reutilizan y pueden cachearse indefinidamente.
+pub async fn process(
+    store: &dyn BlobStore,
+    settings: &ProfileSettings,
+    tenant_id: &str,
+    user_id: i64,
+    content_type: Option<&str>,
+    data: Vec<u8>,
+) -> Result<StoredAvatar, AvatarError> {
+    if data.len() > settings.avatar_max_bytes {
+        return Err(AvatarError::TooLarge {
+            max_bytes: settings.avatar_max_bytes,
+        });
+    }
+    // El tipo declarado y el contenido real deben coincidir
+    let declared = content_type.and_then(declared_format);
+    let detected = image::guess_format(&data).ok();
+    let format = match (declared, detected) {
+        (Some(declared), Some(detected)) if declared == detected => detected,
+        _ => return Err(AvatarError::UnsupportedType),
+    };
+
+    let thumbnails = render(
+        data,
+        format,
+        settings.avatar_sizes.clone(),
+        settings.avatar_max_dimension,
+    )
+    .await?;
+
+    let key = format!("avatars/{}/{}/{}", tenant_id, user_id, random_token(16));
+    let mut urls = Map::new();
+    let mut largest = (0, String::new());
+    for (size, encoded) in thumbnails {
+        let blob_key = format!("{}/{}.webp", key, size);
+        if let Err(e) = store.put(&blob_key, encoded).await {
+            // No dejar miniaturas sueltas de una subida a medias
+            let _ = store.delete_prefix(&key).await;
+            return Err(e.into());
+        }
+        let url = store.url(&blob_key);
+        if size > largest.0 {
+            largest = (size, url.clone());
+        }
+        urls.insert(size.to_string(), Value::String(url));
+    }
+
+    Ok(StoredAvatar {
+        key,
+        url: largest.1,
+        thumbnails: Value::Object(urls),
+    })
+}
@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use std::env;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum BlobError {
+    /// Clave vacía, absoluta o con `..`.
+    InvalidKey,
+    Io(std::io::Error),
+}
+
+impl fmt::Display for BlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlobError::InvalidKey => write!(f, "Invalid blob key"),
+            BlobError::Io(e) => write!(f, "Blob storage error: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for BlobError {
+    fn from(e: std::io::Error) -> Self {
+        BlobError::Io(e)
+    }
+}
+
+/// Contenido guardado y su tipo MIME.
+pub struct Blob {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Almacén de ficheros subidos (avatares). Las claves son rutas relativas
+/// con `/` (`avatars/default/12/abc/128.webp`).
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), BlobError>;
+
+    async fn get(&self, key: &str) -> Result<Option<Blob>, BlobError>;
+
+    /// Borra todo lo que cuelga de `prefix`; no falla si no existe.
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), BlobError>;
+
+    /// URL pública con la que el cliente descarga la clave.
+    fn url(&self, key: &str) -> String;
+}
+
+/// Tipo MIME según la extensión de la clave.
+pub fn content_type(key: &str) -> &'static str {
+    match key.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("webp") => "image/webp",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("json") => "application/json",
+        Some("zip") => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Ficheros en un directorio local, servidos por `GET /media/{key}` (o por
+/// un proxy delante, ajustando `BLOB_PUBLIC_URL`).
+pub struct LocalBlobStore {
+    root: PathBuf,
+    public_url: String,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: PathBuf, public_url: String) -> Self {
+        Self {
+            root,
+            public_url: public_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, BlobError> {
+        let relative = Path::new(key);
+        let safe = !key.is_empty()
+            && !key.contains('\\')
+            && relative.components().all(|c| matches!(c, Component::Normal(_)));
+        if !safe {
+            return Err(BlobError::InvalidKey);
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), BlobError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // Escritura atómica: nunca se sirve un fichero a medias
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, data).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Blob>, BlobError> {
+        let path = self.path(key)?;
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Some(Blob {
+                content_type: content_type(key).to_string(),
+                data,
+            })),
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::IsADirectory) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), BlobError> {
+        let path = self.path(prefix)?;
+        match tokio::fs::remove_dir_all(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+}
+
+/// Elige el almacén según `BLOB_STORE` (por ahora solo `local`).
+pub fn build_blob_store() -> Result<Arc<dyn BlobStore>, String> {
+    match env::var("BLOB_STORE").unwrap_or_else(|_| "local".to_string()).to_lowercase().as_str() {
+        "local" => Ok(Arc::new(LocalBlobStore::new(
+            PathBuf::from(env::var("BLOB_LOCAL_DIR").unwrap_or_else(|_| "./uploads".to_string())),
+            env::var("BLOB_PUBLIC_URL").unwrap_or_else(|_| "/media".to_string()),
+        ))),
+        other => Err(format!("Unknown BLOB_STORE: {}", other)),
+    }
+}
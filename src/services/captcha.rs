@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::registration::RegistrationSettings;
+
+#[derive(Debug)]
+pub struct CaptchaError(pub String);
+
+impl fmt::Display for CaptchaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CAPTCHA verification error: {}", self.0)
+    }
+}
+
+/// Comprueba la respuesta del CAPTCHA antes de crear una cuenta.
+///
+/// `Ok(false)` es una respuesta ausente o rechazada; `Err` indica que no se
+/// pudo consultar al proveedor.
+#[async_trait]
+pub trait CaptchaVerifier: Send + Sync {
+    async fn verify(&self, token: Option<&str>, remote_ip: Option<&str>) -> Result<bool, CaptchaError>;
+}
+
+/// Acepta cualquier petición. Es la implementación sin `CAPTCHA_VERIFY_URL`
+/// (desarrollo y pruebas).
+pub struct NoopCaptchaVerifier;
+
+#[async_trait]
+impl CaptchaVerifier for NoopCaptchaVerifier {
+    async fn verify(&self, _token: Option<&str>, _remote_ip: Option<&str>) -> Result<bool, CaptchaError> {
+        Ok(true)
+    }
+}
+
+/// Proveedores con la API `siteverify` (reCAPTCHA, hCaptcha, Turnstile):
+/// `POST secret=...&response=...&remoteip=...` y respuesta `{"success": bool}`.
+pub struct SiteverifyCaptchaVerifier {
+    http: reqwest::Client,
+    url: String,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+struct SiteverifyResponse {
+    success: bool,
+}
+
+impl SiteverifyCaptchaVerifier {
+    pub fn new(http: reqwest::Client, url: String, secret: String) -> Self {
+        Self { http, url, secret }
+    }
+}
+
+#[async_trait]
+impl CaptchaVerifier for SiteverifyCaptchaVerifier {
+    async fn verify(&self, token: Option<&str>, remote_ip: Option<&str>) -> Result<bool, CaptchaError> {
+        let token = match token {
+            Some(token) if !token.is_empty() => token,
+            _ => return Ok(false),
+        };
+
+        let mut form = vec![("secret", self.secret.as_str()), ("response", token)];
+        if let Some(ip) = remote_ip {
+            form.push(("remoteip", ip));
+        }
+
+        let response = self
+            .http
+            .post(&self.url)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| CaptchaError(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(CaptchaError(format!("provider responded with {}", response.status())));
+        }
+
+        let body = response
+            .json::<SiteverifyResponse>()
+            .await
+            .map_err(|e| CaptchaError(e.to_string()))?;
+        Ok(body.success)
+    }
+}
+
+pub fn build_verifier(
+    settings: &RegistrationSettings,
+    http: reqwest::Client,
+) -> Result<Arc<dyn CaptchaVerifier>, String> {
+    match (&settings.captcha_verify_url, &settings.captcha_secret) {
+        (Some(url), Some(secret)) => Ok(Arc::new(SiteverifyCaptchaVerifier::new(
+            http,
+            url.clone(),
+            secret.clone(),
+        ))),
+        (Some(_), None) => Err("CAPTCHA_SECRET must be set when CAPTCHA_VERIFY_URL is set".to_string()),
+        _ => Ok(Arc::new(NoopCaptchaVerifier)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::registration::load_settings;
+    use crate::config::tenants::Tenant;
+
+    #[test]
+    fn verify_url_without_secret_is_a_configuration_error() {
+        let mut settings = load_settings(&Tenant::for_tests("default", "secret")).unwrap();
+        settings.captcha_verify_url = Some("https://captcha.example.com/siteverify".to_string());
+
+        let error = build_verifier(&settings, reqwest::Client::new()).err().unwrap();
+        assert!(error.contains("CAPTCHA_SECRET"), "{}", error);
+
+        settings.captcha_secret = Some("captcha-secret".to_string());
+        assert!(build_verifier(&settings, reqwest::Client::new()).is_ok());
+    }
+}
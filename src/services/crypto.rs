@@ -0,0 +1,49 @@
+use hmac::{Hmac, Mac};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+/// Cadena alfanumérica aleatoria para tokens, nonces y secretos.
+pub fn random_token(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// SHA-256 en hexadecimal, para guardar tokens aleatorios sin guardar el token.
+pub fn sha256_hex(value: &str) -> String {
+    hex::encode(Sha256::digest(value.as_bytes()))
+}
+
+/// HMAC-SHA256 en hexadecimal con el secreto del servidor.
+///
+/// `purpose` separa los usos para que una firma no valga en otro contexto.
+pub fn sign(secret: &str, purpose: &str, message: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(purpose.as_bytes());
+    mac.update(b".");
+    mac.update(message.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub fn verify_signature(secret: &str, purpose: &str, message: &str, signature: &str) -> bool {
+    sign(secret, purpose, message)
+        .as_bytes()
+        .ct_eq(signature.as_bytes())
+        .into()
+}
+
+/// Token CSRF de una sesión: firma del id de sesión.
+///
+/// Se deriva en vez de guardarse, así que vale lo mismo que la sesión.
+pub fn csrf_token(secret: &str, session_id: &str) -> String {
+    sign(secret, "csrf", session_id)
+}
+
+pub fn verify_csrf_token(secret: &str, session_id: &str, token: &str) -> bool {
+    verify_signature(secret, "csrf", session_id, token)
+}
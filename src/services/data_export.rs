@@ -0,0 +1,375 @@
+use actix_web::web;
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::fmt;
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::models::data_export::DataExport;
+use crate::models::session::SessionSummary;
+use crate::services::blob_store::{BlobError, BlobStore};
+use crate::services::organizations;
+use crate::services::session::{SessionStore, SessionStoreError};
+
+const EXPORT_COLUMNS: &str = "id, status, size_bytes, expires_at, completed_at, created_at, blob_key";
+
+/// Cada cuánto busca el proceso exportaciones pendientes.
+const POLL_INTERVAL: StdDuration = StdDuration::from_secs(5);
+/// Exportaciones que reserva el proceso en cada vuelta.
+const BATCH_SIZE: i64 = 2;
+/// Tiempo que una exportación queda reservada; si vence sin terminar, el
+/// proceso que la generaba ha caído y otro la retoma.
+const LEASE: StdDuration = StdDuration::from_secs(15 * 60);
+/// Espera antes de reintentar una exportación que ha fallado.
+const RETRY_AFTER: StdDuration = StdDuration::from_secs(60);
+
+#[derive(Debug)]
+pub enum ExportError {
+    Database(sqlx::Error),
+    Sessions(SessionStoreError),
+    Storage(BlobError),
+    Archive(String),
+    Blocking,
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Database(e) => write!(f, "Database error: {}", e),
+            ExportError::Sessions(e) => write!(f, "{}", e),
+            ExportError::Storage(e) => write!(f, "{}", e),
+            ExportError::Archive(e) => write!(f, "Could not build export archive: {}", e),
+            ExportError::Blocking => write!(f, "Export worker failed"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ExportError {
+    fn from(e: sqlx::Error) -> Self {
+        ExportError::Database(e)
+    }
+}
+
+impl From<SessionStoreError> for ExportError {
+    fn from(e: SessionStoreError) -> Self {
+        ExportError::Sessions(e)
+    }
+}
+
+impl From<BlobError> for ExportError {
+    fn from(e: BlobError) -> Self {
+        ExportError::Storage(e)
+    }
+}
+
+/// Registra una exportación pendiente; `None` si el usuario ya tiene una en curso.
+///
+/// La genera el proceso de `spawn_worker` del tenant.
+pub async fn create(
+    pool: &PgPool,
+    tenant_id: &str,
+    user_id: i64,
+    ttl: Duration,
+) -> Result<Option<DataExport>, sqlx::Error> {
+    // El índice único parcial impide una segunda exportación pendiente
+    sqlx::query_as::<_, DataExport>(&format!(
+        "INSERT INTO data_exports (tenant_id, user_id, expires_at) VALUES ($1, $2, $3) \
+         ON CONFLICT DO NOTHING RETURNING {}",
+        EXPORT_COLUMNS
+    ))
+    .bind(tenant_id)
+    .bind(user_id)
+    .bind(Utc::now() + ttl)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Exportación en curso del usuario.
+pub async fn pending(pool: &PgPool, user_id: i64) -> Result<Option<DataExport>, sqlx::Error> {
+    sqlx::query_as::<_, DataExport>(&format!(
+        "SELECT {} FROM data_exports WHERE user_id = $1 AND status = 'pending'",
+        EXPORT_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn find(pool: &PgPool, user_id: i64, id: Uuid) -> Result<Option<DataExport>, sqlx::Error> {
+    sqlx::query_as::<_, DataExport>(&format!(
+        "SELECT {} FROM data_exports WHERE id = $1 AND user_id = $2",
+        EXPORT_COLUMNS
+    ))
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Lanza el bucle que genera las exportaciones pendientes del tenant.
+pub fn spawn_worker(
+    pool: PgPool,
+    sessions: Arc<dyn SessionStore>,
+    blobs: Arc<dyn BlobStore>,
+    tenant_id: String,
+    max_attempts: i32,
+) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            match process_due(&pool, sessions.as_ref(), blobs.as_ref(), &tenant_id, max_attempts).await {
+                Ok(0) => {}
+                Ok(count) => log::debug!("Processed {} data export(s) for tenant {}", count, tenant_id),
+                Err(e) => log::error!("Data export worker error for tenant {}: {}", tenant_id, e),
+            }
+        }
+    });
+}
+
+#[derive(sqlx::FromRow)]
+struct DueExport {
+    id: Uuid,
+    user_id: i64,
+    attempts: i32,
+}
+
+/// Reserva un lote de exportaciones pendientes y las genera.
+///
+/// La reserva fija `locked_until`, así que si el proceso cae a mitad de una
+/// exportación otro la retoma cuando vence. Las que agotan los intentos se
+/// marcan como fallidas para que el usuario pueda pedir otra.
+pub async fn process_due(
+    pool: &PgPool,
+    sessions: &dyn SessionStore,
+    blobs: &dyn BlobStore,
+    tenant_id: &str,
+    max_attempts: i32,
+) -> Result<usize, sqlx::Error> {
+    sqlx::query(
+        "UPDATE data_exports SET status = 'failed', completed_at = now(), locked_until = NULL \
+         WHERE tenant_id = $1 AND status = 'pending' AND attempts >= $2 \
+         AND (locked_until IS NULL OR locked_until <= now())",
+    )
+    .bind(tenant_id)
+    .bind(max_attempts)
+    .execute(pool)
+    .await?;
+
+    let due = sqlx::query_as::<_, DueExport>(
+        "WITH due AS ( \
+             SELECT id FROM data_exports \
+             WHERE tenant_id = $1 AND status = 'pending' AND attempts < $2 \
+             AND (locked_until IS NULL OR locked_until <= now()) \
+             ORDER BY created_at LIMIT $3 FOR UPDATE SKIP LOCKED \
+         ) \
+         UPDATE data_exports e SET attempts = e.attempts + 1, \
+             locked_until = now() + make_interval(secs => $4) \
+         FROM due WHERE e.id = due.id \
+         RETURNING e.id, e.user_id, e.attempts",
+    )
+    .bind(tenant_id)
+    .bind(max_attempts)
+    .bind(BATCH_SIZE)
+    .bind(LEASE.as_secs_f64())
+    .fetch_all(pool)
+    .await?;
+
+    let count = due.len();
+    for export in due {
+        run(pool, sessions, blobs, tenant_id, &export, max_attempts).await?;
+    }
+
+    Ok(count)
+}
+
+/// Genera el archivo de una exportación reservada y la marca como lista, o
+/// la libera para otro intento si falla.
+async fn run(
+    pool: &PgPool,
+    sessions: &dyn SessionStore,
+    blobs: &dyn BlobStore,
+    tenant_id: &str,
+    export: &DueExport,
+    max_attempts: i32,
+) -> Result<(), sqlx::Error> {
+    purge_expired(pool, blobs, export.user_id).await;
+
+    // Cada exportación en su directorio para poder borrarla con `delete_prefix`
+    let key = format!("exports/{}/{}/{}/export.zip", tenant_id, export.user_id, export.id);
+    let stored = match build_archive(pool, sessions, tenant_id, export.user_id).await {
+        Ok(archive) => {
+            let size = archive.len() as i64;
+            blobs.put(&key, archive).await.map(|_| size).map_err(ExportError::from)
+        }
+        Err(e) => Err(e),
+    };
+
+    match stored {
+        Ok(size) => {
+            sqlx::query(
+                "UPDATE data_exports SET status = 'ready', blob_key = $2, size_bytes = $3, \
+                 completed_at = now(), locked_until = NULL WHERE id = $1",
+            )
+            .bind(export.id)
+            .bind(&key)
+            .bind(size)
+            .execute(pool)
+            .await?;
+        }
+        Err(e) if export.attempts >= max_attempts => {
+            log::error!("Data export {} for user {} failed: {}", export.id, export.user_id, e);
+            sqlx::query(
+                "UPDATE data_exports SET status = 'failed', completed_at = now(), locked_until = NULL \
+                 WHERE id = $1",
+            )
+            .bind(export.id)
+            .execute(pool)
+            .await?;
+        }
+        Err(e) => {
+            log::warn!(
+                "Data export {} for user {} failed (attempt {}), retrying: {}",
+                export.id,
+                export.user_id,
+                export.attempts,
+                e
+            );
+            sqlx::query(
+                "UPDATE data_exports SET locked_until = now() + make_interval(secs => $2) WHERE id = $1",
+            )
+            .bind(export.id)
+            .bind(RETRY_AFTER.as_secs_f64())
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Borra las exportaciones caducadas del usuario y sus archivos.
+async fn purge_expired(pool: &PgPool, blobs: &dyn BlobStore, user_id: i64) {
+    let keys = match sqlx::query_scalar::<_, Option<String>>(
+        "DELETE FROM data_exports WHERE user_id = $1 AND expires_at <= now() RETURNING blob_key",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    {
+        Ok(keys) => keys,
+        Err(e) => {
+            log::warn!("Could not purge expired data exports of user {}: {}", user_id, e);
+            return;
+        }
+    };
+
+    for key in keys.into_iter().flatten() {
+        let prefix = key.rsplit_once('/').map_or(key.as_str(), |(dir, _)| dir);
+        if let Err(e) = blobs.delete_prefix(prefix).await {
+            log::warn!("Could not delete data export files {}: {}", prefix, e);
+        }
+    }
+}
+
+/// Filas de la consulta como un array JSON ordenado por `created_at`.
+async fn json_rows(pool: &PgPool, query: &str, user_id: i64) -> Result<Value, sqlx::Error> {
+    sqlx::query_scalar::<_, Value>(&format!(
+        "SELECT COALESCE(jsonb_agg(to_jsonb(r) ORDER BY r.created_at), '[]'::jsonb) FROM ({}) r",
+        query
+    ))
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Reúne todo lo que se guarda del usuario en un ZIP con un JSON por fuente.
+async fn build_archive(
+    pool: &PgPool,
+    sessions: &dyn SessionStore,
+    tenant_id: &str,
+    user_id: i64,
+) -> Result<Vec<u8>, ExportError> {
+    // La fila completa salvo el hash de la contraseña y la ruta interna del avatar
+    let user = sqlx::query_scalar::<_, Value>(
+        "SELECT to_jsonb(u) - 'password' - 'avatar_key' FROM users u WHERE u.id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    let sessions: Vec<SessionSummary> = sessions
+        .list_for_user(user_id)
+        .await?
+        .iter()
+        .map(|session| SessionSummary::new(session, ""))
+        .collect();
+
+    let auth_events = json_rows(
+        pool,
+        "SELECT id, event_type, outcome, ip, user_agent, details, created_at \
+         FROM auth_events WHERE user_id = $1",
+        user_id,
+    )
+    .await?;
+    let identities = json_rows(
+        pool,
+        "SELECT provider, subject, email, created_at FROM user_identities WHERE user_id = $1",
+        user_id,
+    )
+    .await?;
+    // Solo metadatos: el hash de la clave no sale nunca
+    let api_keys = json_rows(
+        pool,
+        "SELECT id, name, prefix, scopes, expires_at, last_used_at, created_at, revoked_at \
+         FROM api_keys WHERE user_id = $1",
+        user_id,
+    )
+    .await?;
+    let mfa = json_rows(
+        pool,
+        "SELECT channel, destination, created_at FROM otp_factors WHERE user_id = $1",
+        user_id,
+    )
+    .await?;
+    let organizations = organizations::list_for_user(pool, user_id).await?;
+
+    let files = vec![
+        ("user.json", user),
+        ("sessions.json", json!(sessions)),
+        ("auth_events.json", auth_events),
+        ("identities.json", identities),
+        ("api_keys.json", api_keys),
+        ("mfa.json", mfa),
+        ("organizations.json", json!(organizations)),
+    ];
+    let manifest = json!({
+        "user_id": user_id,
+        "tenant_id": tenant_id,
+        "generated_at": Utc::now(),
+        "files": files.iter().map(|(name, _)| *name).collect::<Vec<_>>()
+    });
+
+    web::block(move || zip_files(manifest, files))
+        .await
+        .map_err(|_| ExportError::Blocking)?
+}
+
+fn zip_files(manifest: Value, files: Vec<(&'static str, Value)>) -> Result<Vec<u8>, ExportError> {
+    let archive_error = |e: &dyn fmt::Display| ExportError::Archive(e.to_string());
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, value) in std::iter::once(("export.json", manifest)).chain(files) {
+        zip.start_file(name, options).map_err(|e| archive_error(&e))?;
+        let contents = serde_json::to_vec_pretty(&value).map_err(|e| archive_error(&e))?;
+        zip.write_all(&contents).map_err(|e| archive_error(&e))?;
+    }
+    let archive = zip.finish().map_err(|e| archive_error(&e))?;
+    Ok(archive.into_inner())
+}
@@ -0,0 +1,92 @@
+use chrono::{TimeZone, Utc};
+use sqlx::PgPool;
+use std::fmt;
+
+use crate::services::crypto::{random_token, sha256_hex, sign, verify_signature};
+
+const PURPOSE: &str = "magic-link";
+
+#[derive(Debug)]
+pub enum MagicLinkError {
+    /// Firma incorrecta, caducado, ya usado o pedido desde otro navegador.
+    Invalid,
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for MagicLinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MagicLinkError::Invalid => write!(f, "Invalid or expired magic link"),
+            MagicLinkError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for MagicLinkError {
+    fn from(e: sqlx::Error) -> Self {
+        MagicLinkError::Database(e)
+    }
+}
+
+/// Crea un enlace de un solo uso y devuelve su token `id.exp.firma`.
+///
+/// Solo se guardan los hashes del id y del nonce del navegador.
+pub async fn issue(
+    pool: &PgPool,
+    secret: &str,
+    user_id: i64,
+    nonce: &str,
+    ttl_seconds: i64,
+) -> Result<String, sqlx::Error> {
+    let id = random_token(32);
+    let expires_at = Utc::now().timestamp() + ttl_seconds;
+    let message = format!("{}.{}", id, expires_at);
+
+    sqlx::query(
+        "INSERT INTO magic_links (user_id, token_hash, nonce_hash, expires_at) \
+         VALUES ($1, $2, $3, $4)",
+    )
+    .bind(user_id)
+    .bind(sha256_hex(&id))
+    .bind(sha256_hex(nonce))
+    .bind(Utc.timestamp_opt(expires_at, 0).single())
+    .execute(pool)
+    .await?;
+
+    Ok(format!("{}.{}", message, sign(secret, PURPOSE, &message)))
+}
+
+/// Consume el enlace si la firma, la caducidad y el nonce del navegador cuadran.
+///
+/// Un enlace reenviado a otro navegador no se consume, así que sigue
+/// sirviendo a quien lo pidió.
+pub async fn consume(
+    pool: &PgPool,
+    secret: &str,
+    token: &str,
+    nonce: &str,
+) -> Result<i64, MagicLinkError> {
+    let (message, signature) = token.rsplit_once('.').ok_or(MagicLinkError::Invalid)?;
+    let (id, expires_at) = message.split_once('.').ok_or(MagicLinkError::Invalid)?;
+
+    if !verify_signature(secret, PURPOSE, message, signature) {
+        return Err(MagicLinkError::Invalid);
+    }
+    match expires_at.parse::<i64>() {
+        Ok(expires_at) if expires_at > Utc::now().timestamp() => {}
+        _ => return Err(MagicLinkError::Invalid),
+    }
+
+    let user_id = sqlx::query_scalar::<_, i64>(
+        "UPDATE magic_links SET consumed_at = now() \
+         WHERE token_hash = $1 AND nonce_hash = $2 \
+           AND consumed_at IS NULL AND expires_at > now() \
+         RETURNING user_id",
+    )
+    .bind(sha256_hex(id))
+    .bind(sha256_hex(nonce))
+    .fetch_optional(pool)
+    .await?;
+
+    user_id.ok_or(MagicLinkError::Invalid)
+}
@@ -0,0 +1,129 @@
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+
+/// Correo de texto plano.
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub enum MailerError {
+    InvalidAddress(String),
+    Send(String),
+}
+
+impl fmt::Display for MailerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailerError::InvalidAddress(e) => write!(f, "Invalid email address: {}", e),
+            MailerError::Send(e) => write!(f, "Error sending email: {}", e),
+        }
+    }
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: &EmailMessage) -> Result<(), MailerError>;
+}
+
+/// Envío por SMTP con `lettre`.
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<(), MailerError> {
+        let to: Mailbox = message
+            .to
+            .parse()
+            .map_err(|e: lettre::address::AddressError| MailerError::InvalidAddress(e.to_string()))?;
+
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&message.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.body.clone())
+            .map_err(|e| MailerError::Send(e.to_string()))?;
+
+        self.transport
+            .send(email)
+            .await
+            .map_err(|e| MailerError::Send(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Solo escribe el correo en el log; para desarrollo local.
+///
+/// El cuerpo incluye códigos y enlaces de login: nunca en producción.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<(), MailerError> {
+        log::info!(
+            "Email to {} (not sent, MAILER=log): {}\n{}",
+            message.to,
+            message.subject,
+            message.body
+        );
+        Ok(())
+    }
+}
+
+fn smtp_mailer() -> Result<SmtpMailer, String> {
+    let host = env::var("SMTP_HOST").map_err(|_| "MAILER=smtp requires SMTP_HOST".to_string())?;
+    let from = env::var("MAIL_FROM")
+        .map_err(|_| "MAILER=smtp requires MAIL_FROM".to_string())?
+        .parse::<Mailbox>()
+        .map_err(|e| format!("Invalid MAIL_FROM: {}", e))?;
+
+    // `tls` = TLS implícito (465), `starttls` (587) o `none` para servidores locales
+    let mut builder = match env::var("SMTP_TLS").unwrap_or_default().to_lowercase().as_str() {
+        "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host),
+        "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host)),
+        _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host),
+    }
+    .map_err(|e| format!("Invalid SMTP configuration: {}", e))?;
+
+    if let Some(port) = env::var("SMTP_PORT").ok().and_then(|v| v.parse().ok()) {
+        builder = builder.port(port);
+    }
+    if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+        builder = builder.credentials(Credentials::new(username, password));
+    }
+
+    Ok(SmtpMailer {
+        transport: builder.build(),
+        from,
+    })
+}
+
+/// Elige la implementación según `MAILER` (`smtp` o `log`).
+///
+/// No hay valor por defecto: un despliegue sin configurar no debe acabar
+/// escribiendo códigos y enlaces de login en el log.
+pub fn build_mailer() -> Result<Arc<dyn Mailer>, String> {
+    let mailer = env::var("MAILER")
+        .map_err(|_| "MAILER is not set (smtp, or log for local development)".to_string())?;
+    match mailer.to_lowercase().as_str() {
+        "smtp" => Ok(Arc::new(smtp_mailer()?)),
+        "log" => {
+            log::warn!("MAILER=log: emails, including one-time codes and login links, are written to the log");
+            Ok(Arc::new(LogMailer))
+        }
+        other => Err(format!("Unknown MAILER: {}", other)),
+    }
+}
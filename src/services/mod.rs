@@ -1 +1,21 @@
-pub mod auth; 
\ No newline at end of file
+pub mod api_key;
+pub mod audit;
+pub mod auth;
+pub mod avatar;
+pub mod blob_store;
+pub mod captcha;
+pub mod crypto;
+pub mod data_export;
+pub mod magic_link;
+pub mod mailer;
+pub mod oidc;
+pub mod organizations;
+pub mod otp;
+pub mod password;
+pub mod password_policy;
+pub mod profile;
+pub mod registration;
+pub mod session;
+pub mod user_invitations;
+pub mod users;
+pub mod webhooks;
\ No newline at end of file
@@ -0,0 +1,290 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+use crate::config::oidc::{OidcProvider, ProviderProtocol};
+use crate::models::session::SessionMode;
+use crate::services::crypto::{random_token, sign, verify_signature};
+use crate::services::users::normalize_email;
+
+const STATE_SIGNATURE_PURPOSE: &str = "oidc_state";
+
+/// Datos que viajan en una cookie firmada entre la redirección y el callback.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorizationState {
+    pub provider: String,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    /// Segundos Unix; la firma lo cubre, así que no depende del `Max-Age` de la cookie.
+    pub expires_at: i64,
+    #[serde(default)]
+    pub issued_at: i64,
+    /// Sesión que se reautentica con el proveedor; `None` en un login.
+    #[serde(default)]
+    pub reauth_session: Option<String>,
+    /// Cómo recibe el cliente la sesión al volver del proveedor.
+    #[serde(default)]
+    pub mode: SessionMode,
+}
+
+impl AuthorizationState {
+    pub fn new(provider: &str, ttl_seconds: i64) -> Self {
+        let now = chrono::Utc::now().timestamp();
+        Self {
+            provider: provider.to_string(),
+            state: random_token(32),
+            nonce: random_token(32),
+            code_verifier: random_token(64),
+            expires_at: now + ttl_seconds,
+            issued_at: now,
+            reauth_session: None,
+            mode: SessionMode::default(),
+        }
+    }
+
+    /// Valor de la cookie: el JSON en base64url y su HMAC con el secreto del tenant.
+    pub fn seal(&self, secret: &str) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default());
+        let signature = sign(secret, STATE_SIGNATURE_PURPOSE, &payload);
+        format!("{}.{}", payload, signature)
+    }
+
+    /// `None` si la firma no es válida o el state ya caducó.
+    pub fn open(value: &str, secret: &str) -> Option<Self> {
+        let (payload, signature) = value.split_once('.')?;
+        if !verify_signature(secret, STATE_SIGNATURE_PURPOSE, payload, signature) {
+            return None;
+        }
+        let state: Self = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+        (state.expires_at > chrono::Utc::now().timestamp()).then_some(state)
+    }
+}
+
+/// Identidad devuelta por el proveedor tras el intercambio del código.
+#[derive(Debug)]
+pub struct ExternalIdentity {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+    /// Momento del login en el proveedor (`auth_time` del id_token); los
+    /// proveedores OAuth2 sin id_token no lo dan.
+    pub auth_time: Option<i64>,
+}
+
+#[derive(Debug)]
+pub enum OidcError {
+    Http(reqwest::Error),
+    InvalidResponse(String),
+    NonceMismatch,
+}
+
+impl fmt::Display for OidcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OidcError::Http(e) => write!(f, "HTTP error: {}", e),
+            OidcError::InvalidResponse(msg) => write!(f, "Invalid provider response: {}", msg),
+            OidcError::NonceMismatch => write!(f, "ID token nonce mismatch"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for OidcError {
+    fn from(e: reqwest::Error) -> Self {
+        OidcError::Http(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    id_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    auth_time: Option<i64>,
+}
+
+/// Construye la URL de autorización con `state`, `nonce` y PKCE (S256).
+///
+/// En una reautenticación pide además al proveedor un login nuevo
+/// (`prompt=login`, `max_age=0`) aunque el usuario tenga sesión allí.
+pub fn authorization_url(
+    provider: &OidcProvider,
+    state: &AuthorizationState,
+) -> Result<String, OidcError> {
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(state.code_verifier.as_bytes()));
+
+    let mut params = vec![
+        ("response_type", "code"),
+        ("client_id", provider.client_id.as_str()),
+        ("redirect_uri", provider.redirect_uri.as_str()),
+        ("scope", provider.scopes.as_str()),
+        ("state", state.state.as_str()),
+        ("nonce", state.nonce.as_str()),
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+    if state.reauth_session.is_some() {
+        params.extend([("prompt", "login"), ("max_age", "0")]);
+    }
+
+    let url = reqwest::Url::parse_with_params(&provider.authorize_url, &params)
+    .map_err(|e| OidcError::InvalidResponse(format!("invalid authorize_url: {}", e)))?;
+
+    Ok(url.to_string())
+}
+
+/// Intercambia el código de autorización y obtiene la identidad del usuario.
+pub async fn fetch_identity(
+    http: &reqwest::Client,
+    provider: &OidcProvider,
+    code: &str,
+    state: &AuthorizationState,
+) -> Result<ExternalIdentity, OidcError> {
+    let tokens: TokenResponse = http
+        .post(&provider.token_url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", provider.redirect_uri.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("client_secret", provider.client_secret.as_str()),
+            ("code_verifier", state.code_verifier.as_str()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let id_claims = match &provider.protocol {
+        ProviderProtocol::Oidc { issuer, jwks_url } => {
+            let id_token = tokens
+                .id_token
+                .as_deref()
+                .ok_or_else(|| OidcError::InvalidResponse("missing id_token".to_string()))?;
+            Some(verify_id_token(http, provider, issuer, jwks_url, id_token, &state.nonce).await?)
+        }
+        ProviderProtocol::OAuth2 => None,
+    };
+
+    let userinfo: Value = http
+        .get(&provider.userinfo_url)
+        .bearer_auth(&tokens.access_token)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .header(reqwest::header::USER_AGENT, "rust-auth-api")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let mut identity = identity_from_userinfo(provider, &userinfo)?;
+
+    // OIDC Core 5.3.2: el userinfo debe ser del mismo usuario que el id_token
+    if let Some(claims) = id_claims {
+        if claims.sub != identity.subject {
+            return Err(OidcError::InvalidResponse(
+                "userinfo subject does not match id_token".to_string(),
+            ));
+        }
+        identity.auth_time = claims.auth_time;
+    }
+
+    Ok(identity)
+}
+
+/// Valida firma (con la clave de la JWKS indicada por `kid`), `iss`, `aud`,
+/// `exp` y `nonce` del id_token.
+async fn verify_id_token(
+    http: &reqwest::Client,
+    provider: &OidcProvider,
+    issuer: &str,
+    jwks_url: &str,
+    id_token: &str,
+    expected_nonce: &str,
+) -> Result<IdTokenClaims, OidcError> {
+    let invalid = |e: jsonwebtoken::errors::Error| {
+        OidcError::InvalidResponse(format!("invalid id_token: {}", e))
+    };
+
+    let header = decode_header(id_token).map_err(invalid)?;
+    let jwks: JwkSet = http
+        .get(jwks_url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or_else(|| OidcError::InvalidResponse("no JWKS key matches the id_token".to_string()))?;
+
+    // La familia de la clave (RSA, EC...) limita los algoritmos aceptados:
+    // un `alg` HS256 en la cabecera no puede usar la clave pública como secreto
+    let key = DecodingKey::from_jwk(jwk).map_err(invalid)?;
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[&provider.client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+        .map_err(invalid)?
+        .claims;
+
+    if claims.nonce.as_deref() != Some(expected_nonce) {
+        return Err(OidcError::NonceMismatch);
+    }
+    Ok(claims)
+}
+
+fn identity_from_userinfo(
+    provider: &OidcProvider,
+    userinfo: &Value,
+) -> Result<ExternalIdentity, OidcError> {
+    let subject = match &userinfo[&provider.subject_claim] {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => {
+            return Err(OidcError::InvalidResponse(format!(
+                "missing subject claim '{}'",
+                provider.subject_claim
+            )))
+        }
+    };
+
+    let email = userinfo[&provider.email_claim]
+        .as_str()
+        .map(normalize_email);
+    let email_verified = provider.trust_email
+        || match &userinfo["email_verified"] {
+            Value::Bool(b) => *b,
+            Value::String(s) => s == "true",
+            _ => false,
+        };
+    let name = userinfo[&provider.name_claim].as_str().map(str::to_string);
+
+    Ok(ExternalIdentity {
+        subject,
+        email,
+        email_verified,
+        name,
+        auth_time: None,
+    })
+}
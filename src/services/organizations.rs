@@ -0,0 +1,248 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::fmt;
+
+use crate::models::organization::{Invitation, Member, OrgRole, Organization};
+use crate::services::crypto::{random_token, sha256_hex};
+
+#[derive(Debug)]
+pub enum OrgError {
+    /// Token desconocido, caducado o ya usado.
+    InvalidInvitation,
+    /// La invitación es para otro correo.
+    EmailMismatch,
+    /// La organización se quedaría sin ningún owner.
+    LastOwner,
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for OrgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrgError::InvalidInvitation => write!(f, "Invalid or expired invitation"),
+            OrgError::EmailMismatch => write!(f, "Invitation was sent to a different email"),
+            OrgError::LastOwner => write!(f, "An organization must keep at least one owner"),
+            OrgError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for OrgError {
+    fn from(e: sqlx::Error) -> Self {
+        OrgError::Database(e)
+    }
+}
+
+/// Crea la organización en el tenant con el usuario como owner.
+pub async fn create(
+    pool: &PgPool,
+    tenant_id: &str,
+    owner_id: i64,
+    name: &str,
+) -> Result<Organization, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let (id, created_at) = sqlx::query_as::<_, (i64, chrono::DateTime<Utc>)>(
+        "INSERT INTO organizations (tenant_id, name) VALUES ($1, $2) RETURNING id, created_at",
+    )
+    .bind(tenant_id)
+    .bind(name)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("INSERT INTO memberships (org_id, user_id, role) VALUES ($1, $2, $3)")
+        .bind(id)
+        .bind(owner_id)
+        .bind(OrgRole::Owner.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(Organization {
+        id,
+        name: name.to_string(),
+        role: OrgRole::Owner,
+        created_at,
+    })
+}
+
+pub async fn list_for_user(pool: &PgPool, user_id: i64) -> Result<Vec<Organization>, sqlx::Error> {
+    sqlx::query_as::<_, Organization>(
+        "SELECT o.id, o.name, m.role, o.created_at FROM organizations o \
+         JOIN memberships m ON m.org_id = o.id \
+         WHERE m.user_id = $1 ORDER BY o.name",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Rol del usuario en la organización, o `None` si no es miembro.
+pub async fn role(pool: &PgPool, org_id: i64, user_id: i64) -> Result<Option<OrgRole>, sqlx::Error> {
+    let role = sqlx::query_scalar::<_, String>(
+        "SELECT role FROM memberships WHERE org_id = $1 AND user_id = $2",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(role.and_then(|role| role.parse().ok()))
+}
+
+pub async fn members(pool: &PgPool, org_id: i64) -> Result<Vec<Member>, sqlx::Error> {
+    sqlx::query_as::<_, Member>(
+        "SELECT u.id AS user_id, u.email, u.name, m.role, m.created_at FROM memberships m \
+         JOIN users u ON u.id = m.user_id \
+         WHERE m.org_id = $1 ORDER BY m.created_at",
+    )
+    .bind(org_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Crea una invitación y devuelve el token en claro; solo se guarda su hash.
+pub async fn invite(
+    pool: &PgPool,
+    org_id: i64,
+    invited_by: i64,
+    email: &str,
+    role: OrgRole,
+    ttl: Duration,
+) -> Result<(Invitation, String), sqlx::Error> {
+    let token = random_token(48);
+
+    let invitation = sqlx::query_as::<_, Invitation>(
+        "INSERT INTO organization_invitations (org_id, email, role, token_hash, invited_by, expires_at) \
+         VALUES ($1, $2, $3, $4, $5, $6) \
+         RETURNING id, email, role, invited_by, expires_at, accepted_at, created_at",
+    )
+    .bind(org_id)
+    .bind(email)
+    .bind(role.as_str())
+    .bind(sha256_hex(&token))
+    .bind(invited_by)
+    .bind(Utc::now() + ttl)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((invitation, token))
+}
+
+pub async fn invitations(pool: &PgPool, org_id: i64) -> Result<Vec<Invitation>, sqlx::Error> {
+    sqlx::query_as::<_, Invitation>(
+        "SELECT id, email, role, invited_by, expires_at, accepted_at, created_at \
+         FROM organization_invitations WHERE org_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(org_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Acepta la invitación para el usuario si va dirigida a su correo y la
+/// organización es de su tenant.
+///
+/// Si ya era miembro conserva su rol actual. Devuelve la organización y el rol.
+pub async fn accept(
+    pool: &PgPool,
+    tenant_id: &str,
+    token: &str,
+    user_id: i64,
+    email: &str,
+) -> Result<(i64, OrgRole), OrgError> {
+    let mut tx = pool.begin().await?;
+
+    let invitation = sqlx::query_as::<_, (i64, i64, String, String)>(
+        "SELECT i.id, i.org_id, i.email, i.role FROM organization_invitations i \
+         JOIN organizations o ON o.id = i.org_id \
+         WHERE i.token_hash = $1 AND i.accepted_at IS NULL AND i.expires_at > now() \
+           AND o.tenant_id = $2 \
+         FOR UPDATE OF i",
+    )
+    .bind(sha256_hex(token))
+    .bind(tenant_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let (id, org_id, invited_email, role) = match invitation {
+        Some(invitation) => invitation,
+        None => return Err(OrgError::InvalidInvitation),
+    };
+    if !invited_email.eq_ignore_ascii_case(email) {
+        return Err(OrgError::EmailMismatch);
+    }
+
+    sqlx::query("UPDATE organization_invitations SET accepted_at = now() WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    let role = sqlx::query_scalar::<_, String>(
+        "INSERT INTO memberships (org_id, user_id, role) VALUES ($1, $2, $3) \
+         ON CONFLICT (org_id, user_id) DO UPDATE SET role = memberships.role \
+         RETURNING role",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .bind(&role)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok((org_id, role.parse().unwrap_or(OrgRole::Member)))
+}
+
+/// Bloquea las membresías de la organización y comprueba que, tras quitarle
+/// el rol de owner a `user_id`, quede al menos otro.
+async fn ensure_other_owner(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    org_id: i64,
+    user_id: i64,
+) -> Result<(), OrgError> {
+    let owners = sqlx::query_scalar::<_, i64>(
+        "SELECT user_id FROM memberships WHERE org_id = $1 AND role = 'owner' FOR UPDATE",
+    )
+    .bind(org_id)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    if owners.iter().all(|owner| *owner == user_id) {
+        return Err(OrgError::LastOwner);
+    }
+    Ok(())
+}
+
+pub async fn update_role(
+    pool: &PgPool,
+    org_id: i64,
+    user_id: i64,
+    role: OrgRole,
+) -> Result<bool, OrgError> {
+    let mut tx = pool.begin().await?;
+    if role != OrgRole::Owner {
+        ensure_other_owner(&mut tx, org_id, user_id).await?;
+    }
+
+    let result = sqlx::query("UPDATE memberships SET role = $3 WHERE org_id = $1 AND user_id = $2")
+        .bind(org_id)
+        .bind(user_id)
+        .bind(role.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn remove_member(pool: &PgPool, org_id: i64, user_id: i64) -> Result<bool, OrgError> {
+    let mut tx = pool.begin().await?;
+    ensure_other_owner(&mut tx, org_id, user_id).await?;
+
+    let result = sqlx::query("DELETE FROM memberships WHERE org_id = $1 AND user_id = $2")
+        .bind(org_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(result.rows_affected() > 0)
+}
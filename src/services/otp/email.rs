@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use super::{OtpError, OtpMessage, OtpSender};
+use crate::services::mailer::{EmailMessage, Mailer};
+
+/// Envía el código por correo con el `Mailer` de la aplicación.
+pub struct EmailOtpSender {
+    mailer: Arc<dyn Mailer>,
+}
+
+impl EmailOtpSender {
+    pub fn new(mailer: Arc<dyn Mailer>) -> Self {
+        Self { mailer }
+    }
+}
+
+#[async_trait]
+impl OtpSender for EmailOtpSender {
+    async fn send(&self, message: &OtpMessage) -> Result<(), OtpError> {
+        self.mailer
+            .send(&EmailMessage {
+                to: message.destination.clone(),
+                subject: "Your verification code".to_string(),
+                body: format!(
+                    "Your verification code is {}.\n\nIt expires in {} minutes. \
+                     If you did not try to sign in, change your password.\n",
+                    message.code,
+                    message.ttl_seconds.div_ceil(60)
+                ),
+            })
+            .await
+            .map_err(|e| OtpError::Send(e.to_string()))
+    }
+}
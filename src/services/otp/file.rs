@@ -0,0 +1,49 @@
+use actix_web::web;
+use async_trait::async_trait;
+use serde_json::json;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::{OtpError, OtpMessage, OtpSender};
+use crate::models::otp::OtpChannel;
+
+/// Escribe cada código como una línea JSON en un fichero en lugar de enviarlo.
+///
+/// Pensado para pruebas de extremo a extremo: el test lee el código del fichero.
+pub struct FileOtpSender {
+    path: PathBuf,
+    channel: OtpChannel,
+}
+
+impl FileOtpSender {
+    pub fn new(path: PathBuf, channel: OtpChannel) -> Self {
+        Self { path, channel }
+    }
+}
+
+#[async_trait]
+impl OtpSender for FileOtpSender {
+    async fn send(&self, message: &OtpMessage) -> Result<(), OtpError> {
+        let path = self.path.clone();
+        let line = format!(
+            "{}\n",
+            json!({
+                "channel": self.channel,
+                "destination": message.destination,
+                "code": message.code
+            })
+        );
+
+        web::block(move || {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(line.as_bytes()))
+        })
+        .await
+        .map_err(|e| OtpError::Send(e.to_string()))?
+        .map_err(|e| OtpError::Send(e.to_string()))
+    }
+}
@@ -0,0 +1,91 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::{OtpChallenge, OtpError, OtpStore, VerifyOutcome};
+
+#[derive(Default)]
+struct State {
+    /// Reto, intentos fallidos y caducidad.
+    challenges: HashMap<String, (OtpChallenge, u32, Instant)>,
+    /// Envíos de la ventana actual y su fin.
+    sends: HashMap<String, (u32, Instant)>,
+}
+
+/// Doble de pruebas de `OtpStore`: retos en memoria del proceso.
+#[derive(Default)]
+pub struct InMemoryOtpStore {
+    state: Mutex<State>,
+}
+
+impl InMemoryOtpStore {
+    fn state(&self) -> MutexGuard<'_, State> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        state.challenges.retain(|_, (_, _, expires)| *expires > now);
+        state.sends.retain(|_, (_, resets)| *resets > now);
+        state
+    }
+}
+
+#[async_trait]
+impl OtpStore for InMemoryOtpStore {
+    async fn put(&self, key: &str, challenge: &OtpChallenge, ttl_seconds: u64) -> Result<(), OtpError> {
+        let expires = Instant::now() + Duration::from_secs(ttl_seconds);
+        self.state()
+            .challenges
+            .insert(key.to_string(), (challenge.clone(), 0, expires));
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<OtpChallenge>, OtpError> {
+        Ok(self.state().challenges.get(key).map(|(challenge, _, _)| challenge.clone()))
+    }
+
+    async fn replace_code(&self, key: &str, code_hash: &str, ttl_seconds: u64) -> Result<bool, OtpError> {
+        Ok(match self.state().challenges.get_mut(key) {
+            Some((challenge, _, expires)) => {
+                challenge.code_hash = code_hash.to_string();
+                *expires = Instant::now() + Duration::from_secs(ttl_seconds);
+                true
+            }
+            None => false,
+        })
+    }
+
+    async fn verify(&self, key: &str, code_hash: &str, max_attempts: u32) -> Result<VerifyOutcome, OtpError> {
+        let mut state = self.state();
+        let (challenge, attempts, _) = match state.challenges.get_mut(key) {
+            Some(entry) => entry,
+            None => return Ok(VerifyOutcome::Expired),
+        };
+
+        if challenge.code_hash == code_hash {
+            let challenge = challenge.clone();
+            state.challenges.remove(key);
+            return Ok(VerifyOutcome::Valid(challenge));
+        }
+
+        *attempts += 1;
+        let remaining = max_attempts.saturating_sub(*attempts);
+        if remaining == 0 {
+            state.challenges.remove(key);
+            return Ok(VerifyOutcome::Expired);
+        }
+        Ok(VerifyOutcome::InvalidCode {
+            remaining_attempts: remaining,
+        })
+    }
+
+    async fn count_send(&self, key: &str, window_seconds: u64) -> Result<(u32, u64), OtpError> {
+        let now = Instant::now();
+        let mut state = self.state();
+        let (sends, resets) = state
+            .sends
+            .entry(key.to_string())
+            .or_insert((0, now + Duration::from_secs(window_seconds)));
+        *sends += 1;
+        Ok((*sends, resets.saturating_duration_since(now).as_secs().max(1)))
+    }
+}
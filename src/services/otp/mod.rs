@@ -0,0 +1,371 @@
+use async_trait::async_trait;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::otp::OtpSettings;
+use crate::models::otp::{OtpChannel, OtpFactor};
+use crate::services::crypto::{random_token, sign};
+use crate::services::mailer::Mailer;
+
+pub mod email;
+pub mod file;
+#[cfg(test)]
+pub mod memory;
+pub mod redis;
+pub mod sms;
+
+pub use self::email::EmailOtpSender;
+pub use self::file::FileOtpSender;
+#[cfg(test)]
+pub use self::memory::InMemoryOtpStore;
+pub use self::redis::RedisOtpStore;
+pub use self::sms::SmsOtpSender;
+
+#[derive(Debug)]
+pub enum OtpError {
+    /// Sin store (Redis) no se pueden guardar los códigos.
+    Unavailable,
+    /// No hay implementación configurada para el canal.
+    ChannelUnavailable(OtpChannel),
+    RateLimited { retry_after: u64 },
+    InvalidCode { remaining_attempts: u32 },
+    /// Caducado, ya usado o agotados los intentos.
+    Expired,
+    Send(String),
+    Redis(::redis::RedisError),
+}
+
+impl fmt::Display for OtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtpError::Unavailable => write!(f, "One-time passcodes require Redis"),
+            OtpError::ChannelUnavailable(channel) => {
+                write!(f, "No OTP sender configured for {}", channel)
+            }
+            OtpError::RateLimited { retry_after } => {
+                write!(f, "Too many codes sent; retry in {} seconds", retry_after)
+            }
+            OtpError::InvalidCode { remaining_attempts } => {
+                write!(f, "Invalid code ({} attempts left)", remaining_attempts)
+            }
+            OtpError::Expired => write!(f, "Code expired or too many attempts"),
+            OtpError::Send(e) => write!(f, "Error sending code: {}", e),
+            OtpError::Redis(e) => write!(f, "Redis error: {}", e),
+        }
+    }
+}
+
+impl From<::redis::RedisError> for OtpError {
+    fn from(e: ::redis::RedisError) -> Self {
+        OtpError::Redis(e)
+    }
+}
+
+/// Código a entregar al usuario.
+#[derive(Debug, Clone)]
+pub struct OtpMessage {
+    pub destination: String,
+    pub code: String,
+    pub ttl_seconds: u64,
+}
+
+/// Entrega de códigos por un canal concreto.
+#[async_trait]
+pub trait OtpSender: Send + Sync {
+    async fn send(&self, message: &OtpMessage) -> Result<(), OtpError>;
+}
+
+/// Reto pendiente: a quién va el código y con qué métodos ya superados.
+///
+/// Solo se guarda un HMAC del código, atado a la clave del reto.
+#[derive(Debug, Clone)]
+pub struct OtpChallenge {
+    pub code_hash: String,
+    pub user_id: i64,
+    pub factor: OtpFactor,
+    pub amr: Vec<String>,
+}
+
+/// Resultado de comprobar un código contra el reto guardado.
+#[derive(Debug)]
+pub enum VerifyOutcome {
+    Valid(OtpChallenge),
+    InvalidCode { remaining_attempts: u32 },
+    /// Caducado, ya usado o agotados los intentos.
+    Expired,
+}
+
+/// Almacenamiento de los retos y del contador de envíos.
+///
+/// Las claves llegan sin prefijo de tenant; el store añade el suyo.
+#[async_trait]
+pub trait OtpStore: Send + Sync {
+    /// Guarda el reto con los intentos a cero, sustituyendo al anterior con la misma clave.
+    async fn put(&self, key: &str, challenge: &OtpChallenge, ttl_seconds: u64) -> Result<(), OtpError>;
+
+    async fn get(&self, key: &str) -> Result<Option<OtpChallenge>, OtpError>;
+
+    /// Cambia el código de un reto vigente y renueva su caducidad; los
+    /// intentos fallidos se conservan. `false` si el reto ya no existe.
+    async fn replace_code(&self, key: &str, code_hash: &str, ttl_seconds: u64) -> Result<bool, OtpError>;
+
+    /// Compara el código; cada fallo consume un intento y al agotarlos el
+    /// reto desaparece. Si acierta, el reto se borra para que no se reutilice.
+    async fn verify(&self, key: &str, code_hash: &str, max_attempts: u32) -> Result<VerifyOutcome, OtpError>;
+
+    /// Cuenta un envío y devuelve `(envíos, segundos hasta reiniciar la ventana)`.
+    async fn count_send(&self, key: &str, window_seconds: u64) -> Result<(u32, u64), OtpError>;
+}
+
+// Claves de OTP:
+//   otp:login:{mfa_token}  -> reto pendiente del login
+//   otp:enroll:{user_id}   -> código para confirmar el alta del factor
+//   otp:phone:{user_id}    -> código para verificar el teléfono del perfil
+//   otp:reauth:{session_id} -> código para reautenticar una sesión abierta
+//   otp_sends:{user_id}    -> contador de envíos de la ventana actual
+const LOGIN_PREFIX: &str = "otp:login:";
+const ENROLL_PREFIX: &str = "otp:enroll:";
+const PHONE_PREFIX: &str = "otp:phone:";
+const REAUTH_PREFIX: &str = "otp:reauth:";
+const SENDS_PREFIX: &str = "otp_sends:";
+
+/// Códigos de un solo uso: generación, envío y verificación.
+pub struct OtpService {
+    store: Option<Arc<dyn OtpStore>>,
+    senders: HashMap<OtpChannel, Arc<dyn OtpSender>>,
+    settings: OtpSettings,
+    secret: String,
+}
+
+impl OtpService {
+    pub fn new(
+        store: Option<Arc<dyn OtpStore>>,
+        senders: HashMap<OtpChannel, Arc<dyn OtpSender>>,
+        settings: OtpSettings,
+        secret: String,
+    ) -> Self {
+        Self {
+            store,
+            senders,
+            settings,
+            secret,
+        }
+    }
+
+    pub fn ttl_seconds(&self) -> u64 {
+        self.settings.ttl_seconds
+    }
+
+    pub fn supports(&self, channel: OtpChannel) -> bool {
+        self.senders.contains_key(&channel)
+    }
+
+    /// Abre el paso pendiente de MFA del login y envía el código.
+    ///
+    /// `amr` son los métodos ya superados; se devuelven al verificar. El
+    /// resultado es el `mfa_token` con el que el cliente completa el login.
+    pub async fn start_login(
+        &self,
+        user_id: i64,
+        factor: &OtpFactor,
+        amr: &[String],
+    ) -> Result<String, OtpError> {
+        let mfa_token = random_token(32);
+        self.issue(&format!("{}{}", LOGIN_PREFIX, mfa_token), user_id, factor, amr)
+            .await?;
+        Ok(mfa_token)
+    }
+
+    /// Envía un código nuevo para el mismo reto; los intentos fallidos se conservan.
+    pub async fn resend_login(&self, mfa_token: &str) -> Result<(), OtpError> {
+        let key = format!("{}{}", LOGIN_PREFIX, mfa_token);
+        let store = self.store()?;
+        let challenge = match store.get(&key).await? {
+            Some(challenge) => challenge,
+            None => return Err(OtpError::Expired),
+        };
+
+        self.check_rate_limit(challenge.user_id).await?;
+        let code = self.generate_code();
+        if !store
+            .replace_code(&key, &self.code_hash(&key, &code), self.settings.ttl_seconds)
+            .await?
+        {
+            return Err(OtpError::Expired);
+        }
+        self.deliver(&challenge.factor, code).await
+    }
+
+    /// Completa el login: devuelve el usuario del reto y su `amr` si el código es correcto.
+    pub async fn verify_login(&self, mfa_token: &str, code: &str) -> Result<(i64, Vec<String>), OtpError> {
+        let challenge = self
+            .verify(&format!("{}{}", LOGIN_PREFIX, mfa_token), code)
+            .await?;
+        Ok((challenge.user_id, challenge.amr))
+    }
+
+    /// Envía un código al destino que el usuario quiere dar de alta.
+    pub async fn start_enrollment(&self, user_id: i64, factor: &OtpFactor) -> Result<(), OtpError> {
+        self.issue(&format!("{}{}", ENROLL_PREFIX, user_id), user_id, factor, &[])
+            .await
+    }
+
+    /// Devuelve el factor a guardar si el código enviado en el alta es correcto.
+    pub async fn confirm_enrollment(&self, user_id: i64, code: &str) -> Result<OtpFactor, OtpError> {
+        let challenge = self
+            .verify(&format!("{}{}", ENROLL_PREFIX, user_id), code)
+            .await?;
+        Ok(challenge.factor)
+    }
+
+    /// Envía por SMS el código que verifica el teléfono del perfil.
+    pub async fn start_phone_verification(&self, user_id: i64, phone: &str) -> Result<(), OtpError> {
+        let factor = OtpFactor {
+            channel: OtpChannel::Sms,
+            destination: phone.to_string(),
+        };
+        self.issue(&format!("{}{}", PHONE_PREFIX, user_id), user_id, &factor, &[])
+            .await
+    }
+
+    /// Devuelve el teléfono al que se envió el código si es correcto.
+    pub async fn confirm_phone_verification(&self, user_id: i64, code: &str) -> Result<String, OtpError> {
+        let challenge = self
+            .verify(&format!("{}{}", PHONE_PREFIX, user_id), code)
+            .await?;
+        Ok(challenge.factor.destination)
+    }
+
+    /// Envía un código para reautenticar la sesión `session_id`; sirve a las
+    /// cuentas sin contraseña.
+    pub async fn start_reauth(
+        &self,
+        session_id: &str,
+        user_id: i64,
+        factor: &OtpFactor,
+    ) -> Result<(), OtpError> {
+        self.issue(&format!("{}{}", REAUTH_PREFIX, session_id), user_id, factor, &[])
+            .await
+    }
+
+    /// Comprueba el código enviado a la sesión con `start_reauth`.
+    pub async fn verify_reauth(&self, session_id: &str, code: &str) -> Result<(), OtpError> {
+        self.verify(&format!("{}{}", REAUTH_PREFIX, session_id), code)
+            .await
+            .map(|_| ())
+    }
+
+    fn store(&self) -> Result<&dyn OtpStore, OtpError> {
+        match &self.store {
+            Some(store) => Ok(store.as_ref()),
+            None => Err(OtpError::Unavailable),
+        }
+    }
+
+    fn generate_code(&self) -> String {
+        let mut rng = rand::thread_rng();
+        (0..self.settings.code_length)
+            .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+            .collect()
+    }
+
+    fn code_hash(&self, key: &str, code: &str) -> String {
+        sign(&self.secret, "otp", &format!("{}:{}", key, code))
+    }
+
+    async fn check_rate_limit(&self, user_id: i64) -> Result<(), OtpError> {
+        let (sends, retry_after) = self
+            .store()?
+            .count_send(&format!("{}{}", SENDS_PREFIX, user_id), self.settings.send_window_seconds)
+            .await?;
+
+        if sends > self.settings.max_sends {
+            return Err(OtpError::RateLimited { retry_after });
+        }
+        Ok(())
+    }
+
+    async fn issue(
+        &self,
+        key: &str,
+        user_id: i64,
+        factor: &OtpFactor,
+        amr: &[String],
+    ) -> Result<(), OtpError> {
+        if !self.supports(factor.channel) {
+            return Err(OtpError::ChannelUnavailable(factor.channel));
+        }
+        self.check_rate_limit(user_id).await?;
+
+        let code = self.generate_code();
+        let challenge = OtpChallenge {
+            code_hash: self.code_hash(key, &code),
+            user_id,
+            factor: factor.clone(),
+            amr: amr.to_vec(),
+        };
+        self.store()?
+            .put(key, &challenge, self.settings.ttl_seconds)
+            .await?;
+
+        self.deliver(factor, code).await
+    }
+
+    async fn deliver(&self, factor: &OtpFactor, code: String) -> Result<(), OtpError> {
+        let sender = match self.senders.get(&factor.channel) {
+            Some(sender) => sender,
+            None => return Err(OtpError::ChannelUnavailable(factor.channel)),
+        };
+        sender
+            .send(&OtpMessage {
+                destination: factor.destination.clone(),
+                code,
+                ttl_seconds: self.settings.ttl_seconds,
+            })
+            .await
+    }
+
+    async fn verify(&self, key: &str, code: &str) -> Result<OtpChallenge, OtpError> {
+        let outcome = self
+            .store()?
+            .verify(key, &self.code_hash(key, code.trim()), self.settings.max_attempts)
+            .await?;
+
+        match outcome {
+            VerifyOutcome::Valid(challenge) => Ok(challenge),
+            VerifyOutcome::InvalidCode { remaining_attempts } => {
+                Err(OtpError::InvalidCode { remaining_attempts })
+            }
+            VerifyOutcome::Expired => Err(OtpError::Expired),
+        }
+    }
+}
+
+/// Un sender por canal. Con `OTP_CAPTURE_FILE` todos los códigos van al
+/// fichero; SMS solo está disponible con `SMS_GATEWAY_URL`.
+pub fn build_senders(
+    settings: &OtpSettings,
+    mailer: Arc<dyn Mailer>,
+    http: reqwest::Client,
+) -> HashMap<OtpChannel, Arc<dyn OtpSender>> {
+    let mut senders: HashMap<OtpChannel, Arc<dyn OtpSender>> = HashMap::new();
+
+    if let Some(path) = &settings.capture_file {
+        log::warn!("OTP_CAPTURE_FILE set; codes are written to {} instead of being sent", path.display());
+        for channel in [OtpChannel::Email, OtpChannel::Sms] {
+            senders.insert(channel, Arc::new(FileOtpSender::new(path.clone(), channel)));
+        }
+        return senders;
+    }
+
+    senders.insert(OtpChannel::Email, Arc::new(EmailOtpSender::new(mailer)));
+    if let Some(url) = &settings.sms_gateway_url {
+        senders.insert(
+            OtpChannel::Sms,
+            Arc::new(SmsOtpSender::new(http, url.clone(), settings.sms_gateway_token.clone())),
+        );
+    }
+    senders
+}
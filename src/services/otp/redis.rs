@@ -0,0 +1,172 @@
+use async_trait::async_trait;
+use redis::{AsyncCommands, Script};
+use std::sync::LazyLock;
+
+use super::{OtpChallenge, OtpError, OtpStore, VerifyOutcome};
+use crate::config::redis::RedisPool;
+use crate::models::otp::OtpFactor;
+
+/// Cuenta un envío y devuelve `{envíos, segundos hasta reiniciar la ventana}`.
+static RATE_LIMIT_SCRIPT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local sends = redis.call('INCR', KEYS[1])
+        if sends == 1 then
+            redis.call('EXPIRE', KEYS[1], ARGV[1])
+        end
+        return {sends, redis.call('TTL', KEYS[1])}
+        ",
+    )
+});
+
+/// Comprueba el código; cada fallo consume un intento y al agotarlos el
+/// reto desaparece. Si acierta, el reto se borra para que no se reutilice.
+static VERIFY_SCRIPT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local data = redis.call('HMGET', KEYS[1], 'code_hash', 'user_id', 'channel', 'destination', 'amr')
+        if not data[1] then
+            return {'expired'}
+        end
+        if data[1] == ARGV[1] then
+            redis.call('DEL', KEYS[1])
+            return {'ok', data[1], data[2], data[3], data[4], data[5] or ''}
+        end
+        local attempts = redis.call('HINCRBY', KEYS[1], 'attempts', 1)
+        local remaining = tonumber(ARGV[2]) - attempts
+        if remaining <= 0 then
+            redis.call('DEL', KEYS[1])
+            return {'expired'}
+        end
+        return {'invalid', tostring(remaining)}
+        ",
+    )
+});
+
+/// Retos en hashes de Redis, bajo el prefijo del tenant del pool.
+pub struct RedisOtpStore {
+    redis: RedisPool,
+}
+
+impl RedisOtpStore {
+    pub fn new(redis: RedisPool) -> Self {
+        Self { redis }
+    }
+}
+
+fn challenge(
+    code_hash: String,
+    user_id: &str,
+    channel: &str,
+    destination: String,
+    amr: &str,
+) -> Option<OtpChallenge> {
+    Some(OtpChallenge {
+        code_hash,
+        user_id: user_id.parse().ok()?,
+        factor: OtpFactor {
+            channel: channel.parse().ok()?,
+            destination,
+        },
+        amr: amr
+            .split(',')
+            .filter(|method| !method.is_empty())
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+#[async_trait]
+impl OtpStore for RedisOtpStore {
+    async fn put(&self, key: &str, challenge: &OtpChallenge, ttl_seconds: u64) -> Result<(), OtpError> {
+        let key = self.redis.key(key);
+        let mut conn = self.redis.get().await?;
+        ::redis::pipe()
+            .atomic()
+            .del(&key)
+            .hset_multiple(
+                &key,
+                &[
+                    ("code_hash", challenge.code_hash.clone()),
+                    ("user_id", challenge.user_id.to_string()),
+                    ("channel", challenge.factor.channel.to_string()),
+                    ("destination", challenge.factor.destination.clone()),
+                    ("amr", challenge.amr.join(",")),
+                    ("attempts", "0".to_string()),
+                ],
+            )
+            .expire(&key, ttl_seconds as usize)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<OtpChallenge>, OtpError> {
+        let mut conn = self.redis.get().await?;
+        let fields: Vec<Option<String>> = ::redis::cmd("HMGET")
+            .arg(self.redis.key(key))
+            .arg("code_hash")
+            .arg("user_id")
+            .arg("channel")
+            .arg("destination")
+            .arg("amr")
+            .query_async(&mut conn)
+            .await?;
+
+        match fields.as_slice() {
+            [Some(code_hash), Some(user_id), Some(channel), Some(destination), amr] => Ok(challenge(
+                code_hash.clone(),
+                user_id,
+                channel,
+                destination.clone(),
+                amr.as_deref().unwrap_or_default(),
+            )),
+            _ => Ok(None),
+        }
+    }
+
+    async fn replace_code(&self, key: &str, code_hash: &str, ttl_seconds: u64) -> Result<bool, OtpError> {
+        let key = self.redis.key(key);
+        let mut conn = self.redis.get().await?;
+        // HSET XX no existe: se comprueba primero para no crear un reto a medias
+        if !conn.exists::<_, bool>(&key).await? {
+            return Ok(false);
+        }
+        conn.hset::<_, _, _, ()>(&key, "code_hash", code_hash).await?;
+        conn.expire::<_, ()>(&key, ttl_seconds as usize).await?;
+        Ok(true)
+    }
+
+    async fn verify(&self, key: &str, code_hash: &str, max_attempts: u32) -> Result<VerifyOutcome, OtpError> {
+        let mut conn = self.redis.get().await?;
+        let result: Vec<String> = VERIFY_SCRIPT
+            .key(self.redis.key(key))
+            .arg(code_hash)
+            .arg(max_attempts)
+            .invoke_async(&mut conn)
+            .await?;
+
+        Ok(match result.as_slice() {
+            [status, code_hash, user_id, channel, destination, amr] if status == "ok" => {
+                match challenge(code_hash.clone(), user_id, channel, destination.clone(), amr) {
+                    Some(challenge) => VerifyOutcome::Valid(challenge),
+                    None => VerifyOutcome::Expired,
+                }
+            }
+            [status, remaining] if status == "invalid" => VerifyOutcome::InvalidCode {
+                remaining_attempts: remaining.parse().unwrap_or(0),
+            },
+            _ => VerifyOutcome::Expired,
+        })
+    }
+
+    async fn count_send(&self, key: &str, window_seconds: u64) -> Result<(u32, u64), OtpError> {
+        let mut conn = self.redis.get().await?;
+        let (sends, ttl): (u32, i64) = RATE_LIMIT_SCRIPT
+            .key(self.redis.key(key))
+            .arg(window_seconds)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok((sends, ttl.max(1) as u64))
+    }
+}
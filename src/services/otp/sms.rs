@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{OtpError, OtpMessage, OtpSender};
+
+/// Envía el código por SMS a través de una pasarela HTTP genérica.
+///
+/// La pasarela recibe `POST {"to": "+34...", "message": "..."}`, con
+/// `Authorization: Bearer` si se configura `SMS_GATEWAY_TOKEN`.
+pub struct SmsOtpSender {
+    http: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl SmsOtpSender {
+    pub fn new(http: reqwest::Client, url: String, token: Option<String>) -> Self {
+        Self { http, url, token }
+    }
+}
+
+#[async_trait]
+impl OtpSender for SmsOtpSender {
+    async fn send(&self, message: &OtpMessage) -> Result<(), OtpError> {
+        let mut request = self.http.post(&self.url).json(&json!({
+            "to": message.destination,
+            "message": format!("Your verification code is {}", message.code)
+        }));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| OtpError::Send(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(OtpError::Send(format!(
+                "SMS gateway responded with {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
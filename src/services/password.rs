@@ -0,0 +1,126 @@
+use actix_web::web;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use std::fmt;
+
+use crate::config::password::{PasswordAlgorithm, PasswordSettings};
+
+#[derive(Debug)]
+pub enum PasswordError {
+    Argon2(String),
+    Bcrypt(bcrypt::BcryptError),
+    Blocking,
+}
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordError::Argon2(e) => write!(f, "Argon2 error: {}", e),
+            PasswordError::Bcrypt(e) => write!(f, "Bcrypt error: {}", e),
+            PasswordError::Blocking => write!(f, "Password hashing task was canceled"),
+        }
+    }
+}
+
+impl From<argon2::password_hash::Error> for PasswordError {
+    fn from(e: argon2::password_hash::Error) -> Self {
+        PasswordError::Argon2(e.to_string())
+    }
+}
+
+impl From<argon2::Error> for PasswordError {
+    fn from(e: argon2::Error) -> Self {
+        PasswordError::Argon2(e.to_string())
+    }
+}
+
+impl From<bcrypt::BcryptError> for PasswordError {
+    fn from(e: bcrypt::BcryptError) -> Self {
+        PasswordError::Bcrypt(e)
+    }
+}
+
+fn is_bcrypt(stored: &str) -> bool {
+    stored.starts_with("$2")
+}
+
+fn argon2(settings: &PasswordSettings) -> Result<Argon2<'static>, PasswordError> {
+    let params = Params::new(
+        settings.argon2_memory_kib,
+        settings.argon2_iterations,
+        settings.argon2_parallelism,
+        None,
+    )?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+/// Hashea con el algoritmo configurado. Es costoso: usar `hash_blocking` desde handlers.
+pub fn hash(settings: &PasswordSettings, password: &str) -> Result<String, PasswordError> {
+    match settings.algorithm {
+        PasswordAlgorithm::Argon2id => {
+            let salt = SaltString::generate(&mut OsRng);
+            Ok(argon2(settings)?
+                .hash_password(password.as_bytes(), &salt)?
+                .to_string())
+        }
+        PasswordAlgorithm::Bcrypt => Ok(bcrypt::hash(password, settings.bcrypt_cost)?),
+    }
+}
+
+/// Verifica contra un hash bcrypt o Argon2 (los parámetros van en el propio hash).
+pub fn verify(password: &str, stored: &str) -> Result<bool, PasswordError> {
+    if is_bcrypt(stored) {
+        return Ok(bcrypt::verify(password, stored)?);
+    }
+
+    let parsed = PasswordHash::new(stored)?;
+    Ok(Argon2::default()
+        .verify_password(password.as_bytes(), &parsed)
+        .is_ok())
+}
+
+/// Indica si el hash guardado usa otro algoritmo o parámetros que los configurados.
+pub fn needs_rehash(settings: &PasswordSettings, stored: &str) -> bool {
+    match settings.algorithm {
+        PasswordAlgorithm::Bcrypt => {
+            // Formato: $2b$<coste>$...
+            !is_bcrypt(stored)
+                || stored.get(4..6).and_then(|cost| cost.parse::<u32>().ok())
+                    != Some(settings.bcrypt_cost)
+        }
+        PasswordAlgorithm::Argon2id => {
+            let parsed = match PasswordHash::new(stored) {
+                Ok(parsed) if !is_bcrypt(stored) => parsed,
+                _ => return true,
+            };
+            let params = match Params::try_from(&parsed) {
+                Ok(params) => params,
+                Err(_) => return true,
+            };
+
+            parsed.algorithm != Algorithm::Argon2id.ident()
+                || params.m_cost() != settings.argon2_memory_kib
+                || params.t_cost() != settings.argon2_iterations
+                || params.p_cost() != settings.argon2_parallelism
+        }
+    }
+}
+
+/// `hash` en el pool de hilos bloqueantes, para no frenar los workers de actix.
+pub async fn hash_blocking(
+    settings: &PasswordSettings,
+    password: String,
+) -> Result<String, PasswordError> {
+    let settings = settings.clone();
+    web::block(move || hash(&settings, &password))
+        .await
+        .map_err(|_| PasswordError::Blocking)?
+}
+
+/// `verify` en el pool de hilos bloqueantes.
+pub async fn verify_blocking(password: String, stored: String) -> Result<bool, PasswordError> {
+    web::block(move || verify(&password, &stored))
+        .await
+        .map_err(|_| PasswordError::Blocking)?
+}
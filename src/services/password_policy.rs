@@ -0,0 +1,254 @@
+use actix_web::web;
+use sha1::{Digest, Sha1};
+use std::borrow::Cow;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::config::password::PasswordPolicy;
+
+/// Contraseñas tan comunes que cualquier diccionario las prueba primero.
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "passw0rd", "p@ssword", "p@ssw0rd", "123456", "12345678", "123456789",
+    "1234567890", "qwerty", "qwertyuiop", "asdfghjkl", "zxcvbnm", "abc123", "111111",
+    "123123", "000000", "letmein", "welcome", "admin", "administrator", "iloveyou",
+    "monkey", "dragon", "football", "baseball", "soccer", "master", "sunshine", "shadow",
+    "princess", "superman", "batman", "trustno1", "starwars", "whatever", "freedom",
+    "hello", "charlie", "login", "secret", "changeme", "default", "qazwsx", "1q2w3e4r",
+    "zaq12wsx", "michael", "jennifer", "computer", "internet", "contraseña", "contrasena",
+];
+
+const KEYBOARD_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+fn violation(code: &'static str, message: String) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(Cow::Owned(message));
+    error
+}
+
+/// `c` continúa una secuencia tras `prev`: alfabeto, dígitos o fila del teclado.
+fn follows(prev: char, c: char) -> bool {
+    let (p, n) = (prev as i64, c as i64);
+    if prev.is_ascii_alphanumeric() && c.is_ascii_alphanumeric() && (n - p).abs() == 1 {
+        return true;
+    }
+
+    KEYBOARD_ROWS.iter().any(|row| {
+        let row: Vec<char> = row.chars().collect();
+        row.windows(2)
+            .any(|pair| (pair[0] == prev && pair[1] == c) || (pair[1] == prev && pair[0] == c))
+    })
+}
+
+/// Estimación del número de intentos necesarios, al estilo de zxcvbn pero
+/// mucho más simple: tamaño del alfabeto usado elevado a la longitud
+/// efectiva, donde repeticiones y secuencias apenas suman.
+fn estimate_guesses(password: &str) -> f64 {
+    let lower = password.to_lowercase();
+    let stripped = lower.trim_end_matches(|c: char| c.is_ascii_digit() || c.is_ascii_punctuation());
+    if COMMON_PASSWORDS.contains(&lower.as_str()) || COMMON_PASSWORDS.contains(&stripped) {
+        return 10.0;
+    }
+
+    let mut pool = 0.0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26.0;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26.0;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10.0;
+    }
+    if password.chars().any(|c| c.is_ascii_punctuation() || c == ' ') {
+        pool += 33.0;
+    }
+    if !password.is_ascii() {
+        pool += 100.0;
+    }
+
+    let mut effective_length = 0.0;
+    let mut prev: Option<char> = None;
+    for c in lower.chars() {
+        effective_length += match prev {
+            Some(p) if p == c || follows(p, c) => 0.25,
+            _ => 1.0,
+        };
+        prev = Some(c);
+    }
+
+    f64::powf(pool, effective_length)
+}
+
+/// Puntuación de 0 a 4 con los mismos umbrales que zxcvbn.
+pub fn strength_score(password: &str) -> u8 {
+    let log_guesses = estimate_guesses(password).log10();
+    match log_guesses {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+/// Partes de la identidad del usuario que no pueden aparecer en la contraseña.
+fn personal_terms<'a>(email: &'a str, name: &'a str) -> (Vec<&'a str>, Vec<&'a str>) {
+    let local_part = email.split('@').next().unwrap_or_default();
+    let email_terms = [email, local_part]
+        .into_iter()
+        .filter(|term| term.chars().count() >= 3)
+        .collect();
+    let name_terms = name
+        .split_whitespace()
+        .filter(|term| term.chars().count() >= 3)
+        .collect();
+    (email_terms, name_terms)
+}
+
+/// Busca la contraseña en el corpus local sin leerlo entero: solo el fichero
+/// del prefijo SHA-1, como el API de rangos de Have I Been Pwned.
+fn is_breached(corpus_dir: &Path, password: &str) -> bool {
+    let digest = hex::encode_upper(Sha1::digest(password.as_bytes()));
+    let (prefix, suffix) = digest.split_at(5);
+
+    let file = match std::fs::File::open(corpus_dir.join(prefix)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return false,
+        Err(e) => {
+            log::error!("Error reading breached password corpus: {}", e);
+            return false;
+        }
+    };
+
+    BufReader::new(file).lines().map_while(Result::ok).any(|line| {
+        line.split(':')
+            .next()
+            .is_some_and(|candidate| candidate.trim().eq_ignore_ascii_case(suffix))
+    })
+}
+
+/// Aplica la política a una contraseña nueva.
+///
+/// Los errores se devuelven en `field` con el formato de `validator`, para que
+/// el cliente los reciba igual que el resto de errores de validación.
+pub async fn check(
+    policy: &PasswordPolicy,
+    field: &'static str,
+    password: &str,
+    email: &str,
+    name: &str,
+) -> Result<(), ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    let length = password.chars().count();
+
+    if length < policy.min_length {
+        errors.add(field, violation(
+            "password_too_short",
+            format!("Password must be at least {} characters", policy.min_length),
+        ));
+    }
+    if length > policy.max_length {
+        errors.add(field, violation(
+            "password_too_long",
+            format!("Password must be at most {} characters", policy.max_length),
+        ));
+    }
+
+    let lower = password.to_lowercase();
+    let (email_terms, name_terms) = personal_terms(email, name);
+    if email_terms.iter().any(|term| lower.contains(&term.to_lowercase())) {
+        errors.add(field, violation(
+            "password_contains_email",
+            "Password must not contain your email address".to_string(),
+        ));
+    }
+    if name_terms.iter().any(|term| lower.contains(&term.to_lowercase())) {
+        errors.add(field, violation(
+            "password_contains_name",
+            "Password must not contain your name".to_string(),
+        ));
+    }
+
+    if strength_score(password) < policy.min_score {
+        errors.add(field, violation(
+            "password_too_weak",
+            "Password is too easy to guess; use a longer password or a mix of unrelated words"
+                .to_string(),
+        ));
+    }
+
+    if let Some(corpus_dir) = policy.breached_corpus_dir.clone() {
+        let candidate = password.to_string();
+        let breached = web::block(move || is_breached(&corpus_dir, &candidate))
+            .await
+            .unwrap_or(false);
+        if breached {
+            errors.add(field, violation(
+                "password_breached",
+                "Password appears in a known data breach; choose a different one".to_string(),
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(breached_corpus_dir: Option<std::path::PathBuf>) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 10,
+            max_length: 32,
+            min_score: 3,
+            breached_corpus_dir,
+            history_size: 0,
+            max_age: None,
+        }
+    }
+
+    async fn codes(policy: &PasswordPolicy, password: &str) -> Vec<String> {
+        match check(policy, "password", password, "countess@example.com", "Ada Lovelace").await {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.field_errors()["password"].iter().map(|e| e.code.to_string()).collect(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn strong_unrelated_passwords_pass() {
+        assert!(strength_score("Copper-Lantern-Orbit-71") >= 3);
+        assert!(codes(&policy(None), "Copper-Lantern-Orbit-71").await.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn each_rule_reports_its_own_code() {
+        let policy = policy(None);
+        assert_eq!(codes(&policy, "Qz7!").await, ["password_too_short", "password_too_weak"]);
+        assert_eq!(codes(&policy, &"Copper-Lantern-".repeat(3)).await, ["password_too_long"]);
+        assert_eq!(codes(&policy, "Copper-Countess-Orbit-71").await, ["password_contains_email"]);
+        assert_eq!(codes(&policy, "Copper-LOVELACE-Orbit").await, ["password_contains_name"]);
+        assert_eq!(codes(&policy, "Qwerty123456!").await, ["password_too_weak"]);
+        assert_eq!(strength_score("aaaa"), 0);
+    }
+
+    #[actix_web::test]
+    async fn breached_passwords_are_found_by_their_sha1_prefix() {
+        let dir = std::env::temp_dir().join(format!("breached-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let digest = hex::encode_upper(Sha1::digest(b"Copper-Lantern-Orbit-71"));
+        let (prefix, suffix) = digest.split_at(5);
+        std::fs::write(dir.join(prefix), format!("0000000000000000000000000000000000A:3\n{}:12\n", suffix)).unwrap();
+
+        let policy = policy(Some(dir.clone()));
+        assert_eq!(codes(&policy, "Copper-Lantern-Orbit-71").await, ["password_breached"]);
+        // Sin fichero para su prefijo no está filtrada
+        assert!(codes(&policy, "Granite-Willow-Signal-48").await.is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
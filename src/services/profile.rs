@@ -0,0 +1,52 @@
+use serde_json::Value;
+
+use crate::config::profile::ProfileSettings;
+
+/// Aplica `patch` sobre `target` según JSON Merge Patch (RFC 7386): los
+/// objetos se fusionan, `null` borra la clave y el resto sustituye.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Comprueba el tamaño y el JSON Schema de `metadata`; devuelve los errores
+/// legibles si no es válido.
+pub fn validate_metadata(settings: &ProfileSettings, metadata: &Value) -> Result<(), Vec<String>> {
+    let size = metadata.to_string().len();
+    if size > settings.metadata_max_bytes {
+        return Err(vec![format!(
+            "metadata is {} bytes; the maximum is {}",
+            size, settings.metadata_max_bytes
+        )]);
+    }
+
+    settings.metadata_schema.validate(metadata).map_err(|errors| {
+        errors
+            .map(|e| {
+                let path = e.instance_path.to_string();
+                if path.is_empty() {
+                    e.to_string()
+                } else {
+                    format!("{}: {}", path, e)
+                }
+            })
+            .collect()
+    })
+}
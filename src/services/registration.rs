@@ -0,0 +1,128 @@
+use std::fmt;
+
+use crate::config::registration::RegistrationSettings;
+
+/// Motivo por el que un correo no puede darse de alta.
+#[derive(Debug, PartialEq)]
+pub enum DomainRejection {
+    NotAllowed,
+    Blocked,
+    Disposable,
+}
+
+impl DomainRejection {
+    /// Valor de `reason` en la auditoría.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DomainRejection::NotAllowed => "domain_not_allowed",
+            DomainRejection::Blocked => "domain_blocked",
+            DomainRejection::Disposable => "disposable_email",
+        }
+    }
+}
+
+impl fmt::Display for DomainRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainRejection::NotAllowed => write!(f, "Email domain is not allowed"),
+            DomainRejection::Blocked => write!(f, "Email domain is blocked"),
+            DomainRejection::Disposable => write!(f, "Disposable email addresses are not allowed"),
+        }
+    }
+}
+
+/// `domain` es `rule` o un subdominio suyo.
+fn matches(domain: &str, rule: &str) -> bool {
+    domain == rule || domain.strip_suffix(rule).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Aplica las listas de dominios permitidos, bloqueados y de correo temporal.
+pub fn check_email_domain(settings: &RegistrationSettings, email: &str) -> Result<(), DomainRejection> {
+    let domain = email
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.trim().to_lowercase())
+        .unwrap_or_default();
+
+    if !settings.allowed_domains.is_empty()
+        && !settings.allowed_domains.iter().any(|rule| matches(&domain, rule))
+    {
+        return Err(DomainRejection::NotAllowed);
+    }
+    if settings.blocked_domains.iter().any(|rule| matches(&domain, rule)) {
+        return Err(DomainRejection::Blocked);
+    }
+    // El dominio y sus padres: `a.mailinator.com`, `mailinator.com`, `com`
+    let mut parents = std::iter::successors(Some(domain.as_str()), |d| d.split_once('.').map(|(_, rest)| rest));
+    if parents.any(|d| settings.disposable_domains.contains(d)) {
+        return Err(DomainRejection::Disposable);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn settings(allowed: &[&str], blocked: &[&str], disposable: &[&str]) -> RegistrationSettings {
+        let list = |domains: &[&str]| domains.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        RegistrationSettings {
+            self_registration: true,
+            allowed_domains: list(allowed),
+            blocked_domains: list(blocked),
+            disposable_domains: list(disposable).into_iter().collect(),
+            captcha_verify_url: None,
+            captcha_secret: None,
+            invitation_url: String::new(),
+            invitation_ttl: Duration::hours(72),
+        }
+    }
+
+    #[test]
+    fn rules_match_the_domain_and_its_subdomains_only() {
+        let settings = settings(&["example.com"], &["blocked.example.com"], &[]);
+
+        assert_eq!(check_email_domain(&settings, "ada@example.com"), Ok(()));
+        assert_eq!(check_email_domain(&settings, "ada@eu.example.com"), Ok(()));
+        assert_eq!(check_email_domain(&settings, "ada@Example.COM"), Ok(()));
+        assert_eq!(
+            check_email_domain(&settings, "ada@notexample.com"),
+            Err(DomainRejection::NotAllowed)
+        );
+        assert_eq!(
+            check_email_domain(&settings, "ada@example.com.evil.org"),
+            Err(DomainRejection::NotAllowed)
+        );
+        assert_eq!(
+            check_email_domain(&settings, "ada@team.blocked.example.com"),
+            Err(DomainRejection::Blocked)
+        );
+    }
+
+    #[test]
+    fn empty_allow_list_admits_any_domain_not_blocked() {
+        let settings = settings(&[], &["competitor.com"], &[]);
+
+        assert_eq!(check_email_domain(&settings, "ada@anywhere.org"), Ok(()));
+        assert_eq!(
+            check_email_domain(&settings, "ada@competitor.com"),
+            Err(DomainRejection::Blocked)
+        );
+        assert_eq!(check_email_domain(&settings, "not-an-email"), Ok(()));
+    }
+
+    #[test]
+    fn disposable_domains_are_checked_on_every_parent() {
+        let settings = settings(&[], &[], &["mailinator.com"]);
+
+        assert_eq!(
+            check_email_domain(&settings, "ada@mailinator.com"),
+            Err(DomainRejection::Disposable)
+        );
+        assert_eq!(
+            check_email_domain(&settings, "ada@inbox.eu.MAILINATOR.com"),
+            Err(DomainRejection::Disposable)
+        );
+        assert_eq!(check_email_domain(&settings, "ada@notmailinator.com"), Ok(()));
+    }
+}
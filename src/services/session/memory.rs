@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{SessionStore, SessionStoreError};
+use crate::models::session::Session;
+
+/// Sesiones en memoria del proceso.
+///
+/// No se comparten entre instancias ni sobreviven a un reinicio; pensado
+/// para desarrollo y para levantar la aplicación en pruebas.
+#[derive(Default)]
+pub struct InMemorySessionStore {
+    sessions: Mutex<HashMap<String, (Session, Instant)>>,
+}
+
+impl InMemorySessionStore {
+    fn live(&self) -> std::sync::MutexGuard<'_, HashMap<String, (Session, Instant)>> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        sessions.retain(|_, (_, expires)| *expires > now);
+        sessions
+    }
+}
+
+#[async_trait]
+impl SessionStore for InMemorySessionStore {
+    async fn create(&self, session: &Session, ttl_seconds: u64) -> Result<(), SessionStoreError> {
+        let expires = Instant::now() + Duration::from_secs(ttl_seconds);
+        self.live()
+            .insert(session.id.clone(), (session.clone(), expires));
+        Ok(())
+    }
+
+    async fn get(&self, session_id: &str) -> Result<Option<Session>, SessionStoreError> {
+        Ok(self.live().get(session_id).map(|(session, _)| session.clone()))
+    }
+
+    async fn update(&self, session: &Session, ttl_seconds: u64) -> Result<bool, SessionStoreError> {
+        match self.live().get_mut(&session.id) {
+            Some(entry) => {
+                *entry = (session.clone(), Instant::now() + Duration::from_secs(ttl_seconds));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn touch(&self, session: &Session, ttl_seconds: u64) -> Result<bool, SessionStoreError> {
+        match self.live().get_mut(&session.id) {
+            Some((stored, expires)) => {
+                stored.last_seen_at = stored.last_seen_at.max(session.last_seen_at);
+                *expires = Instant::now() + Duration::from_secs(ttl_seconds);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn revoke(&self, session_id: &str) -> Result<bool, SessionStoreError> {
+        Ok(self.live().remove(session_id).is_some())
+    }
+
+    async fn list_for_user(&self, user_id: i64) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .live()
+            .values()
+            .filter(|(session, _)| session.user_id == user_id)
+            .map(|(session, _)| session.clone())
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        Ok(sessions)
+    }
+
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, SessionStoreError> {
+        let mut sessions = self.live();
+        let before = sessions.len();
+        sessions.retain(|_, (session, _)| session.user_id != user_id);
+        Ok((before - sessions.len()) as u64)
+    }
+}
@@ -0,0 +1,102 @@
+use async_trait::async_trait;
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::redis::RedisPool;
+use crate::models::session::Session;
+
+pub mod memory;
+pub mod postgres;
+pub mod redis;
+
+pub use self::memory::InMemorySessionStore;
+pub use self::postgres::PgSessionStore;
+pub use self::redis::RedisSessionStore;
+
+#[derive(Debug)]
+pub enum SessionStoreError {
+    Redis(::redis::RedisError),
+    Database(sqlx::Error),
+    Serialization(serde_json::Error),
+}
+
+impl fmt::Display for SessionStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionStoreError::Redis(e) => write!(f, "Redis error: {}", e),
+            SessionStoreError::Database(e) => write!(f, "Database error: {}", e),
+            SessionStoreError::Serialization(e) => write!(f, "Invalid session data: {}", e),
+        }
+    }
+}
+
+impl From<::redis::RedisError> for SessionStoreError {
+    fn from(e: ::redis::RedisError) -> Self {
+        SessionStoreError::Redis(e)
+    }
+}
+
+impl From<sqlx::Error> for SessionStoreError {
+    fn from(e: sqlx::Error) -> Self {
+        SessionStoreError::Database(e)
+    }
+}
+
+impl From<serde_json::Error> for SessionStoreError {
+    fn from(e: serde_json::Error) -> Self {
+        SessionStoreError::Serialization(e)
+    }
+}
+
+/// Almacenamiento de sesiones; los handlers solo conocen este trait.
+///
+/// Las implementaciones no devuelven sesiones caducadas: `ttl_seconds`
+/// marca cuánto vive la sesión desde la última escritura.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn create(&self, session: &Session, ttl_seconds: u64) -> Result<(), SessionStoreError>;
+
+    async fn get(&self, session_id: &str) -> Result<Option<Session>, SessionStoreError>;
+
+    /// Reescribe una sesión existente y renueva su caducidad.
+    ///
+    /// Devuelve `false` si la sesión ya no existe; nunca la recrea.
+    async fn update(&self, session: &Session, ttl_seconds: u64) -> Result<bool, SessionStoreError>;
+
+    /// Registra actividad: adelanta `last_seen_at` al de `session` (nunca lo
+    /// retrasa) y renueva la caducidad, en una sola operación.
+    ///
+    /// El resto de la sesión guardada no se toca, para no deshacer un
+    /// `update` concurrente. Devuelve `false` si la sesión ya no existe.
+    async fn touch(&self, session: &Session, ttl_seconds: u64) -> Result<bool, SessionStoreError>;
+
+    async fn revoke(&self, session_id: &str) -> Result<bool, SessionStoreError>;
+
+    async fn list_for_user(&self, user_id: i64) -> Result<Vec<Session>, SessionStoreError>;
+
+    /// Revoca todas las sesiones del usuario y devuelve cuántas había.
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, SessionStoreError>;
+}
+
+/// Elige el backend según `SESSION_STORE` (`redis`, `postgres` o `memory`).
+///
+/// Se crea uno por tenant: `redis` llega ya con el prefijo del tenant y
+/// `postgres` filtra por `tenant_id`.
+pub fn build_store(
+    tenant_id: &str,
+    redis: Option<&RedisPool>,
+    pool: &sqlx::PgPool,
+) -> Result<Arc<dyn SessionStore>, String> {
+    let backend = env::var("SESSION_STORE").unwrap_or_else(|_| "redis".to_string());
+
+    match backend.to_lowercase().as_str() {
+        "redis" => match redis {
+            Some(redis) => Ok(Arc::new(RedisSessionStore::new(redis.clone()))),
+            None => Err("SESSION_STORE=redis requires REDIS_URL".to_string()),
+        },
+        "postgres" => Ok(Arc::new(PgSessionStore::new(pool.clone(), tenant_id.to_string()))),
+        "memory" => Ok(Arc::new(InMemorySessionStore::default())),
+        other => Err(format!("Unknown SESSION_STORE: {}", other)),
+    }
+}
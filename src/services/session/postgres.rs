@@ -0,0 +1,119 @@
+use async_trait::async_trait;
+use sqlx::types::Json;
+use sqlx::PgPool;
+
+use super::{SessionStore, SessionStoreError};
+use crate::models::session::Session;
+
+/// Sesiones en la tabla `sessions`, para despliegues sin Redis.
+///
+/// Cada tenant tiene su store y solo ve sus filas. Las caducadas se ignoran
+/// en las lecturas y se borran al crear sesiones nuevas.
+pub struct PgSessionStore {
+    pool: PgPool,
+    tenant_id: String,
+}
+
+impl PgSessionStore {
+    pub fn new(pool: PgPool, tenant_id: String) -> Self {
+        Self { pool, tenant_id }
+    }
+}
+
+#[async_trait]
+impl SessionStore for PgSessionStore {
+    async fn create(&self, session: &Session, ttl_seconds: u64) -> Result<(), SessionStoreError> {
+        sqlx::query("DELETE FROM sessions WHERE tenant_id = $1 AND expires_at <= now()")
+            .bind(&self.tenant_id)
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO sessions (id, user_id, data, expires_at, tenant_id) \
+             VALUES ($1, $2, $3, now() + make_interval(secs => $4), $5)",
+        )
+        .bind(&session.id)
+        .bind(session.user_id)
+        .bind(Json(session))
+        .bind(ttl_seconds as f64)
+        .bind(&self.tenant_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get(&self, session_id: &str) -> Result<Option<Session>, SessionStoreError> {
+        let row = sqlx::query_scalar::<_, Json<Session>>(
+            "SELECT data FROM sessions WHERE id = $1 AND tenant_id = $2 AND expires_at > now()",
+        )
+        .bind(session_id)
+        .bind(&self.tenant_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|Json(session)| session))
+    }
+
+    async fn update(&self, session: &Session, ttl_seconds: u64) -> Result<bool, SessionStoreError> {
+        let result = sqlx::query(
+            "UPDATE sessions SET data = $2, expires_at = now() + make_interval(secs => $3) \
+             WHERE id = $1 AND tenant_id = $4 AND expires_at > now()",
+        )
+        .bind(&session.id)
+        .bind(Json(session))
+        .bind(ttl_seconds as f64)
+        .bind(&self.tenant_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn touch(&self, session: &Session, ttl_seconds: u64) -> Result<bool, SessionStoreError> {
+        let result = sqlx::query(
+            "UPDATE sessions SET data = jsonb_set(data, '{last_seen_at}', \
+             to_jsonb(GREATEST(COALESCE((data->>'last_seen_at')::bigint, 0), $2))), \
+             expires_at = now() + make_interval(secs => $3) \
+             WHERE id = $1 AND tenant_id = $4 AND expires_at > now()",
+        )
+        .bind(&session.id)
+        .bind(session.last_seen_at)
+        .bind(ttl_seconds as f64)
+        .bind(&self.tenant_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn revoke(&self, session_id: &str) -> Result<bool, SessionStoreError> {
+        let result = sqlx::query(
+            "DELETE FROM sessions WHERE id = $1 AND tenant_id = $2 AND expires_at > now()",
+        )
+        .bind(session_id)
+        .bind(&self.tenant_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn list_for_user(&self, user_id: i64) -> Result<Vec<Session>, SessionStoreError> {
+        let rows = sqlx::query_scalar::<_, Json<Session>>(
+            "SELECT data FROM sessions WHERE user_id = $1 AND tenant_id = $2 AND expires_at > now() \
+             ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .bind(&self.tenant_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|Json(session)| session).collect())
+    }
+
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, SessionStoreError> {
+        let result = sqlx::query(
+            "DELETE FROM sessions WHERE user_id = $1 AND tenant_id = $2 AND expires_at > now()",
+        )
+        .bind(user_id)
+        .bind(&self.tenant_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,231 @@
+use async_trait::async_trait;
+use redis::{AsyncCommands, Script};
+use std::sync::LazyLock;
+
+use super::{SessionStore, SessionStoreError};
+use crate::config::redis::RedisPool;
+use crate::models::session::Session;
+
+// Claves de sesión en Redis, bajo el prefijo del tenant del pool:
+//   session:{session_id}     -> JSON de la sesión
+//   user_sessions:{user_id}  -> set con los ids de sesión del usuario
+const SESSION_PREFIX: &str = "session:";
+const USER_SESSIONS_PREFIX: &str = "user_sessions:";
+
+/// Crea la sesión y la añade al índice del usuario en una sola operación.
+///
+/// El índice vive tanto como la sesión más reciente; las entradas de
+/// sesiones ya caducadas se limpian al listar.
+static CREATE_SCRIPT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        redis.call('SET', KEYS[1], ARGV[1], 'EX', ARGV[3])
+        redis.call('SADD', KEYS[2], ARGV[2])
+        if redis.call('TTL', KEYS[2]) < tonumber(ARGV[3]) then
+            redis.call('EXPIRE', KEYS[2], ARGV[3])
+        end
+        return 1
+        ",
+    )
+});
+
+/// Reescribe la sesión solo si sigue existiendo y alarga el índice.
+static UPDATE_SCRIPT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        if redis.call('SET', KEYS[1], ARGV[1], 'XX', 'EX', ARGV[2]) then
+            if redis.call('TTL', KEYS[2]) < tonumber(ARGV[2]) then
+                redis.call('EXPIRE', KEYS[2], ARGV[2])
+            end
+            return 1
+        end
+        return 0
+        ",
+    )
+});
+
+/// Adelanta `last_seen_at` en la sesión guardada y renueva la caducidad.
+///
+/// Se edita el JSON en Redis en vez de reescribirlo desde la petición, así
+/// una actividad que llega tarde no deshace un `update` (token reemitido,
+/// reautenticación) hecho entretanto. Se sustituye el número en el texto
+/// porque `cjson` codifica las listas vacías como objetos.
+static TOUCH_SCRIPT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+        local data = redis.call('GET', KEYS[1])
+        if not data then
+            return 0
+        end
+        local seen = tonumber(string.match(data, '"last_seen_at":(%d+)'))
+        if seen and seen < tonumber(ARGV[1]) then
+            data = string.gsub(data, '"last_seen_at":%d+', '"last_seen_at":' .. ARGV[1], 1)
+        end
+        redis.call('SET', KEYS[1], data, 'EX', ARGV[2])
+        if redis.call('TTL', KEYS[2]) < tonumber(ARGV[2]) then
+            redis.call('EXPIRE', KEYS[2], ARGV[2])
+        end
+        return 1
+        "#,
+    )
+});
+
+/// Elimina la sesión y su entrada en el índice del usuario juntas.
+static REVOKE_SCRIPT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        if redis.call('DEL', KEYS[1]) == 0 then
+            return 0
+        end
+        redis.call('SREM', KEYS[2], ARGV[1])
+        return 1
+        ",
+    )
+});
+
+/// Elimina las sesiones indicadas (`KEYS[2..]`, con sus ids en `ARGV`) y
+/// las saca del índice; el índice se borra si queda vacío.
+static REVOKE_ALL_SCRIPT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local revoked = 0
+        for i = 2, #KEYS do
+            revoked = revoked + redis.call('DEL', KEYS[i])
+            redis.call('SREM', KEYS[1], ARGV[i - 1])
+        end
+        if redis.call('SCARD', KEYS[1]) == 0 then
+            redis.call('DEL', KEYS[1])
+        end
+        return revoked
+        ",
+    )
+});
+
+pub struct RedisSessionStore {
+    redis: RedisPool,
+}
+
+impl RedisSessionStore {
+    pub fn new(redis: RedisPool) -> Self {
+        Self { redis }
+    }
+
+    fn user_sessions_key(&self, user_id: i64) -> String {
+        self.redis.key(&format!("{}{}", USER_SESSIONS_PREFIX, user_id))
+    }
+
+    fn session_key(&self, session_id: &str) -> String {
+        self.redis.key(&format!("{}{}", SESSION_PREFIX, session_id))
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn create(&self, session: &Session, ttl_seconds: u64) -> Result<(), SessionStoreError> {
+        let mut conn = self.redis.get().await?;
+        let _: i64 = CREATE_SCRIPT
+            .key(self.session_key(&session.id))
+            .key(self.user_sessions_key(session.user_id))
+            .arg(serde_json::to_string(session)?)
+            .arg(&session.id)
+            .arg(ttl_seconds)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, session_id: &str) -> Result<Option<Session>, SessionStoreError> {
+        let mut conn = self.redis.get().await?;
+        let data: Option<String> = conn.get(self.session_key(session_id)).await?;
+
+        match data {
+            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn update(&self, session: &Session, ttl_seconds: u64) -> Result<bool, SessionStoreError> {
+        let mut conn = self.redis.get().await?;
+        let updated: i64 = UPDATE_SCRIPT
+            .key(self.session_key(&session.id))
+            .key(self.user_sessions_key(session.user_id))
+            .arg(serde_json::to_string(session)?)
+            .arg(ttl_seconds)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(updated == 1)
+    }
+
+    async fn touch(&self, session: &Session, ttl_seconds: u64) -> Result<bool, SessionStoreError> {
+        let mut conn = self.redis.get().await?;
+        let touched: i64 = TOUCH_SCRIPT
+            .key(self.session_key(&session.id))
+            .key(self.user_sessions_key(session.user_id))
+            .arg(session.last_seen_at)
+            .arg(ttl_seconds)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(touched == 1)
+    }
+
+    async fn revoke(&self, session_id: &str) -> Result<bool, SessionStoreError> {
+        // El usuario no cambia nunca: leerlo antes no abre ninguna carrera
+        let user_id = match self.get(session_id).await? {
+            Some(session) => session.user_id,
+            None => return Ok(false),
+        };
+
+        let mut conn = self.redis.get().await?;
+        let revoked: i64 = REVOKE_SCRIPT
+            .key(self.session_key(session_id))
+            .key(self.user_sessions_key(user_id))
+            .arg(session_id)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(revoked == 1)
+    }
+
+    async fn list_for_user(&self, user_id: i64) -> Result<Vec<Session>, SessionStoreError> {
+        let mut conn = self.redis.get().await?;
+        let ids: Vec<String> = conn.smembers(self.user_sessions_key(user_id)).await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let keys: Vec<String> = ids.iter().map(|id| self.session_key(id)).collect();
+        let values: Vec<Option<String>> = redis::cmd("MGET")
+            .arg(&keys)
+            .query_async(&mut conn)
+            .await?;
+
+        let mut sessions = Vec::new();
+        let mut stale = Vec::new();
+        for (id, value) in ids.into_iter().zip(values) {
+            match value {
+                Some(data) => sessions.push(serde_json::from_str::<Session>(&data)?),
+                None => stale.push(id),
+            }
+        }
+
+        if !stale.is_empty() {
+            let _: i64 = conn.srem(self.user_sessions_key(user_id), stale).await?;
+        }
+
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        Ok(sessions)
+    }
+
+    /// Revoca las sesiones que hay en el índice al empezar; todas sus claves
+    /// se pasan al script en `KEYS`.
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, SessionStoreError> {
+        let mut conn = self.redis.get().await?;
+        let ids: Vec<String> = conn.smembers(self.user_sessions_key(user_id)).await?;
+
+        let mut invocation = REVOKE_ALL_SCRIPT.key(self.user_sessions_key(user_id));
+        for id in &ids {
+            invocation.key(self.session_key(id)).arg(id);
+        }
+        let revoked: u64 = invocation.invoke_async(&mut conn).await?;
+        Ok(revoked)
+    }
+}
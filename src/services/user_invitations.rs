@@ -0,0 +1,173 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::fmt;
+
+use crate::models::user::User;
+use crate::models::user_invitation::UserInvitation;
+use crate::services::crypto::{random_token, sha256_hex};
+use crate::services::users::normalize_email;
+use crate::services::webhooks::{self, UserEvent};
+
+#[derive(Debug)]
+pub enum InviteError {
+    /// Ya existe un usuario activo con ese correo en el tenant.
+    EmailTaken,
+    /// Token desconocido, caducado o ya usado.
+    InvalidInvitation,
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for InviteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InviteError::EmailTaken => write!(f, "Email already registered"),
+            InviteError::InvalidInvitation => write!(f, "Invalid or expired invitation"),
+            InviteError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for InviteError {
+    fn from(e: sqlx::Error) -> Self {
+        InviteError::Database(e)
+    }
+}
+
+/// Crea el usuario pendiente en el tenant y su token de invitación.
+///
+/// `unusable_password` es el hash de una contraseña aleatoria: hasta que
+/// acepta no puede entrar con contraseña. Si el correo ya es de un usuario
+/// pendiente se le reenvía una invitación nueva y se anulan las anteriores.
+pub async fn create(
+    pool: &PgPool,
+    tenant_id: &str,
+    invited_by: i64,
+    email: &str,
+    name: &str,
+    unusable_password: &str,
+    ttl: Duration,
+) -> Result<(UserInvitation, String), InviteError> {
+    let email = normalize_email(email);
+    let mut tx = pool.begin().await?;
+
+    let created = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO users (tenant_id, email, password, name) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (tenant_id, email) DO NOTHING RETURNING id",
+    )
+    .bind(tenant_id)
+    .bind(&email)
+    .bind(unusable_password)
+    .bind(name)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let user_id = match created {
+        Some(user_id) => user_id,
+        None => {
+            let pending = sqlx::query_scalar::<_, i64>(
+                "SELECT u.id FROM users u \
+                 WHERE u.tenant_id = $1 AND u.email = $2 \
+                   AND EXISTS (SELECT 1 FROM user_invitations i \
+                               WHERE i.user_id = u.id AND i.accepted_at IS NULL)",
+            )
+            .bind(tenant_id)
+            .bind(&email)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            let user_id = pending.ok_or(InviteError::EmailTaken)?;
+            sqlx::query("DELETE FROM user_invitations WHERE user_id = $1 AND accepted_at IS NULL")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            user_id
+        }
+    };
+
+    let token = random_token(48);
+    let invitation = sqlx::query_as::<_, UserInvitation>(
+        "INSERT INTO user_invitations (user_id, token_hash, invited_by, expires_at) \
+         VALUES ($1, $2, $3, $4) \
+         RETURNING id, user_id, $5::TEXT AS email, invited_by, expires_at, accepted_at, created_at",
+    )
+    .bind(user_id)
+    .bind(sha256_hex(&token))
+    .bind(invited_by)
+    .bind(Utc::now() + ttl)
+    .bind(&email)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok((invitation, token))
+}
+
+/// Invitación vigente del tenant para el token, si la hay.
+pub async fn find_pending(
+    pool: &PgPool,
+    tenant_id: &str,
+    token: &str,
+) -> Result<Option<UserInvitation>, sqlx::Error> {
+    sqlx::query_as::<_, UserInvitation>(
+        "SELECT i.id, i.user_id, u.email, i.invited_by, i.expires_at, i.accepted_at, i.created_at \
+         FROM user_invitations i JOIN users u ON u.id = i.user_id \
+         WHERE i.token_hash = $1 AND u.tenant_id = $2 \
+           AND i.accepted_at IS NULL AND i.expires_at > now()",
+    )
+    .bind(sha256_hex(token))
+    .bind(tenant_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Consume la invitación y activa al usuario con su nombre y contraseña.
+///
+/// El alta cuenta como registro: se emite `user.registered` en este momento.
+pub async fn accept(
+    pool: &PgPool,
+    tenant_id: &str,
+    invitation_id: i64,
+    name: &str,
+    password_hash: &str,
+) -> Result<User, InviteError> {
+    let mut tx = pool.begin().await?;
+
+    // Solo una aceptación gana si llegan dos a la vez
+    let user_id = sqlx::query_scalar::<_, i64>(
+        "UPDATE user_invitations SET accepted_at = now() \
+         WHERE id = $1 AND accepted_at IS NULL AND expires_at > now() \
+         RETURNING user_id",
+    )
+    .bind(invitation_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(InviteError::InvalidInvitation)?;
+
+    let user = sqlx::query_as::<_, User>(
+        "UPDATE users SET name = $2, password = $3, password_changed_at = now() \
+         WHERE id = $1 RETURNING id, email, password, name",
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(password_hash)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("INSERT INTO password_history (user_id, password_hash) VALUES ($1, $2)")
+        .bind(user.id)
+        .bind(password_hash)
+        .execute(&mut *tx)
+        .await?;
+
+    let event = UserEvent::Registered {
+        user_id: user.id,
+        email: user.email.clone(),
+        name: user.name.clone(),
+        provider: None,
+        invited: true,
+    };
+    webhooks::enqueue(&mut *tx, tenant_id, &event).await?;
+
+    tx.commit().await?;
+    Ok(user)
+}
@@ -0,0 +1,342 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use super::{normalize_email, RepositoryError, UserRepository};
+use crate::models::otp::OtpFactor;
+use crate::models::profile::{Profile, UpdateProfile};
+use crate::models::user::User;
+use crate::services::avatar::StoredAvatar;
+use crate::services::webhooks::UserEvent;
+
+#[derive(Default)]
+struct State {
+    users: Vec<User>,
+    /// `(user_id, hash)` en orden de inserción.
+    password_history: Vec<(i64, String)>,
+    password_changed_at: HashMap<i64, DateTime<Utc>>,
+    otp_factors: HashMap<i64, OtpFactor>,
+    /// Perfil de cada usuario; se crea al darlo de alta.
+    profiles: HashMap<i64, Profile>,
+    /// `(user_id, provider, subject)` de las identidades externas vinculadas.
+    identities: Vec<(i64, String, String)>,
+    /// Eventos que `PgUserRepository` dejaría en el outbox, en orden.
+    events: Vec<UserEvent>,
+}
+
+impl State {
+    fn insert_user(&mut self, email: &str, password_hash: &str, name: &str) -> Result<User, RepositoryError> {
+        let email = normalize_email(email);
+        if self.users.iter().any(|u| u.email == email) {
+            return Err(RepositoryError::EmailTaken);
+        }
+
+        let user = User {
+            id: self.users.iter().map(|u| u.id).max().unwrap_or(0) + 1,
+            email,
+            password: password_hash.to_string(),
+            name: name.to_string(),
+        };
+        self.users.push(user.clone());
+        let now = Utc::now();
+        self.profiles.insert(
+            user.id,
+            Profile {
+                id: user.id,
+                email: user.email.clone(),
+                name: user.name.clone(),
+                avatar_url: None,
+                avatar_thumbnails: None,
+                avatar_key: None,
+                locale: None,
+                timezone: None,
+                phone_number: None,
+                phone_verified_at: None,
+                metadata: serde_json::json!({}),
+                created_at: now,
+                updated_at: now,
+                last_login_at: None,
+            },
+        );
+        self.password_history.push((user.id, password_hash.to_string()));
+        self.password_changed_at.insert(user.id, Utc::now());
+        Ok(user)
+    }
+}
+
+/// Doble de pruebas de `UserRepository`: usuarios en memoria del proceso.
+#[derive(Default)]
+pub struct InMemoryUserRepository {
+    state: Mutex<State>,
+}
+
+impl InMemoryUserRepository {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Cambia la antigüedad de la contraseña para probar la caducidad.
+    pub fn set_password_changed_at(&self, id: i64, changed_at: DateTime<Utc>) {
+        self.state().password_changed_at.insert(id, changed_at);
+    }
+
+    /// Eventos emitidos hasta ahora, en orden.
+    pub fn events(&self) -> Vec<UserEvent> {
+        self.state().events.clone()
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn find_by_id(&self, id: i64) -> Result<Option<User>, RepositoryError> {
+        Ok(self.state().users.iter().find(|u| u.id == id).cloned())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+        let email = normalize_email(email);
+        Ok(self.state().users.iter().find(|u| u.email == email).cloned())
+    }
+
+    async fn find_by_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<User>, RepositoryError> {
+        let state = self.state();
+        let user_id = state
+            .identities
+            .iter()
+            .find(|(_, p, s)| p == provider && s == subject)
+            .map(|(id, _, _)| *id);
+        Ok(user_id.and_then(|id| state.users.iter().find(|u| u.id == id).cloned()))
+    }
+
+    async fn link_identity(
+        &self,
+        id: i64,
+        provider: &str,
+        subject: &str,
+        _email: &str,
+    ) -> Result<bool, RepositoryError> {
+        let mut state = self.state();
+        if !state.users.iter().any(|u| u.id == id)
+            || state.identities.iter().any(|(user_id, p, _)| *user_id == id && p == provider)
+        {
+            return Ok(false);
+        }
+        if state.identities.iter().any(|(_, p, s)| p == provider && s == subject) {
+            return Err(RepositoryError::IdentityTaken);
+        }
+        state.identities.push((id, provider.to_string(), subject.to_string()));
+        Ok(true)
+    }
+
+    async fn create(
+        &self,
+        email: &str,
+        password_hash: &str,
+        name: &str,
+    ) -> Result<User, RepositoryError> {
+        let mut state = self.state();
+        let user = state.insert_user(email, password_hash, name)?;
+        state.events.push(UserEvent::Registered {
+            user_id: user.id,
+            email: user.email.clone(),
+            name: user.name.clone(),
+            provider: None,
+            invited: false,
+        });
+        Ok(user)
+    }
+
+    async fn create_with_identity(
+        &self,
+        email: &str,
+        password_hash: &str,
+        name: &str,
+        provider: &str,
+        subject: &str,
+    ) -> Result<User, RepositoryError> {
+        let mut state = self.state();
+        if state.identities.iter().any(|(_, p, s)| p == provider && s == subject) {
+            return Err(RepositoryError::IdentityTaken);
+        }
+        let user = state.insert_user(email, password_hash, name)?;
+        state.identities.push((user.id, provider.to_string(), subject.to_string()));
+        state.events.push(UserEvent::Registered {
+            user_id: user.id,
+            email: user.email.clone(),
+            name: user.name.clone(),
+            provider: Some(provider.to_string()),
+            invited: false,
+        });
+        Ok(user)
+    }
+
+    async fn update_password(&self, id: i64, password_hash: &str) -> Result<bool, RepositoryError> {
+        match self.state().users.iter_mut().find(|u| u.id == id) {
+            Some(user) => {
+                user.password = password_hash.to_string();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_password(&self, id: i64, password_hash: &str) -> Result<bool, RepositoryError> {
+        let mut state = self.state();
+        match state.users.iter_mut().find(|u| u.id == id) {
+            Some(user) => user.password = password_hash.to_string(),
+            None => return Ok(false),
+        }
+        state.password_history.push((id, password_hash.to_string()));
+        state.password_changed_at.insert(id, Utc::now());
+        state.events.push(UserEvent::PasswordChanged { user_id: id });
+        Ok(true)
+    }
+
+    async fn recent_password_hashes(
+        &self,
+        id: i64,
+        limit: usize,
+    ) -> Result<Vec<String>, RepositoryError> {
+        Ok(self
+            .state()
+            .password_history
+            .iter()
+            .rev()
+            .filter(|(user_id, _)| *user_id == id)
+            .take(limit)
+            .map(|(_, hash)| hash.clone())
+            .collect())
+    }
+
+    async fn password_changed_at(&self, id: i64) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        Ok(self.state().password_changed_at.get(&id).copied())
+    }
+
+    async fn otp_factor(&self, id: i64) -> Result<Option<OtpFactor>, RepositoryError> {
+        Ok(self.state().otp_factors.get(&id).cloned())
+    }
+
+    async fn set_otp_factor(&self, id: i64, factor: &OtpFactor) -> Result<(), RepositoryError> {
+        self.state().otp_factors.insert(id, factor.clone());
+        Ok(())
+    }
+
+    async fn remove_otp_factor(&self, id: i64) -> Result<bool, RepositoryError> {
+        Ok(self.state().otp_factors.remove(&id).is_some())
+    }
+
+    async fn profile(&self, id: i64) -> Result<Option<Profile>, RepositoryError> {
+        Ok(self.state().profiles.get(&id).cloned())
+    }
+
+    async fn update_profile(
+        &self,
+        id: i64,
+        update: &UpdateProfile,
+    ) -> Result<Option<Profile>, RepositoryError> {
+        let mut state = self.state();
+        let profile = match state.profiles.get_mut(&id) {
+            Some(profile) => profile,
+            None => return Ok(None),
+        };
+
+        if let Some(name) = &update.name {
+            profile.name = name.clone();
+        }
+        if let Some(avatar_url) = &update.avatar_url {
+            profile.avatar_url = avatar_url.clone();
+            profile.avatar_thumbnails = None;
+            profile.avatar_key = None;
+        }
+        if let Some(locale) = &update.locale {
+            profile.locale = locale.clone();
+        }
+        if let Some(timezone) = &update.timezone {
+            profile.timezone = timezone.clone();
+        }
+        if let Some(phone_number) = &update.phone_number {
+            if *phone_number != profile.phone_number {
+                profile.phone_verified_at = None;
+            }
+            profile.phone_number = phone_number.clone();
+        }
+        if let Some(metadata) = &update.metadata {
+            profile.metadata = metadata.clone();
+        }
+        profile.updated_at = Utc::now();
+        let profile = profile.clone();
+
+        if let Some(user) = state.users.iter_mut().find(|u| u.id == id) {
+            user.name = profile.name.clone();
+        }
+        Ok(Some(profile))
+    }
+
+    async fn mark_phone_verified(&self, id: i64, phone: &str) -> Result<bool, RepositoryError> {
+        let mut state = self.state();
+        let taken = state.profiles.values().any(|p| {
+            p.id != id && p.phone_verified_at.is_some() && p.phone_number.as_deref() == Some(phone)
+        });
+        if taken {
+            return Err(RepositoryError::PhoneTaken);
+        }
+
+        match state.profiles.get_mut(&id) {
+            Some(profile) if profile.phone_number.as_deref() == Some(phone) => {
+                profile.phone_verified_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn set_avatar(
+        &self,
+        id: i64,
+        avatar: Option<&StoredAvatar>,
+    ) -> Result<Option<Profile>, RepositoryError> {
+        let mut state = self.state();
+        Ok(state.profiles.get_mut(&id).map(|profile| {
+            profile.avatar_url = avatar.map(|a| a.url.clone());
+            profile.avatar_thumbnails = avatar.map(|a| a.thumbnails.clone());
+            profile.avatar_key = avatar.map(|a| a.key.clone());
+            profile.updated_at = Utc::now();
+            profile.clone()
+        }))
+    }
+
+    async fn record_login(&self, id: i64, amr: &[String]) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        if let Some(profile) = state.profiles.get_mut(&id) {
+            profile.last_login_at = Some(Utc::now());
+        }
+        state.events.push(UserEvent::Login {
+            user_id: id,
+            amr: amr.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Sin organizaciones en memoria: solo se borran los datos del usuario.
+    async fn delete(&self, id: i64) -> Result<bool, RepositoryError> {
+        let mut state = self.state();
+        let Some(index) = state.users.iter().position(|u| u.id == id) else {
+            return Ok(false);
+        };
+        let user = state.users.remove(index);
+        state.password_history.retain(|(user_id, _)| *user_id != id);
+        state.password_changed_at.remove(&id);
+        state.otp_factors.remove(&id);
+        state.profiles.remove(&id);
+        state.identities.retain(|(user_id, _, _)| *user_id != id);
+        state.events.push(UserEvent::Deleted {
+            user_id: id,
+            email: user.email,
+        });
+        Ok(true)
+    }
+}
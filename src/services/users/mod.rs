@@ -0,0 +1,179 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+use crate::models::otp::OtpFactor;
+use crate::models::profile::{Profile, UpdateProfile};
+use crate::models::user::User;
+use crate::services::avatar::StoredAvatar;
+
+#[cfg(test)]
+pub mod memory;
+pub mod postgres;
+
+#[cfg(test)]
+pub use self::memory::InMemoryUserRepository;
+pub use self::postgres::PgUserRepository;
+
+/// Índices únicos cuyas violaciones tienen error propio (`migrations/0010_add_tenants.sql`
+/// y `0013_unique_verified_phone.sql`).
+const EMAIL_UNIQUE_INDEX: &str = "users_tenant_email_idx";
+const PHONE_UNIQUE_INDEX: &str = "users_tenant_verified_phone_idx";
+const IDENTITY_UNIQUE_INDEX: &str = "user_identities_tenant_subject_idx";
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    EmailTaken,
+    /// Otra cuenta del tenant ya tiene verificado ese teléfono.
+    PhoneTaken,
+    /// La identidad externa ya está vinculada a otra cuenta del tenant.
+    IdentityTaken,
+    /// El usuario es el único owner de una organización con más miembros.
+    SoleOwner,
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepositoryError::EmailTaken => write!(f, "Email already registered"),
+            RepositoryError::PhoneTaken => write!(f, "Phone number already verified by another account"),
+            RepositoryError::IdentityTaken => {
+                write!(f, "External identity already linked to another account")
+            }
+            RepositoryError::SoleOwner => {
+                write!(f, "User is the only owner of an organization with other members")
+            }
+            RepositoryError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for RepositoryError {
+    fn from(e: sqlx::Error) -> Self {
+        // Solo los índices conocidos; cualquier otra violación es un error de base de datos
+        let constraint = e
+            .as_database_error()
+            .filter(|d| d.code().as_deref() == Some("23505"))
+            .and_then(|d| d.constraint());
+        match constraint {
+            Some(EMAIL_UNIQUE_INDEX) => RepositoryError::EmailTaken,
+            Some(PHONE_UNIQUE_INDEX) => RepositoryError::PhoneTaken,
+            Some(IDENTITY_UNIQUE_INDEX) => RepositoryError::IdentityTaken,
+            _ => RepositoryError::Database(e),
+        }
+    }
+}
+
+/// Forma canónica de un correo: sin espacios alrededor y en minúsculas.
+///
+/// Los repositorios la aplican al guardar y al buscar, y
+/// `migrations/0016_normalize_user_emails.sql` la exige en la tabla.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Acceso a la tabla `users`; `AuthService` y los handlers solo usan este trait.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find_by_id(&self, id: i64) -> Result<Option<User>, RepositoryError>;
+
+    /// Busca por la forma normalizada del correo (ver `normalize_email`).
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError>;
+
+    /// Usuario vinculado a la identidad externa `subject` del proveedor.
+    async fn find_by_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<User>, RepositoryError>;
+
+    /// Vincula una identidad externa a un usuario existente.
+    ///
+    /// Devuelve `false` si el usuario ya tiene otra identidad de ese proveedor.
+    async fn link_identity(
+        &self,
+        id: i64,
+        provider: &str,
+        subject: &str,
+        email: &str,
+    ) -> Result<bool, RepositoryError>;
+
+    /// Crea el usuario con la contraseña ya hasheada y la anota en el historial.
+    ///
+    /// Falla con `EmailTaken` si el correo ya existe.
+    async fn create(
+        &self,
+        email: &str,
+        password_hash: &str,
+        name: &str,
+    ) -> Result<User, RepositoryError>;
+
+    /// Como `create`, pero para quien entra por un proveedor externo: el
+    /// usuario nace vinculado a la identidad, en la misma transacción.
+    async fn create_with_identity(
+        &self,
+        email: &str,
+        password_hash: &str,
+        name: &str,
+        provider: &str,
+        subject: &str,
+    ) -> Result<User, RepositoryError>;
+
+    /// Sustituye el hash de la misma contraseña (rehash); no cuenta como cambio.
+    async fn update_password(&self, id: i64, password_hash: &str) -> Result<bool, RepositoryError>;
+
+    /// Cambia la contraseña: la anota en el historial, reinicia su antigüedad
+    /// y encola el webhook `user.password_changed`, todo en una transacción.
+    async fn set_password(&self, id: i64, password_hash: &str) -> Result<bool, RepositoryError>;
+
+    /// Hashes de las últimas `limit` contraseñas, la más reciente primero.
+    async fn recent_password_hashes(
+        &self,
+        id: i64,
+        limit: usize,
+    ) -> Result<Vec<String>, RepositoryError>;
+
+    async fn password_changed_at(&self, id: i64) -> Result<Option<DateTime<Utc>>, RepositoryError>;
+
+    /// Segundo factor por código de un solo uso, si el usuario lo tiene activo.
+    async fn otp_factor(&self, id: i64) -> Result<Option<OtpFactor>, RepositoryError>;
+
+    /// Activa el factor o sustituye el que hubiera.
+    async fn set_otp_factor(&self, id: i64, factor: &OtpFactor) -> Result<(), RepositoryError>;
+
+    async fn remove_otp_factor(&self, id: i64) -> Result<bool, RepositoryError>;
+
+    async fn profile(&self, id: i64) -> Result<Option<Profile>, RepositoryError>;
+
+    /// Aplica los campos presentes en `update`; `metadata`, si viene, sustituye
+    /// al actual. Cambiar el teléfono anula su verificación.
+    async fn update_profile(
+        &self,
+        id: i64,
+        update: &UpdateProfile,
+    ) -> Result<Option<Profile>, RepositoryError>;
+
+    /// Marca el teléfono como verificado si sigue siendo el del perfil.
+    ///
+    /// Devuelve `false` si el usuario lo cambió entre el envío y la
+    /// confirmación, y falla con `PhoneTaken` si otra cuenta ya lo verificó.
+    async fn mark_phone_verified(&self, id: i64, phone: &str) -> Result<bool, RepositoryError>;
+
+    /// Sustituye el avatar subido (o lo quita con `None`).
+    async fn set_avatar(
+        &self,
+        id: i64,
+        avatar: Option<&StoredAvatar>,
+    ) -> Result<Option<Profile>, RepositoryError>;
+
+    /// Anota el inicio de sesión en `last_login_at` y encola el webhook
+    /// `user.login` en la misma transacción. `amr` son los métodos superados.
+    async fn record_login(&self, id: i64, amr: &[String]) -> Result<(), RepositoryError>;
+
+    /// Borra el usuario con sus datos y encola el webhook `user.deleted` en la
+    /// misma transacción. Las organizaciones en las que era el único miembro
+    /// se borran con él; si es el único owner de una con más miembros falla
+    /// con `SoleOwner`.
+    async fn delete(&self, id: i64) -> Result<bool, RepositoryError>;
+}
@@ -0,0 +1,456 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
+
+use super::{normalize_email, RepositoryError, UserRepository};
+use crate::models::otp::OtpFactor;
+use crate::models::profile::{Profile, UpdateProfile};
+use crate::models::user::User;
+use crate::services::avatar::StoredAvatar;
+use crate::services::webhooks::{self, UserEvent};
+
+const PROFILE_COLUMNS: &str = "id, email, name, avatar_url, avatar_thumbnails, avatar_key, locale, timezone, phone_number, \
+     phone_verified_at, metadata, created_at, updated_at, last_login_at";
+
+/// Usuarios de un tenant: cada consulta se limita a su `tenant_id`.
+pub struct PgUserRepository {
+    pool: PgPool,
+    tenant_id: String,
+}
+
+impl PgUserRepository {
+    pub fn new(pool: PgPool, tenant_id: String) -> Self {
+        Self { pool, tenant_id }
+    }
+
+    /// Alta del usuario con el correo normalizado y su primera contraseña en
+    /// el historial; el llamador abre y confirma la transacción.
+    async fn insert_user(
+        &self,
+        conn: &mut PgConnection,
+        email: &str,
+        password_hash: &str,
+        name: &str,
+    ) -> Result<User, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            "INSERT INTO users (tenant_id, email, password, name) VALUES ($1, $2, $3, $4) \
+             RETURNING id, email, password, name",
+        )
+        .bind(&self.tenant_id)
+        .bind(normalize_email(email))
+        .bind(password_hash)
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        sqlx::query("INSERT INTO password_history (user_id, password_hash) VALUES ($1, $2)")
+            .bind(user.id)
+            .bind(password_hash)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(user)
+    }
+
+    /// `false` si el usuario ya tenía una identidad de ese proveedor.
+    async fn insert_identity(
+        &self,
+        conn: &mut PgConnection,
+        id: i64,
+        provider: &str,
+        subject: &str,
+        email: &str,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "INSERT INTO user_identities (tenant_id, user_id, provider, subject, email) \
+             SELECT tenant_id, id, $3, $4, $5 FROM users WHERE id = $1 AND tenant_id = $2 \
+             ON CONFLICT (user_id, provider) DO NOTHING",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(provider)
+        .bind(subject)
+        .bind(email)
+        .execute(&mut *conn)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgUserRepository {
+    async fn find_by_id(&self, id: i64) -> Result<Option<User>, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            "SELECT id, email, password, name FROM users WHERE id = $1 AND tenant_id = $2",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(user)
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            "SELECT id, email, password, name FROM users WHERE email = $1 AND tenant_id = $2",
+        )
+        .bind(normalize_email(email))
+        .bind(&self.tenant_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(user)
+    }
+
+    /// El evento `user.registered` se encola en la misma transacción que el usuario.
+    async fn create(
+        &self,
+        email: &str,
+        password_hash: &str,
+        name: &str,
+    ) -> Result<User, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = self.insert_user(&mut tx, email, password_hash, name).await?;
+
+        let event = UserEvent::Registered {
+            user_id: user.id,
+            email: user.email.clone(),
+            name: user.name.clone(),
+            provider: None,
+            invited: false,
+        };
+        webhooks::enqueue(&mut *tx, &self.tenant_id, &event).await?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    async fn create_with_identity(
+        &self,
+        email: &str,
+        password_hash: &str,
+        name: &str,
+        provider: &str,
+        subject: &str,
+    ) -> Result<User, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = self.insert_user(&mut tx, email, password_hash, name).await?;
+        self.insert_identity(&mut tx, user.id, provider, subject, &user.email).await?;
+
+        let event = UserEvent::Registered {
+            user_id: user.id,
+            email: user.email.clone(),
+            name: user.name.clone(),
+            provider: Some(provider.to_string()),
+            invited: false,
+        };
+        webhooks::enqueue(&mut *tx, &self.tenant_id, &event).await?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    async fn find_by_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<User>, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            "SELECT u.id, u.email, u.password, u.name FROM users u \
+             JOIN user_identities i ON i.user_id = u.id \
+             WHERE i.tenant_id = $1 AND i.provider = $2 AND i.subject = $3",
+        )
+        .bind(&self.tenant_id)
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(user)
+    }
+
+    async fn link_identity(
+        &self,
+        id: i64,
+        provider: &str,
+        subject: &str,
+        email: &str,
+    ) -> Result<bool, RepositoryError> {
+        let mut conn = self.pool.acquire().await?;
+        self.insert_identity(&mut conn, id, provider, subject, email).await
+    }
+
+    async fn update_password(&self, id: i64, password_hash: &str) -> Result<bool, RepositoryError> {
+        let result = sqlx::query("UPDATE users SET password = $3 WHERE id = $1 AND tenant_id = $2")
+            .bind(id)
+            .bind(&self.tenant_id)
+            .bind(password_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn set_password(&self, id: i64, password_hash: &str) -> Result<bool, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            "UPDATE users SET password = $3, password_changed_at = now() \
+             WHERE id = $1 AND tenant_id = $2",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(password_hash)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query("INSERT INTO password_history (user_id, password_hash) VALUES ($1, $2)")
+            .bind(id)
+            .bind(password_hash)
+            .execute(&mut *tx)
+            .await?;
+
+        webhooks::enqueue(&mut *tx, &self.tenant_id, &UserEvent::PasswordChanged { user_id: id }).await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn recent_password_hashes(
+        &self,
+        id: i64,
+        limit: usize,
+    ) -> Result<Vec<String>, RepositoryError> {
+        let hashes = sqlx::query_scalar::<_, String>(
+            "SELECT h.password_hash FROM password_history h JOIN users u ON u.id = h.user_id \
+             WHERE h.user_id = $1 AND u.tenant_id = $2 \
+             ORDER BY h.created_at DESC, h.id DESC LIMIT $3",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(hashes)
+    }
+
+    async fn password_changed_at(&self, id: i64) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        let changed_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+            "SELECT password_changed_at FROM users WHERE id = $1 AND tenant_id = $2",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(changed_at)
+    }
+
+    async fn otp_factor(&self, id: i64) -> Result<Option<OtpFactor>, RepositoryError> {
+        let row = sqlx::query_as::<_, (String, String)>(
+            "SELECT f.channel, f.destination FROM otp_factors f JOIN users u ON u.id = f.user_id \
+             WHERE f.user_id = $1 AND u.tenant_id = $2",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.and_then(|(channel, destination)| match channel.parse() {
+            Ok(channel) => Some(OtpFactor { channel, destination }),
+            Err(e) => {
+                log::error!("Ignoring OTP factor of user {}: {}", id, e);
+                None
+            }
+        }))
+    }
+
+    async fn set_otp_factor(&self, id: i64, factor: &OtpFactor) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO otp_factors (user_id, channel, destination) \
+             SELECT id, $3, $4 FROM users WHERE id = $1 AND tenant_id = $2 \
+             ON CONFLICT (user_id) DO UPDATE \
+             SET channel = EXCLUDED.channel, destination = EXCLUDED.destination, created_at = now()",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(factor.channel.as_str())
+        .bind(&factor.destination)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_otp_factor(&self, id: i64) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "DELETE FROM otp_factors f USING users u \
+             WHERE f.user_id = $1 AND u.id = f.user_id AND u.tenant_id = $2",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn profile(&self, id: i64) -> Result<Option<Profile>, RepositoryError> {
+        let profile = sqlx::query_as::<_, Profile>(&format!(
+            "SELECT {} FROM users WHERE id = $1 AND tenant_id = $2",
+            PROFILE_COLUMNS
+        ))
+        .bind(id)
+        .bind(&self.tenant_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(profile)
+    }
+
+    async fn update_profile(
+        &self,
+        id: i64,
+        update: &UpdateProfile,
+    ) -> Result<Option<Profile>, RepositoryError> {
+        // Cada opcional va como (presente, valor) para poder borrarlo con null.
+        // En el SET todas las expresiones ven la fila anterior.
+        let profile = sqlx::query_as::<_, Profile>(&format!(
+            "UPDATE users SET \
+               name = COALESCE($3, name), \
+               avatar_url = CASE WHEN $4 THEN $5 ELSE avatar_url END, \
+               avatar_thumbnails = CASE WHEN $4 THEN NULL ELSE avatar_thumbnails END, \
+               avatar_key = CASE WHEN $4 THEN NULL ELSE avatar_key END, \
+               locale = CASE WHEN $6 THEN $7 ELSE locale END, \
+               timezone = CASE WHEN $8 THEN $9 ELSE timezone END, \
+               phone_number = CASE WHEN $10 THEN $11 ELSE phone_number END, \
+               phone_verified_at = CASE WHEN $10 AND $11 IS DISTINCT FROM phone_number \
+                                        THEN NULL ELSE phone_verified_at END, \
+               metadata = COALESCE($12, metadata), \
+               updated_at = now() \
+             WHERE id = $1 AND tenant_id = $2 RETURNING {}",
+            PROFILE_COLUMNS
+        ))
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(&update.name)
+        .bind(update.avatar_url.is_some())
+        .bind(update.avatar_url.clone().flatten())
+        .bind(update.locale.is_some())
+        .bind(update.locale.clone().flatten())
+        .bind(update.timezone.is_some())
+        .bind(update.timezone.clone().flatten())
+        .bind(update.phone_number.is_some())
+        .bind(update.phone_number.clone().flatten())
+        .bind(&update.metadata)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(profile)
+    }
+
+    async fn mark_phone_verified(&self, id: i64, phone: &str) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "UPDATE users SET phone_verified_at = now(), updated_at = now() \
+             WHERE id = $1 AND tenant_id = $2 AND phone_number = $3",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn set_avatar(
+        &self,
+        id: i64,
+        avatar: Option<&StoredAvatar>,
+    ) -> Result<Option<Profile>, RepositoryError> {
+        let profile = sqlx::query_as::<_, Profile>(&format!(
+            "UPDATE users SET avatar_url = $3, avatar_thumbnails = $4, avatar_key = $5, \
+             updated_at = now() WHERE id = $1 AND tenant_id = $2 RETURNING {}",
+            PROFILE_COLUMNS
+        ))
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(avatar.map(|a| &a.url))
+        .bind(avatar.map(|a| &a.thumbnails))
+        .bind(avatar.map(|a| &a.key))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(profile)
+    }
+
+    async fn record_login(&self, id: i64, amr: &[String]) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE users SET last_login_at = now() WHERE id = $1 AND tenant_id = $2")
+            .bind(id)
+            .bind(&self.tenant_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let event = UserEvent::Login {
+            user_id: id,
+            amr: amr.to_vec(),
+        };
+        webhooks::enqueue(&mut *tx, &self.tenant_id, &event).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: i64) -> Result<bool, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let email = sqlx::query_scalar::<_, String>(
+            "SELECT email FROM users WHERE id = $1 AND tenant_id = $2 FOR UPDATE",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(email) = email else {
+            return Ok(false);
+        };
+
+        // Se bloquean las membresías de sus organizaciones para que nadie cambie los roles mientras tanto
+        sqlx::query(
+            "SELECT 1 FROM memberships WHERE org_id IN (SELECT org_id FROM memberships WHERE user_id = $1) \
+             FOR UPDATE",
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        let orphaned = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM memberships m WHERE m.user_id = $1 AND m.role = 'owner' \
+             AND EXISTS (SELECT 1 FROM memberships o WHERE o.org_id = m.org_id AND o.user_id <> $1) \
+             AND NOT EXISTS (SELECT 1 FROM memberships o WHERE o.org_id = m.org_id \
+             AND o.user_id <> $1 AND o.role = 'owner'))",
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if orphaned {
+            return Err(RepositoryError::SoleOwner);
+        }
+
+        sqlx::query(
+            "DELETE FROM organizations org WHERE org.id IN (SELECT org_id FROM memberships WHERE user_id = $1) \
+             AND NOT EXISTS (SELECT 1 FROM memberships m WHERE m.org_id = org.id AND m.user_id <> $1)",
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        // El resto de tablas cuelgan de `users` con ON DELETE CASCADE
+        sqlx::query("DELETE FROM users WHERE id = $1 AND tenant_id = $2")
+            .bind(id)
+            .bind(&self.tenant_id)
+            .execute(&mut *tx)
+            .await?;
+
+        webhooks::enqueue(&mut *tx, &self.tenant_id, &UserEvent::Deleted { user_id: id, email }).await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+}
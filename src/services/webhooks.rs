@@ -0,0 +1,467 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::{PgExecutor, PgPool};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::webhooks::WebhookSettings;
+
+pub const USER_REGISTERED: &str = "user.registered";
+pub const USER_LOGIN: &str = "user.login";
+pub const USER_PASSWORD_CHANGED: &str = "user.password_changed";
+pub const USER_DELETED: &str = "user.deleted";
+
+/// Eventos a los que se puede suscribir un endpoint.
+pub const SUPPORTED_EVENTS: &[&str] = &[USER_REGISTERED, USER_LOGIN, USER_PASSWORD_CHANGED, USER_DELETED];
+
+/// Evento de usuario publicado en los webhooks.
+///
+/// Es el contrato común de los repositorios de usuarios: `PgUserRepository`
+/// lo guarda en el outbox dentro de la transacción del cambio y el doble en
+/// memoria lo anota para que las pruebas comprueben lo mismo.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserEvent {
+    Registered {
+        user_id: i64,
+        email: String,
+        name: String,
+        /// Proveedor externo con el que se dio de alta, si lo hubo.
+        provider: Option<String>,
+        /// Alta al aceptar una invitación.
+        invited: bool,
+    },
+    Login {
+        user_id: i64,
+        amr: Vec<String>,
+    },
+    PasswordChanged {
+        user_id: i64,
+    },
+    Deleted {
+        user_id: i64,
+        email: String,
+    },
+}
+
+impl UserEvent {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            UserEvent::Registered { .. } => USER_REGISTERED,
+            UserEvent::Login { .. } => USER_LOGIN,
+            UserEvent::PasswordChanged { .. } => USER_PASSWORD_CHANGED,
+            UserEvent::Deleted { .. } => USER_DELETED,
+        }
+    }
+
+    /// Campo `data` del payload.
+    pub fn data(&self) -> Value {
+        match self {
+            UserEvent::Registered { user_id, email, name, provider, invited } => {
+                let mut data = json!({
+                    "user_id": user_id,
+                    "email": email,
+                    "name": name
+                });
+                if let Some(provider) = provider {
+                    data["provider"] = json!(provider);
+                }
+                if *invited {
+                    data["invited"] = json!(true);
+                }
+                data
+            }
+            // El último método es el que completó el login (p. ej. `otp` tras la contraseña)
+            UserEvent::Login { user_id, amr } => json!({
+                "user_id": user_id,
+                "method": amr.last(),
+                "amr": amr
+            }),
+            UserEvent::PasswordChanged { user_id } => json!({
+                "user_id": user_id
+            }),
+            UserEvent::Deleted { user_id, email } => json!({
+                "user_id": user_id,
+                "email": email
+            }),
+        }
+    }
+
+    /// Cuerpo completo que se entrega a los endpoints.
+    pub fn payload(&self) -> Value {
+        json!({
+            "id": Uuid::new_v4(),
+            "type": self.event_type(),
+            "created_at": Utc::now(),
+            "data": self.data()
+        })
+    }
+}
+
+/// Guarda el evento en el outbox para cada endpoint activo del tenant suscrito.
+///
+/// Acepta una transacción para que el evento se confirme junto con el cambio que lo produce.
+pub async fn enqueue<'e, E: PgExecutor<'e>>(
+    executor: E,
+    tenant_id: &str,
+    event: &UserEvent,
+) -> Result<u64, sqlx::Error> {
+    let event_type = event.event_type();
+    let payload = event.payload();
+
+    let result = sqlx::query(
+        "INSERT INTO webhook_outbox (endpoint_id, event_type, payload) \
+         SELECT id, $1, $2 FROM webhook_endpoints \
+         WHERE active AND $1 = ANY(events) AND tenant_id = $3",
+    )
+    .bind(event_type)
+    .bind(payload)
+    .bind(tenant_id)
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Firma `"{timestamp}.{body}"` con HMAC-SHA256 y la devuelve en hexadecimal.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Lanza el bucle que entrega los eventos pendientes del outbox a los
+/// endpoints del tenant, con su configuración.
+pub fn spawn_dispatcher(pool: PgPool, http: reqwest::Client, tenant_id: String, settings: WebhookSettings) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(settings.poll_interval);
+        loop {
+            interval.tick().await;
+            match dispatch_due(&pool, &http, &tenant_id, &settings).await {
+                Ok(0) => {}
+                Ok(count) => log::debug!("Dispatched {} webhook(s) for tenant {}", count, tenant_id),
+                Err(e) => log::error!("Webhook dispatcher error for tenant {}: {}", tenant_id, e),
+            }
+        }
+    });
+}
+
+#[derive(sqlx::FromRow)]
+struct DueDelivery {
+    id: Uuid,
+    endpoint_id: Uuid,
+    event_type: String,
+    payload: Value,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+/// Reserva un lote de entregas vencidas y las envía.
+///
+/// La reserva adelanta `next_attempt_at`, así que si el proceso cae a mitad
+/// del envío la entrega se reintenta cuando vence la reserva.
+async fn dispatch_due(
+    pool: &PgPool,
+    http: &reqwest::Client,
+    tenant_id: &str,
+    settings: &WebhookSettings,
+) -> Result<usize, sqlx::Error> {
+    let lease = settings.request_timeout.as_secs_f64() * 2.0 + 60.0;
+
+    let due = sqlx::query_as::<_, DueDelivery>(
+        "WITH due AS ( \
+             SELECT o.id FROM webhook_outbox o \
+             JOIN webhook_endpoints e ON e.id = o.endpoint_id AND e.tenant_id = $3 \
+             WHERE o.delivered_at IS NULL AND o.failed_at IS NULL AND o.next_attempt_at <= now() \
+             ORDER BY o.next_attempt_at LIMIT $1 FOR UPDATE OF o SKIP LOCKED \
+         ) \
+         UPDATE webhook_outbox o SET next_attempt_at = now() + make_interval(secs => $2) \
+         FROM due, webhook_endpoints e \
+         WHERE o.id = due.id AND e.id = o.endpoint_id \
+         RETURNING o.id, o.endpoint_id, o.event_type, o.payload, o.attempts, e.url, e.secret",
+    )
+    .bind(settings.batch_size)
+    .bind(lease)
+    .bind(tenant_id)
+    .fetch_all(pool)
+    .await?;
+
+    let count = due.len();
+    for delivery in due {
+        deliver(pool, http, settings, delivery).await?;
+    }
+
+    Ok(count)
+}
+
+/// Resultado de un intento de entrega.
+#[derive(Debug)]
+struct Attempt {
+    status_code: Option<i32>,
+    error: Option<String>,
+    duration_ms: i32,
+}
+
+/// Qué hacer con la entrega tras un intento.
+#[derive(Debug, PartialEq)]
+enum NextStep {
+    Delivered,
+    Retry(Duration),
+    Failed,
+}
+
+fn next_step(settings: &WebhookSettings, attempt: i32, outcome: &Attempt) -> NextStep {
+    match outcome.error {
+        None => NextStep::Delivered,
+        Some(_) if attempt >= settings.max_attempts => NextStep::Failed,
+        Some(_) => NextStep::Retry(backoff(settings, attempt)),
+    }
+}
+
+/// Envía el evento firmado al endpoint. Cualquier respuesta que no sea 2xx cuenta como fallo.
+async fn send(
+    http: &reqwest::Client,
+    settings: &WebhookSettings,
+    delivery: &DueDelivery,
+) -> Attempt {
+    let body = delivery.payload.to_string();
+    let timestamp = Utc::now().timestamp();
+    let signature = sign(&delivery.secret, timestamp, &body);
+
+    let started = Instant::now();
+    let result = http
+        .post(&delivery.url)
+        .timeout(settings.request_timeout)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Id", delivery.id.to_string())
+        .header("X-Webhook-Event", &delivery.event_type)
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", format!("sha256={}", signature))
+        .body(body)
+        .send()
+        .await;
+    let duration_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+    let (status_code, error) = match &result {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16() as i32), None),
+        Ok(response) => (
+            Some(response.status().as_u16() as i32),
+            Some(format!("HTTP {}", response.status())),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    Attempt {
+        status_code,
+        error,
+        duration_ms,
+    }
+}
+
+async fn deliver(
+    pool: &PgPool,
+    http: &reqwest::Client,
+    settings: &WebhookSettings,
+    delivery: DueDelivery,
+) -> Result<(), sqlx::Error> {
+    let attempt = delivery.attempts + 1;
+    let outcome = send(http, settings, &delivery).await;
+
+    sqlx::query(
+        "INSERT INTO webhook_deliveries \
+         (outbox_id, endpoint_id, event_type, attempt, status_code, error, duration_ms) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(delivery.id)
+    .bind(delivery.endpoint_id)
+    .bind(&delivery.event_type)
+    .bind(attempt)
+    .bind(outcome.status_code)
+    .bind(&outcome.error)
+    .bind(outcome.duration_ms)
+    .execute(pool)
+    .await?;
+
+    let error = outcome.error.as_deref().unwrap_or_default();
+    match next_step(settings, attempt, &outcome) {
+        NextStep::Delivered => {
+            sqlx::query("UPDATE webhook_outbox SET attempts = $2, delivered_at = now() WHERE id = $1")
+                .bind(delivery.id)
+                .bind(attempt)
+                .execute(pool)
+                .await?;
+        }
+        NextStep::Failed => {
+            log::error!(
+                "Webhook {} to {} failed permanently after {} attempts: {}",
+                delivery.id,
+                delivery.url,
+                attempt,
+                error
+            );
+            sqlx::query("UPDATE webhook_outbox SET attempts = $2, failed_at = now() WHERE id = $1")
+                .bind(delivery.id)
+                .bind(attempt)
+                .execute(pool)
+                .await?;
+        }
+        NextStep::Retry(delay) => {
+            log::warn!(
+                "Webhook {} to {} failed (attempt {}), retrying in {}s: {}",
+                delivery.id,
+                delivery.url,
+                attempt,
+                delay.as_secs(),
+                error
+            );
+            sqlx::query(
+                "UPDATE webhook_outbox SET attempts = $2, \
+                 next_attempt_at = now() + make_interval(secs => $3) WHERE id = $1",
+            )
+            .bind(delivery.id)
+            .bind(attempt)
+            .bind(delay.as_secs_f64())
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Backoff exponencial: `base * 2^(intento - 1)`, con tope en `max_backoff`.
+fn backoff(settings: &WebhookSettings, attempt: i32) -> Duration {
+    let exponent = (attempt - 1).clamp(0, 20) as u32;
+    settings
+        .base_backoff
+        .saturating_mul(2u32.saturating_pow(exponent))
+        .min(settings.max_backoff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    const SECRET: &str = "whsec_test";
+
+    #[derive(Default)]
+    struct Receiver {
+        calls: AtomicUsize,
+        /// Cabeceras de firma y cuerpo de cada petición recibida
+        received: Mutex<Vec<(String, String, String)>>,
+    }
+
+    fn header(req: &HttpRequest, name: &str) -> String {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Endpoint que falla en la primera entrega y acepta las siguientes.
+    async fn receive(req: HttpRequest, body: String, receiver: web::Data<Receiver>) -> HttpResponse {
+        receiver.received.lock().unwrap().push((
+            header(&req, "X-Webhook-Timestamp"),
+            header(&req, "X-Webhook-Signature"),
+            body,
+        ));
+        match receiver.calls.fetch_add(1, Ordering::SeqCst) {
+            0 => HttpResponse::InternalServerError().finish(),
+            _ => HttpResponse::Ok().finish(),
+        }
+    }
+
+    fn settings(max_attempts: i32) -> WebhookSettings {
+        WebhookSettings {
+            poll_interval: Duration::from_secs(1),
+            batch_size: 10,
+            max_attempts,
+            base_backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(3600),
+            request_timeout: Duration::from_secs(5),
+        }
+    }
+
+    fn spawn_receiver() -> (String, Arc<Receiver>) {
+        let receiver = Arc::new(Receiver::default());
+        let data = web::Data::from(receiver.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .route("/hook", web::post().to(receive))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_web::rt::spawn(server);
+        (url, receiver)
+    }
+
+    fn delivery(url: &str, attempts: i32) -> DueDelivery {
+        DueDelivery {
+            id: Uuid::new_v4(),
+            endpoint_id: Uuid::new_v4(),
+            event_type: USER_LOGIN.to_string(),
+            payload: json!({"type": USER_LOGIN, "data": {"user_id": 1}}),
+            attempts,
+            url: url.to_string(),
+            secret: SECRET.to_string(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn signs_deliveries_and_retries_failures_with_backoff() {
+        let (url, receiver) = spawn_receiver();
+        let http = reqwest::Client::new();
+        let settings = settings(8);
+
+        let first = send(&http, &settings, &delivery(&url, 0)).await;
+        assert_eq!(first.status_code, Some(500));
+        assert_eq!(next_step(&settings, 1, &first), NextStep::Retry(Duration::from_secs(30)));
+
+        let second = send(&http, &settings, &delivery(&url, 1)).await;
+        assert_eq!(second.status_code, Some(200));
+        assert_eq!(next_step(&settings, 2, &second), NextStep::Delivered);
+
+        let received = receiver.received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        for (timestamp, signature, body) in received.iter() {
+            let timestamp: i64 = timestamp.parse().unwrap();
+            assert_eq!(signature, &format!("sha256={}", sign(SECRET, timestamp, body)));
+            assert_ne!(signature, &format!("sha256={}", sign("other", timestamp, body)));
+        }
+    }
+
+    #[actix_web::test]
+    async fn unreachable_endpoints_fail_after_max_attempts() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+        let settings = settings(3);
+
+        let outcome = send(&reqwest::Client::new(), &settings, &delivery(&url, 2)).await;
+        assert_eq!(outcome.status_code, None);
+        assert!(outcome.error.is_some());
+        assert_eq!(next_step(&settings, 2, &outcome), NextStep::Retry(Duration::from_secs(60)));
+        assert_eq!(next_step(&settings, 3, &outcome), NextStep::Failed);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let settings = settings(30);
+        assert_eq!(backoff(&settings, 1), Duration::from_secs(30));
+        assert_eq!(backoff(&settings, 4), Duration::from_secs(240));
+        assert_eq!(backoff(&settings, 20), Duration::from_secs(3600));
+    }
+}
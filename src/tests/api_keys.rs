@@ -0,0 +1,115 @@
+//! API keys: alta, uso con sus scopes y revocación.
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+
+use super::{bearer, blob_store, init_with, login, pg_tenant_app, register, test_database, EMAIL};
+
+#[actix_web::test]
+async fn api_keys_authenticate_within_their_scopes_until_revoked() {
+    let Some(pool) = test_database().await else { return };
+    let tenant_app = pg_tenant_app(&pool);
+    let app = init_with(&tenant_app, pool.clone(), blob_store()).await;
+
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+
+    let request = test::TestRequest::post()
+        .uri("/api-keys")
+        .insert_header(bearer(&token))
+        .set_json(json!({"name": "ci", "scopes": ["profile:read"], "expires_in_days": 30}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 201);
+    let created: Value = test::read_body_json(response).await;
+    let key = created["key"].as_str().unwrap().to_string();
+    assert!(key.starts_with(&format!("rak_{}_", created["prefix"].as_str().unwrap())));
+
+    // Las dos cabeceras valen
+    let request = test::TestRequest::get()
+        .uri("/profile")
+        .insert_header(("X-API-Key", key.clone()))
+        .to_request();
+    let profile: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(profile["email"], EMAIL);
+    let request = test::TestRequest::get()
+        .uri("/profile")
+        .insert_header(("Authorization", format!("ApiKey {}", key)))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 200);
+
+    // Fuera de sus scopes no, ni para crear otras claves
+    let request = test::TestRequest::get()
+        .uri("/api-keys")
+        .insert_header(("X-API-Key", key.clone()))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 403);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["required_scope"], "api_keys:read");
+
+    // La lista no devuelve la clave completa
+    let request = test::TestRequest::get()
+        .uri("/api-keys")
+        .insert_header(bearer(&token))
+        .to_request();
+    let keys: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(keys.as_array().unwrap().len(), 1);
+    assert!(keys[0].get("key").is_none());
+
+    let request = test::TestRequest::delete()
+        .uri(&format!("/api-keys/{}", created["id"].as_str().unwrap()))
+        .insert_header(bearer(&token))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 204);
+
+    let request = test::TestRequest::get()
+        .uri("/profile")
+        .insert_header(("X-API-Key", key))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn invalid_api_keys_and_scopes_are_rejected() {
+    let Some(pool) = test_database().await else { return };
+    let tenant_app = pg_tenant_app(&pool);
+    let app = init_with(&tenant_app, pool.clone(), blob_store()).await;
+
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+
+    let request = test::TestRequest::post()
+        .uri("/api-keys")
+        .insert_header(bearer(&token))
+        .set_json(json!({"name": "ci", "scopes": ["admin"]}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["error"], "Unknown scope: admin");
+
+    let request = test::TestRequest::get()
+        .uri("/profile")
+        .insert_header(("X-API-Key", "not-a-key"))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+
+    // Una clave caducada tampoco
+    let request = test::TestRequest::post()
+        .uri("/api-keys")
+        .insert_header(bearer(&token))
+        .set_json(json!({"name": "old", "scopes": ["profile:read"], "expires_in_days": 1}))
+        .to_request();
+    let created: Value = test::call_and_read_body_json(&app, request).await;
+    sqlx::query("UPDATE api_keys SET expires_at = now() - interval '1 second'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let request = test::TestRequest::get()
+        .uri("/profile")
+        .insert_header(("X-API-Key", created["key"].as_str().unwrap()))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,102 @@
+//! Subida de avatares: validación, miniaturas y almacenamiento en el `BlobStore`.
+
+use actix_web::http::StatusCode;
+use actix_web::{test, web};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use serde_json::Value;
+use std::io::Cursor;
+
+use super::{bearer, blob_store, closed_pool, init_with, login, register, tenant_app};
+use crate::config::profile::ProfileSettings;
+
+const BOUNDARY: &str = "avatar-test-boundary";
+
+/// Petición `PUT /profile/avatar` con un único campo `avatar`.
+fn upload(token: &Value, content_type: &str, data: &[u8]) -> actix_http::Request {
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"avatar\"\r\n\
+         Content-Type: {}\r\n\r\n",
+        BOUNDARY, content_type
+    )
+    .into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+    test::TestRequest::put()
+        .uri("/profile/avatar")
+        .insert_header(bearer(token))
+        .insert_header(("Content-Type", format!("multipart/form-data; boundary={}", BOUNDARY)))
+        .set_payload(body)
+        .to_request()
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut encoded = Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, image::Rgb([200, 80, 40])))
+        .write_to(&mut encoded, ImageFormat::Png)
+        .unwrap();
+    encoded.into_inner()
+}
+
+#[actix_web::test]
+async fn uploaded_avatars_are_stored_as_square_thumbnails() {
+    let tenant_app = tenant_app();
+    let blobs = blob_store();
+    let app = init_with(&tenant_app, closed_pool(), blobs.clone()).await;
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+
+    let response = test::call_service(&app, upload(&token, "image/png", &png(300, 200))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let profile: Value = test::read_body_json(response).await;
+    let thumbnails = profile["avatar_thumbnails"].as_object().unwrap();
+    assert_eq!(thumbnails.keys().collect::<Vec<_>>(), ["128", "256", "64"]);
+    assert_eq!(profile["avatar_url"], thumbnails["256"]);
+
+    for (size, url) in thumbnails {
+        let key = url.as_str().unwrap().strip_prefix("/media/").unwrap();
+        let blob = blobs.get(key).await.unwrap().unwrap();
+        assert_eq!(blob.content_type, "image/webp");
+        let thumbnail = image::load_from_memory_with_format(&blob.data, ImageFormat::WebP).unwrap();
+        let size: u32 = size.parse().unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (size, size));
+    }
+
+    // La siguiente subida sustituye y borra la anterior
+    let old = thumbnails["64"].as_str().unwrap().strip_prefix("/media/").unwrap().to_string();
+    let response = test::call_service(&app, upload(&token, "image/png", &png(64, 64))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(blobs.get(&old).await.unwrap().is_none());
+}
+
+#[actix_web::test]
+async fn unsupported_invalid_or_oversized_avatars_are_rejected() {
+    let mut tenant_app = tenant_app();
+    tenant_app.profile = web::Data::new(ProfileSettings {
+        avatar_max_bytes: 4096,
+        ..tenant_app.profile.get_ref().clone()
+    });
+    let app = init_with(&tenant_app, closed_pool(), blob_store()).await;
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+
+    // El tipo declarado debe ser una imagen admitida y coincidir con el contenido
+    for (content_type, data) in [("image/gif", b"GIF89a".to_vec()), ("image/jpeg", png(32, 32))] {
+        let response = test::call_service(&app, upload(&token, content_type, &data)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "Avatar must be a PNG, JPEG or WebP image");
+    }
+
+    let mut truncated = png(32, 32);
+    truncated.truncate(40);
+    let response = test::call_service(&app, upload(&token, "image/png", &truncated)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = test::call_service(&app, upload(&token, "image/png", &vec![0; 5000])).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let request = test::TestRequest::get().uri("/profile").insert_header(bearer(&token)).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, request).await;
+    assert!(profile["avatar_url"].is_null());
+}
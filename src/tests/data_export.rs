@@ -0,0 +1,212 @@
+//! Exportación de datos: endpoints, contenido del ZIP y cola de exportaciones.
+
+use actix_web::test;
+use chrono::Duration;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+use super::{bearer, blob_store, init_with, login, pg_tenant_app, register, test_database};
+use crate::services::data_export;
+
+#[actix_web::test]
+async fn exports_are_generated_by_the_queue_and_downloaded() {
+    let Some(pool) = test_database().await else { return };
+    let tenant_app = pg_tenant_app(&pool);
+    let blobs = blob_store();
+    let app = init_with(&tenant_app, pool.clone(), blobs.clone()).await;
+
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+
+    let request = test::TestRequest::post()
+        .uri("/profile/export")
+        .insert_header(bearer(&token))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 202);
+    let export: Value = test::read_body_json(response).await;
+    assert_eq!(export["status"], "pending");
+    let id = export["id"].as_str().unwrap().to_string();
+
+    // Solo una en curso por usuario
+    let request = test::TestRequest::post()
+        .uri("/profile/export")
+        .insert_header(bearer(&token))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 409);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["export_id"], id.as_str());
+
+    // Sin generar todavía no se puede descargar
+    let request = test::TestRequest::get()
+        .uri(&format!("/profile/export/{}/download", id))
+        .insert_header(bearer(&token))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 409);
+
+    let processed =
+        data_export::process_due(&pool, tenant_app.sessions.get_ref(), blobs.as_ref(), "default", 3)
+            .await
+            .unwrap();
+    assert_eq!(processed, 1);
+
+    let request = test::TestRequest::get()
+        .uri(&format!("/profile/export/{}", id))
+        .insert_header(bearer(&token))
+        .to_request();
+    let export: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(export["status"], "ready");
+    assert_eq!(export["download_url"], format!("/profile/export/{}/download", id));
+
+    let request = test::TestRequest::get()
+        .uri(&format!("/profile/export/{}/download", id))
+        .insert_header(bearer(&token))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 200);
+    let archive = test::read_body(response).await;
+
+    let mut zip = ZipArchive::new(Cursor::new(archive.to_vec())).unwrap();
+    let names = zip.file_names().collect::<BTreeSet<_>>();
+    let expected = BTreeSet::from([
+        "export.json",
+        "user.json",
+        "sessions.json",
+        "auth_events.json",
+        "identities.json",
+        "api_keys.json",
+        "mfa.json",
+        "organizations.json",
+    ]);
+    assert_eq!(names, expected);
+
+    let read_json = |zip: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str| -> Value {
+        let mut contents = String::new();
+        zip.by_name(name).unwrap().read_to_string(&mut contents).unwrap();
+        serde_json::from_str(&contents).unwrap()
+    };
+    let user = read_json(&mut zip, "user.json");
+    assert_eq!(user["email"], super::EMAIL);
+    assert!(user.get("password").is_none());
+    let sessions = read_json(&mut zip, "sessions.json");
+    assert_eq!(sessions.as_array().unwrap().len(), 1);
+    assert!(sessions[0].get("token").is_none());
+    let manifest = read_json(&mut zip, "export.json");
+    assert_eq!(manifest["files"].as_array().unwrap().len(), expected.len() - 1);
+}
+
+#[actix_web::test]
+async fn unknown_and_foreign_exports_are_not_found() {
+    let Some(pool) = test_database().await else { return };
+    let tenant_app = pg_tenant_app(&pool);
+    let app = init_with(&tenant_app, pool.clone(), blob_store()).await;
+
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+
+    let request = test::TestRequest::get()
+        .uri(&format!("/profile/export/{}", uuid::Uuid::new_v4()))
+        .insert_header(bearer(&token))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 404);
+
+    // La de otro usuario tampoco se ve
+    let other: i64 = sqlx::query_scalar(
+        "INSERT INTO users (email, password, name, tenant_id) VALUES ('grace@example.com', 'x', 'Grace', 'default') \
+         RETURNING id",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let foreign = data_export::create(&pool, "default", other, Duration::hours(1))
+        .await
+        .unwrap()
+        .unwrap();
+    let request = test::TestRequest::get()
+        .uri(&format!("/profile/export/{}", foreign.id))
+        .insert_header(bearer(&token))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn orphaned_exports_are_retried_and_then_failed() {
+    let Some(pool) = test_database().await else { return };
+    let tenant_app = pg_tenant_app(&pool);
+    let blobs = blob_store();
+    let user_id: i64 = sqlx::query_scalar(
+        "INSERT INTO users (email, password, name, tenant_id) VALUES ('ada@example.com', 'x', 'Ada', 'default') \
+         RETURNING id",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let status = |id| {
+        let pool = pool.clone();
+        async move {
+            sqlx::query_scalar::<_, String>("SELECT status FROM data_exports WHERE id = $1")
+                .bind(id)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        }
+    };
+
+    // Un proceso la reservó y cayó: la reserva venció y se retoma
+    let export = data_export::create(&pool, "default", user_id, Duration::hours(1))
+        .await
+        .unwrap()
+        .unwrap();
+    sqlx::query("UPDATE data_exports SET attempts = 1, locked_until = now() - interval '1 second' WHERE id = $1")
+        .bind(export.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let processed =
+        data_export::process_due(&pool, tenant_app.sessions.get_ref(), blobs.as_ref(), "default", 3)
+            .await
+            .unwrap();
+    assert_eq!(processed, 1);
+    assert_eq!(status(export.id).await, "ready");
+
+    // Mientras la reserva no vence nadie más la toca
+    let export = data_export::create(&pool, "default", user_id, Duration::hours(1))
+        .await
+        .unwrap()
+        .unwrap();
+    sqlx::query("UPDATE data_exports SET attempts = 3, locked_until = now() + interval '1 minute' WHERE id = $1")
+        .bind(export.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let processed =
+        data_export::process_due(&pool, tenant_app.sessions.get_ref(), blobs.as_ref(), "default", 3)
+            .await
+            .unwrap();
+    assert_eq!(processed, 0);
+    assert_eq!(status(export.id).await, "pending");
+    assert!(data_export::create(&pool, "default", user_id, Duration::hours(1))
+        .await
+        .unwrap()
+        .is_none());
+
+    // Sin intentos restantes queda fallida y se puede pedir otra
+    sqlx::query("UPDATE data_exports SET locked_until = now() - interval '1 second' WHERE id = $1")
+        .bind(export.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let processed =
+        data_export::process_due(&pool, tenant_app.sessions.get_ref(), blobs.as_ref(), "default", 3)
+            .await
+            .unwrap();
+    assert_eq!(processed, 0);
+    assert_eq!(status(export.id).await, "failed");
+    assert!(data_export::create(&pool, "default", user_id, Duration::hours(1))
+        .await
+        .unwrap()
+        .is_some());
+}
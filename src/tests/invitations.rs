@@ -0,0 +1,128 @@
+//! Invitaciones de administradores y cierre del registro abierto.
+
+use actix_web::http::StatusCode;
+use actix_web::{test, web};
+use serde_json::{json, Value};
+
+use super::{bearer, blob_store, init_with, login, pg_tenant_app, register, test_database, EMAIL, PASSWORD};
+use crate::config::registration::RegistrationSettings;
+use crate::services::crypto::sha256_hex;
+
+const GRACE: &str = "grace@example.com";
+
+/// Marca como administrador al usuario de pruebas.
+async fn make_admin(pool: &sqlx::PgPool) {
+    sqlx::query("UPDATE users SET is_admin = true WHERE email = $1")
+        .bind(EMAIL)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn invited_users_choose_their_password_and_then_log_in() {
+    let Some(pool) = test_database().await else { return };
+    let mut tenant_app = pg_tenant_app(&pool);
+    register(&init_with(&tenant_app, pool.clone(), blob_store()).await).await;
+
+    // Con el registro abierto cerrado las invitaciones siguen funcionando
+    let registration = RegistrationSettings {
+        self_registration: false,
+        ..tenant_app.registration.get_ref().clone()
+    };
+    tenant_app.registration = web::Data::new(registration);
+    let app = init_with(&tenant_app, pool.clone(), blob_store()).await;
+    make_admin(&pool).await;
+    let token = login(&app).await["token"].clone();
+
+    let request = test::TestRequest::post()
+        .uri("/admin/invitations")
+        .insert_header(bearer(&token))
+        .set_json(json!({"email": "Grace@Example.com"}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let invitation: Value = test::read_body_json(response).await;
+    assert_eq!(invitation["email"], GRACE);
+
+    // Hasta aceptar no hay contraseña con la que entrar
+    let grace_login = || {
+        test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(json!({"email": GRACE, "password": PASSWORD}))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, grace_login()).await.status(), StatusCode::UNAUTHORIZED);
+
+    // El token solo viaja por correo: se sustituye por uno conocido
+    sqlx::query("UPDATE user_invitations SET token_hash = $1 WHERE id = $2")
+        .bind(sha256_hex("invitation-token"))
+        .bind(invitation["id"].as_i64().unwrap())
+        .execute(&pool)
+        .await
+        .unwrap();
+    let accept = |password: &str| {
+        test::TestRequest::post()
+            .uri("/auth/accept-invite")
+            .set_json(json!({"token": "invitation-token", "name": "Grace Hopper", "password": password}))
+            .to_request()
+    };
+
+    // Las reglas de `NewUser` también valen aquí
+    let response = test::call_service(&app, accept("password")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["errors"]["password"][0]["code"], "password_too_weak");
+
+    let response = test::call_service(&app, accept(PASSWORD)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let user: Value = test::read_body_json(response).await;
+    assert_eq!(user["name"], "Grace Hopper");
+    assert_eq!(test::call_service(&app, grace_login()).await.status(), StatusCode::OK);
+
+    // Un solo uso
+    assert_eq!(test::call_service(&app, accept(PASSWORD)).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn invitations_need_an_admin_and_closed_registration_is_refused() {
+    let Some(pool) = test_database().await else { return };
+    let mut tenant_app = pg_tenant_app(&pool);
+    let app = init_with(&tenant_app, pool.clone(), blob_store()).await;
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+
+    let invite = |token: &Value, email: &str| {
+        test::TestRequest::post()
+            .uri("/admin/invitations")
+            .insert_header(bearer(token))
+            .set_json(json!({"email": email}))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, invite(&token, GRACE)).await.status(), StatusCode::FORBIDDEN);
+
+    make_admin(&pool).await;
+    assert_eq!(test::call_service(&app, invite(&token, EMAIL)).await.status(), StatusCode::CONFLICT);
+    assert_eq!(test::call_service(&app, invite(&token, "not-an-email")).await.status(), StatusCode::BAD_REQUEST);
+
+    let request = test::TestRequest::post()
+        .uri("/auth/accept-invite")
+        .set_json(json!({"token": "made-up", "name": "Grace Hopper", "password": PASSWORD}))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+
+    let registration = RegistrationSettings {
+        self_registration: false,
+        ..tenant_app.registration.get_ref().clone()
+    };
+    tenant_app.registration = web::Data::new(registration);
+    let app = init_with(&tenant_app, pool.clone(), blob_store()).await;
+    let request = test::TestRequest::post()
+        .uri("/auth/register")
+        .set_json(json!({"email": GRACE, "password": PASSWORD, "name": "Grace Hopper"}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["error"], "Self-registration is disabled");
+}
@@ -0,0 +1,103 @@
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::{bearer, capture_otp, init, login, register, tenant_app, CaptureFile, EMAIL};
+use crate::models::otp::{OtpChannel, OtpFactor};
+
+#[actix_web::test]
+async fn enrolled_otp_completes_login_and_rejects_wrong_codes() {
+    let capture = CaptureFile::new();
+    let mut tenant_app = tenant_app();
+    capture_otp(&mut tenant_app, &capture.0, 300);
+    let app = init(&tenant_app).await;
+    register(&app).await;
+    let body = login(&app).await;
+
+    // Alta del factor: el código llega al fichero en lugar de enviarse
+    let request = test::TestRequest::post()
+        .uri("/auth/mfa/otp")
+        .insert_header(bearer(&body["token"]))
+        .set_json(json!({"channel": "email"}))
+        .to_request();
+    assert!(test::call_service(&app, request).await.status().is_success());
+
+    let request = test::TestRequest::post()
+        .uri("/auth/mfa/otp/confirm")
+        .insert_header(bearer(&body["token"]))
+        .set_json(json!({"code": capture.last_code()}))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    // El login ya no abre sesión: pide el segundo factor
+    let challenge = login(&app).await;
+    assert_eq!(challenge["mfa_required"], true);
+    assert!(challenge.get("token").is_none());
+    let code = capture.last_code();
+    let wrong = if code == "000000" { "111111" } else { "000000" };
+
+    let request = test::TestRequest::post()
+        .uri("/auth/mfa/verify")
+        .set_json(json!({"mfa_token": challenge["mfa_token"], "code": wrong}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let error: Value = test::read_body_json(response).await;
+    assert_eq!(error["remaining_attempts"], 2);
+
+    let request = test::TestRequest::post()
+        .uri("/auth/mfa/verify")
+        .set_json(json!({"mfa_token": challenge["mfa_token"], "code": code}))
+        .to_request();
+    let session: Value = test::call_and_read_body_json(&app, request).await;
+    let request = test::TestRequest::get()
+        .uri("/auth/sessions")
+        .insert_header(bearer(&session["token"]))
+        .to_request();
+    let sessions: Value = test::call_and_read_body_json(&app, request).await;
+    let current = sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["id"] == session["session_id"])
+        .unwrap();
+    assert_eq!(current["amr"], json!(["password", "otp"]));
+
+    // Un código ya usado no vuelve a valer
+    let request = test::TestRequest::post()
+        .uri("/auth/mfa/verify")
+        .set_json(json!({"mfa_token": challenge["mfa_token"], "code": code}))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn expired_otp_code_is_rejected() {
+    let capture = CaptureFile::new();
+    let mut tenant_app = tenant_app();
+    capture_otp(&mut tenant_app, &capture.0, 1);
+    let app = init(&tenant_app).await;
+    register(&app).await;
+
+    let user = tenant_app.users.find_by_email(EMAIL).await.unwrap().unwrap();
+    let factor = OtpFactor {
+        channel: OtpChannel::Email,
+        destination: EMAIL.to_string(),
+    };
+    tenant_app.users.set_otp_factor(user.id, &factor).await.unwrap();
+
+    let challenge = login(&app).await;
+    assert_eq!(challenge["mfa_required"], true);
+    let code = capture.last_code();
+    actix_web::rt::time::sleep(Duration::from_millis(1500)).await;
+
+    let request = test::TestRequest::post()
+        .uri("/auth/mfa/verify")
+        .set_json(json!({"mfa_token": challenge["mfa_token"], "code": code}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let error: Value = test::read_body_json(response).await;
+    assert_eq!(error["error"], "Code expired or too many attempts");
+}
@@ -0,0 +1,476 @@
+//! Login OIDC contra un proveedor simulado en un puerto local: token
+//! endpoint, userinfo y JWKS con una clave RSA de pruebas.
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::cookie::Cookie;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::{test, web, App, HttpResponse, HttpServer};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+use super::{bearer, init, login, register, tenant_app, EMAIL};
+use crate::config::oidc::{OidcProvider, OidcProviders, ProviderProtocol};
+
+const CLIENT_ID: &str = "rust-auth-api";
+const SUBJECT: &str = "mock-user-1";
+const KID: &str = "test-key";
+/// Módulo público de `oidc_test_key.pem` en base64url (exponente 65537).
+const MODULUS: &str = "1KHnIZsb0NYhfgpuI3LFMdG9I4w2NjAZoa0IPEyXxxdUtNDaPx2HWXgF-0rUtdHyDIsgGYke27X0RbBLZ4A_6e-HRDalzCz3eDpWScT3rbL17YO5vvMMoZQcpLr9bJ3VbI6EgSR_14ucngLOTQ1l8nEKE6teXqy7ZTChe-Xv_yCwxNxwmjVeDRVFLYvXoA15A7TOwCWuUpcQZ72zuOxOkC7CsK5wT5E3SEPB5FL0DYSAeQzg9DvBHyC_C0JeyRgT4EWeCVQgeqHcz8se97Q_mNqQhvJuVZLj3ETAmNRcJGzlNovH8XjECsZCiy8j1TZwg9Enuv90FoRjfKkL9T3-mw";
+
+/// Cambio sobre unos claims válidos del id_token.
+type ClaimsEdit = fn(&mut Value);
+
+/// Respuestas del proveedor simulado; cada prueba las ajusta antes del callback.
+struct Idp {
+    id_token: Mutex<Option<String>>,
+    userinfo: Mutex<Value>,
+}
+
+async fn token(idp: web::Data<Idp>) -> HttpResponse {
+    let mut body = json!({"access_token": "mock-access-token", "token_type": "Bearer"});
+    if let Some(id_token) = idp.id_token.lock().unwrap().clone() {
+        body["id_token"] = json!(id_token);
+    }
+    HttpResponse::Ok().json(body)
+}
+
+async fn userinfo(idp: web::Data<Idp>) -> HttpResponse {
+    HttpResponse::Ok().json(idp.userinfo.lock().unwrap().clone())
+}
+
+async fn jwks() -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "keys": [{"kty": "RSA", "kid": KID, "use": "sig", "alg": "RS256", "n": MODULUS, "e": "AQAB"}]
+    }))
+}
+
+/// Arranca el proveedor y devuelve su URL base.
+fn spawn_idp() -> (String, Arc<Idp>) {
+    let idp = Arc::new(Idp {
+        id_token: Mutex::new(None),
+        userinfo: Mutex::new(json!({
+            "sub": SUBJECT,
+            "email": EMAIL,
+            "email_verified": true,
+            "name": "Ada Lovelace"
+        })),
+    });
+    let data = web::Data::from(idp.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(data.clone())
+            .route("/token", web::post().to(token))
+            .route("/userinfo", web::get().to(userinfo))
+            .route("/jwks", web::get().to(jwks))
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    actix_web::rt::spawn(server);
+    (url, idp)
+}
+
+fn provider(idp_url: &str, protocol: ProviderProtocol) -> OidcProvider {
+    OidcProvider {
+        name: "mock".to_string(),
+        client_id: CLIENT_ID.to_string(),
+        client_secret: "mock-secret".to_string(),
+        authorize_url: format!("{}/authorize", idp_url),
+        token_url: format!("{}/token", idp_url),
+        userinfo_url: format!("{}/userinfo", idp_url),
+        redirect_uri: "http://localhost/auth/oidc/mock/callback".to_string(),
+        protocol,
+        scopes: "openid email profile".to_string(),
+        subject_claim: "sub".to_string(),
+        email_claim: "email".to_string(),
+        name_claim: "name".to_string(),
+        trust_email: false,
+    }
+}
+
+fn oidc(idp_url: &str) -> ProviderProtocol {
+    ProviderProtocol::Oidc {
+        issuer: idp_url.to_string(),
+        jwks_url: format!("{}/jwks", idp_url),
+    }
+}
+
+/// Claims válidos para el `nonce` de la petición.
+fn claims(idp_url: &str, nonce: &str) -> Value {
+    let now = chrono::Utc::now().timestamp();
+    json!({
+        "iss": idp_url,
+        "aud": CLIENT_ID,
+        "sub": SUBJECT,
+        "nonce": nonce,
+        "iat": now,
+        "exp": now + 300
+    })
+}
+
+fn sign_id_token(claims: &Value) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(KID.to_string());
+    let key = EncodingKey::from_rsa_pem(include_bytes!("oidc_test_key.pem")).unwrap();
+    encode(&header, claims, &key).unwrap()
+}
+
+/// Resultado de `/authorize`: `state` y `nonce` de la URL y la cookie firmada.
+struct Authorization {
+    state: String,
+    nonce: String,
+    cookie: Cookie<'static>,
+}
+
+async fn authorize<S, B>(app: &S) -> Authorization
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = test::TestRequest::get().uri("/auth/oidc/mock/authorize").to_request();
+    let response = test::call_service(app, request).await;
+    assert_eq!(response.status(), 302);
+
+    let cookie = response
+        .response()
+        .cookies()
+        .find(|c| c.name() == "oidc_state")
+        .unwrap()
+        .into_owned();
+    assert!(cookie.http_only().unwrap_or(false));
+
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    let url = reqwest::Url::parse(location).unwrap();
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    };
+    Authorization {
+        state: param("state"),
+        nonce: param("nonce"),
+        cookie,
+    }
+}
+
+async fn callback<S, B>(app: &S, state: &str, cookie: Option<Cookie<'static>>) -> ServiceResponse<B>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let mut request = test::TestRequest::get()
+        .uri(&format!("/auth/oidc/mock/callback?code=mock-code&state={}", state));
+    if let Some(cookie) = cookie {
+        request = request.cookie(cookie);
+    }
+    test::call_service(app, request.to_request()).await
+}
+
+/// Autoriza, prepara el id_token con `edit` sobre unos claims válidos y
+/// completa el callback.
+async fn sign_in<S, B>(app: &S, idp_url: &str, idp: &Idp, edit: impl FnOnce(&mut Value)) -> ServiceResponse<B>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let authorization = authorize(app).await;
+    let mut claims = claims(idp_url, &authorization.nonce);
+    edit(&mut claims);
+    *idp.id_token.lock().unwrap() = Some(sign_id_token(&claims));
+    callback(app, &authorization.state, Some(authorization.cookie)).await
+}
+
+#[actix_web::test]
+async fn first_login_creates_the_user_and_later_logins_reuse_the_identity() {
+    let (idp_url, idp) = spawn_idp();
+    let mut tenant_app = tenant_app();
+    tenant_app.oidc = web::Data::new(OidcProviders::for_tests(vec![provider(&idp_url, oidc(&idp_url))]));
+    let app = init(&tenant_app).await;
+
+    let response = sign_in(&app, &idp_url, &idp, |_| {}).await;
+    assert_eq!(response.status(), 200);
+    let removal = response
+        .response()
+        .cookies()
+        .find(|c| c.name() == "oidc_state")
+        .unwrap()
+        .into_owned();
+    assert_eq!(removal.value(), "");
+    let first: Value = test::read_body_json(response).await;
+    assert_eq!(first["user"]["email"], EMAIL);
+    assert!(first["token"].is_string());
+
+    // El correo cambia en el proveedor, pero la identidad ya está vinculada
+    idp.userinfo.lock().unwrap()["email"] = json!("ada.lovelace@example.org");
+    let response = sign_in(&app, &idp_url, &idp, |_| {}).await;
+    assert_eq!(response.status(), 200);
+    let second: Value = test::read_body_json(response).await;
+    assert_eq!(second["user"]["id"], first["user"]["id"]);
+}
+
+#[actix_web::test]
+async fn existing_account_is_linked_only_by_a_verified_email() {
+    let (idp_url, idp) = spawn_idp();
+    let mut tenant_app = tenant_app();
+    tenant_app.oidc = web::Data::new(OidcProviders::for_tests(vec![provider(&idp_url, oidc(&idp_url))]));
+    let app = init(&tenant_app).await;
+    register(&app).await;
+
+    idp.userinfo.lock().unwrap()["email_verified"] = json!(false);
+    let response = sign_in(&app, &idp_url, &idp, |_| {}).await;
+    assert_eq!(response.status(), 403);
+
+    // Mismo correo con otras mayúsculas: se vincula a la cuenta existente
+    *idp.userinfo.lock().unwrap() = json!({
+        "sub": SUBJECT,
+        "email": "Ada@Example.com",
+        "email_verified": true
+    });
+    let response = sign_in(&app, &idp_url, &idp, |_| {}).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["user"]["email"], EMAIL);
+    assert_eq!(body["user"]["name"], "Ada Lovelace");
+}
+
+#[actix_web::test]
+async fn callback_requires_the_signed_state_cookie() {
+    let (idp_url, idp) = spawn_idp();
+    let mut tenant_app = tenant_app();
+    tenant_app.oidc = web::Data::new(OidcProviders::for_tests(vec![provider(&idp_url, oidc(&idp_url))]));
+    let app = init(&tenant_app).await;
+
+    let authorization = authorize(&app).await;
+    *idp.id_token.lock().unwrap() = Some(sign_id_token(&claims(&idp_url, &authorization.nonce)));
+
+    let response = callback(&app, &authorization.state, None).await;
+    assert_eq!(response.status(), 400);
+
+    let response = callback(&app, "another-state", Some(authorization.cookie.clone())).await;
+    assert_eq!(response.status(), 400);
+
+    // Cualquier cambio en el valor invalida la firma
+    let mut tampered = authorization.cookie.clone();
+    let value = tampered.value().to_string();
+    let flipped = if value.starts_with('e') { "f" } else { "e" };
+    tampered.set_value(format!("{}{}", flipped, &value[1..]));
+    let response = callback(&app, &authorization.state, Some(tampered)).await;
+    assert_eq!(response.status(), 400);
+
+    let response = callback(&app, &authorization.state, Some(authorization.cookie)).await;
+    assert_eq!(response.status(), 200);
+}
+
+#[actix_web::test]
+async fn invalid_id_tokens_are_rejected() {
+    let (idp_url, idp) = spawn_idp();
+    let mut tenant_app = tenant_app();
+    tenant_app.oidc = web::Data::new(OidcProviders::for_tests(vec![provider(&idp_url, oidc(&idp_url))]));
+    let app = init(&tenant_app).await;
+
+    let edits: [(&str, ClaimsEdit); 5] = [
+        ("audience", |c| c["aud"] = json!("another-client")),
+        ("issuer", |c| c["iss"] = json!("https://evil.example.com")),
+        ("nonce", |c| c["nonce"] = json!("replayed-nonce")),
+        ("expiry", |c| c["exp"] = json!(chrono::Utc::now().timestamp() - 600)),
+        ("subject", |c| c["sub"] = json!("someone-else")),
+    ];
+    for (case, edit) in edits {
+        let response = sign_in(&app, &idp_url, &idp, edit).await;
+        assert_eq!(response.status(), 502, "wrong {} was accepted", case);
+    }
+
+    // Firmado con HS256 y la clave pública como secreto
+    let authorization = authorize(&app).await;
+    let mut header = Header::new(Algorithm::HS256);
+    header.kid = Some(KID.to_string());
+    let forged = encode(
+        &header,
+        &claims(&idp_url, &authorization.nonce),
+        &EncodingKey::from_secret(MODULUS.as_bytes()),
+    )
+    .unwrap();
+    *idp.id_token.lock().unwrap() = Some(forged);
+    let response = callback(&app, &authorization.state, Some(authorization.cookie)).await;
+    assert_eq!(response.status(), 502);
+
+    // Sin id_token un proveedor OIDC no vale
+    let authorization = authorize(&app).await;
+    *idp.id_token.lock().unwrap() = None;
+    let response = callback(&app, &authorization.state, Some(authorization.cookie)).await;
+    assert_eq!(response.status(), 502);
+}
+
+#[actix_web::test]
+async fn oauth2_providers_sign_in_without_an_id_token() {
+    let (idp_url, _idp) = spawn_idp();
+    let mut tenant_app = tenant_app();
+    tenant_app.oidc = web::Data::new(OidcProviders::for_tests(vec![provider(
+        &idp_url,
+        ProviderProtocol::OAuth2,
+    )]));
+    let app = init(&tenant_app).await;
+
+    let authorization = authorize(&app).await;
+    let response = callback(&app, &authorization.state, Some(authorization.cookie)).await;
+    assert_eq!(response.status(), 200);
+}
+
+/// Pide la reautenticación con el proveedor para la sesión de `token`.
+async fn start_reauthentication<S, B>(app: &S, token: &Value) -> Authorization
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = test::TestRequest::post()
+        .uri("/auth/oidc/mock/reauthenticate")
+        .insert_header(bearer(token))
+        .to_request();
+    let response = test::call_service(app, request).await;
+    assert_eq!(response.status(), 200);
+    let cookie = response
+        .response()
+        .cookies()
+        .find(|c| c.name() == "oidc_state")
+        .unwrap()
+        .into_owned();
+    let body: Value = test::read_body_json(response).await;
+
+    let url = reqwest::Url::parse(body["authorization_url"].as_str().unwrap()).unwrap();
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    };
+    // El proveedor debe pedir las credenciales aunque haya sesión abierta
+    assert_eq!(param("prompt"), "login");
+    assert_eq!(param("max_age"), "0");
+    Authorization {
+        state: param("state"),
+        nonce: param("nonce"),
+        cookie,
+    }
+}
+
+#[actix_web::test]
+async fn a_fresh_provider_login_reauthenticates_the_session() {
+    let (idp_url, idp) = spawn_idp();
+    let mut tenant_app = tenant_app();
+    tenant_app.oidc = web::Data::new(OidcProviders::for_tests(vec![provider(&idp_url, oidc(&idp_url))]));
+    let app = init(&tenant_app).await;
+
+    let response = sign_in(&app, &idp_url, &idp, |_| {}).await;
+    let body: Value = test::read_body_json(response).await;
+    let token = body["token"].clone();
+    let sessions = tenant_app.sessions.get_ref();
+    let mut session = sessions.get(body["session_id"].as_str().unwrap()).await.unwrap().unwrap();
+    session.auth_time -= 3600;
+    sessions.update(&session, 3600).await.unwrap();
+
+    // Sin `auth_time` no hay prueba de un login reciente
+    let authorization = start_reauthentication(&app, &token).await;
+    *idp.id_token.lock().unwrap() = Some(sign_id_token(&claims(&idp_url, &authorization.nonce)));
+    let response = callback(&app, &authorization.state, Some(authorization.cookie)).await;
+    assert_eq!(response.status(), 401);
+
+    // Un login en el proveedor anterior a la redirección tampoco vale
+    let authorization = start_reauthentication(&app, &token).await;
+    let mut stale = claims(&idp_url, &authorization.nonce);
+    stale["auth_time"] = json!(chrono::Utc::now().timestamp() - 3600);
+    *idp.id_token.lock().unwrap() = Some(sign_id_token(&stale));
+    let response = callback(&app, &authorization.state, Some(authorization.cookie)).await;
+    assert_eq!(response.status(), 401);
+
+    let authorization = start_reauthentication(&app, &token).await;
+    let mut fresh = claims(&idp_url, &authorization.nonce);
+    fresh["auth_time"] = json!(chrono::Utc::now().timestamp());
+    *idp.id_token.lock().unwrap() = Some(sign_id_token(&fresh));
+    let response = callback(&app, &authorization.state, Some(authorization.cookie)).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = test::read_body_json(response).await;
+    assert!(body["amr"].as_array().unwrap().contains(&json!("oidc")));
+    assert!(body["token"].is_string());
+
+    let session = sessions.get(&session.id).await.unwrap().unwrap();
+    assert!(session.authenticated_within(60, chrono::Utc::now().timestamp()));
+}
+
+#[actix_web::test]
+async fn reauthentication_requires_a_linked_identity_and_an_id_token() {
+    let (idp_url, idp) = spawn_idp();
+    let mut tenant_app = tenant_app();
+    tenant_app.oidc = web::Data::new(OidcProviders::for_tests(vec![provider(&idp_url, oidc(&idp_url))]));
+    let app = init(&tenant_app).await;
+
+    // Sesión con contraseña de una cuenta sin identidad vinculada
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+    let authorization = start_reauthentication(&app, &token).await;
+    let mut fresh = claims(&idp_url, &authorization.nonce);
+    fresh["auth_time"] = json!(chrono::Utc::now().timestamp());
+    *idp.id_token.lock().unwrap() = Some(sign_id_token(&fresh));
+    let response = callback(&app, &authorization.state, Some(authorization.cookie)).await;
+    assert_eq!(response.status(), 403);
+
+    let mut tenant_app = super::tenant_app();
+    tenant_app.oidc = web::Data::new(OidcProviders::for_tests(vec![provider(
+        &idp_url,
+        ProviderProtocol::OAuth2,
+    )]));
+    let app = init(&tenant_app).await;
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+    let request = test::TestRequest::post()
+        .uri("/auth/oidc/mock/reauthenticate")
+        .insert_header(bearer(&token))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn cookie_mode_is_carried_to_the_callback() {
+    let (idp_url, idp) = spawn_idp();
+    let mut tenant_app = tenant_app();
+    tenant_app.oidc = web::Data::new(OidcProviders::for_tests(vec![provider(&idp_url, oidc(&idp_url))]));
+    let app = init(&tenant_app).await;
+
+    let request = test::TestRequest::get().uri("/auth/oidc/mock/authorize?mode=cookie").to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 302);
+    let cookie = response
+        .response()
+        .cookies()
+        .find(|c| c.name() == "oidc_state")
+        .unwrap()
+        .into_owned();
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    let url = reqwest::Url::parse(location).unwrap();
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    };
+    *idp.id_token.lock().unwrap() = Some(sign_id_token(&claims(&idp_url, &param("nonce"))));
+
+    let response = callback(&app, &param("state"), Some(cookie)).await;
+    assert_eq!(response.status(), 200);
+    let session = response
+        .response()
+        .cookies()
+        .find(|c| c.name() == tenant_app.cookie_settings.session_name)
+        .unwrap()
+        .into_owned();
+    assert!(session.http_only().unwrap_or(false));
+    let body: Value = test::read_body_json(response).await;
+    assert!(body.get("token").is_none());
+    assert!(body["csrf_token"].is_string());
+}
@@ -0,0 +1,204 @@
+//! Organizaciones: alta, cambio de organización activa, invitaciones y roles.
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use chrono::Duration;
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use serde_json::{json, Value};
+
+use super::{bearer, blob_store, init_with, login, pg_tenant_app, register, test_database, PASSWORD};
+use crate::models::organization::OrgRole;
+use crate::models::user::TokenClaims;
+use crate::services::organizations;
+
+const GRACE: &str = "grace@example.com";
+
+/// Da de alta a Grace y devuelve su token.
+async fn register_grace<S, B>(app: &S) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = test::TestRequest::post()
+        .uri("/auth/register")
+        .set_json(json!({"email": GRACE, "password": PASSWORD, "name": "Grace Hopper"}))
+        .to_request();
+    assert!(test::call_service(app, request).await.status().is_success());
+    let request = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({"email": GRACE, "password": PASSWORD}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(app, request).await;
+    body["token"].clone()
+}
+
+async fn switch<S, B>(app: &S, token: &Value, org_id: Value) -> ServiceResponse<B>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = test::TestRequest::post()
+        .uri("/organizations/switch")
+        .insert_header(bearer(token))
+        .set_json(json!({"org_id": org_id}))
+        .to_request();
+    test::call_service(app, request).await
+}
+
+fn claims(token: &Value) -> TokenClaims {
+    decode::<TokenClaims>(
+        token.as_str().unwrap(),
+        &DecodingKey::from_secret(b"integration-test-secret"),
+        &Validation::default(),
+    )
+    .unwrap()
+    .claims
+}
+
+#[actix_web::test]
+async fn members_join_by_invitation_and_act_with_their_role() {
+    let Some(pool) = test_database().await else { return };
+    let tenant_app = pg_tenant_app(&pool);
+    let app = init_with(&tenant_app, pool.clone(), blob_store()).await;
+
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+    let request = test::TestRequest::post()
+        .uri("/organizations")
+        .insert_header(bearer(&token))
+        .set_json(json!({"name": "Analytical Engines"}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let organization: Value = test::read_body_json(response).await;
+    let org_id = organization["id"].clone();
+
+    // El cambio emite un token con el `org_id` y el anterior deja de valer
+    let response = switch(&app, &token, org_id.clone()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let switched: Value = test::read_body_json(response).await;
+    assert_eq!(switched["role"], "owner");
+    let ada = switched["token"].clone();
+    assert_eq!(claims(&ada).org_id, org_id.as_i64());
+    let request = test::TestRequest::get().uri("/organizations").insert_header(bearer(&token)).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+
+    let grace = register_grace(&app).await;
+    let grace_id = claims(&grace).sub;
+    let owner_id = claims(&ada).sub;
+    let (_, invitation) = organizations::invite(
+        &pool,
+        org_id.as_i64().unwrap(),
+        owner_id,
+        GRACE,
+        OrgRole::Member,
+        Duration::hours(1),
+    )
+    .await
+    .unwrap();
+    let request = test::TestRequest::post()
+        .uri("/organizations/invitations/accept")
+        .insert_header(bearer(&grace))
+        .set_json(json!({"token": invitation}))
+        .to_request();
+    let accepted: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(accepted["role"], "member");
+
+    let response = switch(&app, &grace, org_id).await;
+    let grace: Value = test::read_body_json::<Value, _>(response).await["token"].clone();
+    let request = test::TestRequest::get()
+        .uri("/organizations/current/members")
+        .insert_header(bearer(&grace))
+        .to_request();
+    let members: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(members.as_array().unwrap().len(), 2);
+
+    // Un miembro no invita ni cambia roles
+    let request = test::TestRequest::post()
+        .uri("/organizations/current/invitations")
+        .insert_header(bearer(&grace))
+        .set_json(json!({"email": "alan@example.com", "role": "member"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+
+    // Ascendida a admin por el owner, ya puede invitar
+    let request = test::TestRequest::put()
+        .uri(&format!("/organizations/current/members/{}", grace_id))
+        .insert_header(bearer(&ada))
+        .set_json(json!({"role": "admin"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let request = test::TestRequest::post()
+        .uri("/organizations/current/invitations")
+        .insert_header(bearer(&grace))
+        .set_json(json!({"email": "alan@example.com", "role": "member"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn organization_routes_reject_outsiders_and_bad_invitations() {
+    let Some(pool) = test_database().await else { return };
+    let tenant_app = pg_tenant_app(&pool);
+    let app = init_with(&tenant_app, pool.clone(), blob_store()).await;
+
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+    let request = test::TestRequest::post()
+        .uri("/organizations")
+        .insert_header(bearer(&token))
+        .set_json(json!({"name": "Analytical Engines"}))
+        .to_request();
+    let organization: Value = test::call_and_read_body_json(&app, request).await;
+    let org_id = organization["id"].as_i64().unwrap();
+
+    // Sin organización activa
+    let request = test::TestRequest::get()
+        .uri("/organizations/current/members")
+        .insert_header(bearer(&token))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+
+    let grace = register_grace(&app).await;
+    assert_eq!(switch(&app, &grace, json!(org_id)).await.status(), StatusCode::FORBIDDEN);
+
+    let owner_id = claims(&token).sub;
+    let accept = |invitation: String| {
+        test::TestRequest::post()
+            .uri("/organizations/invitations/accept")
+            .insert_header(bearer(&grace))
+            .set_json(json!({"token": invitation}))
+            .to_request()
+    };
+
+    // Para otro correo, caducada o inventada
+    let invite = |email: &'static str, ttl: Duration| {
+        let pool = pool.clone();
+        async move {
+            organizations::invite(&pool, org_id, owner_id, email, OrgRole::Member, ttl)
+                .await
+                .unwrap()
+                .1
+        }
+    };
+    let other = invite("alan@example.com", Duration::hours(1)).await;
+    assert_eq!(test::call_service(&app, accept(other)).await.status(), StatusCode::FORBIDDEN);
+    let expired = invite(GRACE, Duration::seconds(-1)).await;
+    assert_eq!(test::call_service(&app, accept(expired)).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(test::call_service(&app, accept("made-up".to_string())).await.status(), StatusCode::NOT_FOUND);
+
+    // Quitar a un miembro surte efecto aunque su token lleve el `org_id`
+    let invitation = invite(GRACE, Duration::hours(1)).await;
+    assert_eq!(test::call_service(&app, accept(invitation)).await.status(), StatusCode::OK);
+    let response = switch(&app, &grace, json!(org_id)).await;
+    let grace: Value = test::read_body_json::<Value, _>(response).await["token"].clone();
+    organizations::remove_member(&pool, org_id, claims(&grace).sub).await.unwrap();
+    let request = test::TestRequest::get()
+        .uri("/organizations/current/members")
+        .insert_header(bearer(&grace))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,495 @@
+use actix_web::cookie::Cookie;
+use actix_web::http::StatusCode;
+use actix_web::{test, web};
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::{
+    bearer, capture_otp, init, login, register, tenant_app, tenant_app_with_users, test_database,
+    test_redis, CaptureFile, EMAIL, PASSWORD,
+};
+use crate::config::{password, session};
+use crate::models::session::{Session, AMR_MAGIC_LINK, AMR_OTP};
+use crate::services::auth::{AuthService, LoginOutcome};
+use crate::services::users::InMemoryUserRepository;
+use crate::services::session::{InMemorySessionStore, PgSessionStore, RedisSessionStore, SessionStore};
+
+#[actix_web::test]
+async fn login_issues_a_session() {
+    let tenant_app = tenant_app();
+    let app = init(&tenant_app).await;
+    register(&app).await;
+
+    let body = login(&app).await;
+    let session_id = body["session_id"].as_str().unwrap();
+    let stored = tenant_app.sessions.get(session_id).await.unwrap().unwrap();
+    assert_eq!(stored.token, body["token"].as_str().unwrap());
+
+    let request = test::TestRequest::get()
+        .uri("/auth/sessions")
+        .insert_header(bearer(&body["token"]))
+        .to_request();
+    let sessions: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(sessions.as_array().unwrap().len(), 1);
+    assert_eq!(sessions[0]["id"], body["session_id"]);
+}
+
+#[actix_web::test]
+async fn logout_invalidates_the_bearer_token() {
+    let tenant_app = tenant_app();
+    let app = init(&tenant_app).await;
+    register(&app).await;
+    let body = login(&app).await;
+
+    let request = test::TestRequest::post()
+        .uri("/auth/logout")
+        .insert_header(bearer(&body["token"]))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let request = test::TestRequest::get()
+        .uri("/auth/sessions")
+        .insert_header(bearer(&body["token"]))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn revoke_all_invalidates_every_bearer_token() {
+    let tenant_app = tenant_app();
+    let app = init(&tenant_app).await;
+    register(&app).await;
+    let first = login(&app).await;
+    let second = login(&app).await;
+
+    let request = test::TestRequest::delete()
+        .uri("/auth/sessions")
+        .insert_header(bearer(&first["token"]))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    for body in [&first, &second] {
+        let request = test::TestRequest::get()
+            .uri("/auth/sessions")
+            .insert_header(bearer(&body["token"]))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[actix_web::test]
+async fn revoked_session_is_rejected_by_the_validator() {
+    let tenant_app = tenant_app();
+    let app = init(&tenant_app).await;
+    register(&app).await;
+    let body = login(&app).await;
+
+    // Revocada desde fuera de la petición, p. ej. por un administrador
+    let session_id = body["session_id"].as_str().unwrap();
+    assert!(tenant_app.sessions.revoke(session_id).await.unwrap());
+
+    let request = test::TestRequest::post()
+        .uri("/auth/logout")
+        .insert_header(bearer(&body["token"]))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn sessions_slide_with_activity_and_expire_when_idle_or_too_old() {
+    let tenant_app = tenant_app();
+    let app = init(&tenant_app).await;
+    register(&app).await;
+    let settings = tenant_app.session_settings.get_ref().clone();
+    let profile = |token: &Value| test::TestRequest::get().uri("/profile").insert_header(bearer(token)).to_request();
+    // Cambia la sesión en el store como si hubiera pasado el tiempo
+    let rewind = |session_id: String, last_seen_ago: i64, expires_in: i64| {
+        let sessions = tenant_app.sessions.clone();
+        async move {
+            let now = Utc::now().timestamp();
+            let mut session = sessions.get(&session_id).await.unwrap().unwrap();
+            session.last_seen_at = now - last_seen_ago;
+            session.expires_at = now + expires_in;
+            assert!(sessions.update(&session, 3600).await.unwrap());
+        }
+    };
+    let last_seen = |session_id: String| {
+        let sessions = tenant_app.sessions.clone();
+        async move { sessions.get(&session_id).await.unwrap().unwrap().last_seen_at }
+    };
+
+    let body = login(&app).await;
+    let session_id = body["session_id"].as_str().unwrap().to_string();
+
+    // Dentro de `touch_interval` la actividad no se escribe
+    rewind(session_id.clone(), settings.touch_interval / 2, 3600).await;
+    let before = last_seen(session_id.clone()).await;
+    assert_eq!(test::call_service(&app, profile(&body["token"])).await.status(), StatusCode::OK);
+    assert_eq!(last_seen(session_id.clone()).await, before);
+
+    // Cerca del idle timeout una petición la renueva
+    rewind(session_id.clone(), settings.idle_timeout - 10, 3600).await;
+    assert_eq!(test::call_service(&app, profile(&body["token"])).await.status(), StatusCode::OK);
+    assert!(last_seen(session_id.clone()).await >= Utc::now().timestamp() - 1);
+
+    // Sin actividad durante el idle timeout muere
+    rewind(session_id, settings.idle_timeout, 3600).await;
+    assert_eq!(test::call_service(&app, profile(&body["token"])).await.status(), StatusCode::UNAUTHORIZED);
+
+    // Y, activa o no, al llegar al límite absoluto
+    let body = login(&app).await;
+    rewind(body["session_id"].as_str().unwrap().to_string(), 0, 0).await;
+    assert_eq!(test::call_service(&app, profile(&body["token"])).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn postgres_sessions_are_isolated_per_tenant() {
+    let Some(pool) = test_database().await else { return };
+    let user_id: i64 = sqlx::query_scalar(
+        "INSERT INTO users (email, password, name, tenant_id) VALUES ('ada@example.com', 'x', 'Ada', 'acme') \
+         RETURNING id",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let acme = PgSessionStore::new(pool.clone(), "acme".to_string());
+    let other = PgSessionStore::new(pool.clone(), "globex".to_string());
+
+    let now = chrono::Utc::now().timestamp();
+    let session = Session {
+        id: "session-1".to_string(),
+        user_id,
+        email: "ada@example.com".to_string(),
+        name: "Ada".to_string(),
+        token: "token".to_string(),
+        created_at: now,
+        expires_at: now + 3600,
+        last_seen_at: now,
+        auth_time: now,
+        amr: vec!["password".to_string()],
+        org_id: None,
+    };
+    acme.create(&session, 3600).await.unwrap();
+
+    // Otro tenant no la ve ni puede tocarla, aunque conozca el id
+    assert!(other.get(&session.id).await.unwrap().is_none());
+    assert!(!other.touch(&session, 3600).await.unwrap());
+    assert!(!other.update(&session, 3600).await.unwrap());
+    assert!(other.list_for_user(user_id).await.unwrap().is_empty());
+    assert!(!other.revoke(&session.id).await.unwrap());
+    assert_eq!(other.revoke_all_for_user(user_id).await.unwrap(), 0);
+
+    assert!(acme.get(&session.id).await.unwrap().is_some());
+    assert_eq!(acme.list_for_user(user_id).await.unwrap().len(), 1);
+    assert!(acme.revoke(&session.id).await.unwrap());
+    assert!(acme.get(&session.id).await.unwrap().is_none());
+}
+
+/// Deja la autenticación de la sesión fuera de `REAUTH_MAX_AGE_SECONDS`.
+async fn age_session(store: &dyn SessionStore, session_id: &str) {
+    let mut session = store.get(session_id).await.unwrap().unwrap();
+    session.auth_time -= 3600;
+    assert!(store.update(&session, 3600).await.unwrap());
+}
+
+#[actix_web::test]
+async fn passwordless_sessions_reauthenticate_with_a_one_time_code() {
+    let capture = CaptureFile::new();
+    let mut tenant_app = tenant_app();
+    capture_otp(&mut tenant_app, &capture.0, 300);
+    let app = init(&tenant_app).await;
+
+    // Cuenta creada por enlace mágico: no conoce ninguna contraseña
+    let user = tenant_app.users.create(EMAIL, "unusable", "Ada").await.unwrap();
+    let session = match tenant_app.auth.begin_session(user, vec![AMR_MAGIC_LINK.to_string()]).await.unwrap() {
+        LoginOutcome::Authenticated(_, session) => session,
+        LoginOutcome::MfaRequired(..) => panic!("unexpected second factor"),
+    };
+    age_session(tenant_app.sessions.get_ref(), &session.id).await;
+    let token = json!(session.token);
+
+    let change = |token: &Value| {
+        test::TestRequest::post()
+            .uri("/auth/password")
+            .insert_header(bearer(token))
+            .set_json(json!({"new_password": "Velvet-Harbor-Quartz-58"}))
+            .to_request()
+    };
+    let response = test::call_service(&app, change(&token)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["reauth_required"], true);
+
+    let reauthenticate = |token: &Value, body: Value| {
+        test::TestRequest::post()
+            .uri("/auth/reauthenticate")
+            .insert_header(bearer(token))
+            .set_json(body)
+            .to_request()
+    };
+    for body in [json!({}), json!({"password": PASSWORD, "code": "123456"})] {
+        let response = test::call_service(&app, reauthenticate(&token, body)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let request = test::TestRequest::post()
+        .uri("/auth/reauthenticate/otp")
+        .insert_header(bearer(&token))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["channel"], "email");
+
+    let code = capture.last_code();
+    let wrong = if code == "000000" { "111111" } else { "000000" };
+    let response = test::call_service(&app, reauthenticate(&token, json!({"code": wrong}))).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = test::call_service(&app, reauthenticate(&token, json!({"code": code}))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(response).await;
+    assert!(body["amr"].as_array().unwrap().contains(&json!(AMR_OTP)));
+    let renewed = body["token"].clone();
+
+    // El token anterior deja de valer; con el nuevo se fija la contraseña
+    let response = test::call_service(&app, change(&token)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = test::call_service(&app, change(&renewed)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn password_sessions_still_need_the_current_password() {
+    let app = init(&tenant_app()).await;
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+
+    let request = test::TestRequest::post()
+        .uri("/auth/password")
+        .insert_header(bearer(&token))
+        .set_json(json!({"new_password": "Velvet-Harbor-Quartz-58"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Cookies de sesión y CSRF de una respuesta de login en modo cookie.
+fn session_cookies<B>(
+    tenant_app: &crate::TenantApp,
+    response: &actix_web::dev::ServiceResponse<B>,
+) -> (Cookie<'static>, Cookie<'static>) {
+    let find = |name: &str| {
+        response
+            .response()
+            .cookies()
+            .find(|c| c.name() == name)
+            .unwrap_or_else(|| panic!("missing cookie {}", name))
+            .into_owned()
+    };
+    let session = find(&tenant_app.cookie_settings.session_name);
+    assert!(session.http_only().unwrap_or(false));
+    let csrf = find(&tenant_app.cookie_settings.csrf_name);
+    assert!(!csrf.http_only().unwrap_or(false));
+    (session, csrf)
+}
+
+#[actix_web::test]
+async fn cookie_sessions_require_the_csrf_header_on_writes() {
+    let tenant_app = tenant_app();
+    let app = init(&tenant_app).await;
+    register(&app).await;
+
+    let request = test::TestRequest::post()
+        .uri("/auth/login?mode=cookie")
+        .set_json(json!({"email": EMAIL, "password": PASSWORD}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let (session, csrf) = session_cookies(&tenant_app, &response);
+    let body: Value = test::read_body_json(response).await;
+    assert!(body.get("token").is_none());
+    assert_eq!(body["csrf_token"], csrf.value());
+
+    // Las lecturas no necesitan la cabecera
+    let request = test::TestRequest::get()
+        .uri("/auth/sessions")
+        .cookie(session.clone())
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    for header in [None, Some("forged-token")] {
+        let mut request = test::TestRequest::post().uri("/auth/logout").cookie(session.clone());
+        if let Some(token) = header {
+            request = request.insert_header(("X-CSRF-Token", token));
+        }
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "CSRF header {:?} was accepted", header);
+    }
+
+    let request = test::TestRequest::post()
+        .uri("/auth/logout")
+        .cookie(session)
+        .insert_header(("X-CSRF-Token", csrf.value()))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let removed = response
+        .response()
+        .cookies()
+        .find(|c| c.name() == tenant_app.cookie_settings.session_name)
+        .unwrap();
+    assert_eq!(removed.value(), "");
+}
+
+#[actix_web::test]
+async fn bearer_requests_skip_the_csrf_check() {
+    let app = init(&tenant_app()).await;
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+
+    let request = test::TestRequest::post()
+        .uri("/auth/logout")
+        .insert_header(bearer(&token))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn expired_password_change_honours_the_session_mode() {
+    let users = Arc::new(InMemoryUserRepository::default());
+    let mut tenant_app = tenant_app_with_users(users.clone());
+    let mut policy = password::load_policy(&tenant_app.tenant);
+    policy.max_age = Some(Duration::days(90));
+    tenant_app.auth = web::Data::new(AuthService::new(
+        users.clone(),
+        tenant_app.sessions.clone().into_inner(),
+        session::load_settings(&tenant_app.tenant),
+        password::load_settings(&tenant_app.tenant),
+        policy,
+        tenant_app.tenant.jwt_secret.clone(),
+        None,
+    ));
+    let app = init(&tenant_app).await;
+    register(&app).await;
+
+    let user = tenant_app.users.find_by_email(EMAIL).await.unwrap().unwrap();
+    users.set_password_changed_at(user.id, Utc::now() - Duration::days(91));
+    let request = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({"email": EMAIL, "password": PASSWORD}))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+
+    let request = test::TestRequest::post()
+        .uri("/auth/password/expired?mode=cookie")
+        .set_json(json!({
+            "email": EMAIL,
+            "current_password": PASSWORD,
+            "new_password": "Velvet-Harbor-Quartz-58"
+        }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    session_cookies(&tenant_app, &response);
+    let body: Value = test::read_body_json(response).await;
+    assert!(body.get("token").is_none());
+    assert!(body["csrf_token"].is_string());
+}
+
+/// Una petición lenta registra actividad con la sesión que leyó antes de
+/// que otra la reemitiera: el token y los métodos nuevos se conservan.
+async fn assert_touch_keeps_concurrent_updates(store: &dyn SessionStore, user_id: i64) {
+    let now = Utc::now().timestamp();
+    let read = Session {
+        id: uuid::Uuid::new_v4().to_string(),
+        user_id,
+        email: EMAIL.to_string(),
+        name: "Ada".to_string(),
+        token: "old-token".to_string(),
+        created_at: now - 600,
+        expires_at: now + 3600,
+        last_seen_at: now - 600,
+        auth_time: now - 600,
+        amr: Vec::new(),
+        org_id: None,
+    };
+    store.create(&read, 3600).await.unwrap();
+
+    let mut reissued = read.clone();
+    reissued.token = "new-token".to_string();
+    reissued.auth_time = now;
+    reissued.amr = vec![AMR_OTP.to_string()];
+    reissued.last_seen_at = now;
+    assert!(store.update(&reissued, 3600).await.unwrap());
+
+    let mut stale = read.clone();
+    stale.last_seen_at = now - 60;
+    assert!(store.touch(&stale, 3600).await.unwrap());
+    let stored = store.get(&read.id).await.unwrap().unwrap();
+    assert_eq!(stored.token, "new-token");
+    assert_eq!(stored.amr, vec![AMR_OTP.to_string()]);
+    // Tampoco retrasa la actividad ya registrada
+    assert_eq!(stored.last_seen_at, now);
+
+    stale.last_seen_at = now + 30;
+    assert!(store.touch(&stale, 3600).await.unwrap());
+    let stored = store.get(&read.id).await.unwrap().unwrap();
+    assert_eq!(stored.last_seen_at, now + 30);
+    assert_eq!(stored.token, "new-token");
+
+    assert!(store.revoke(&read.id).await.unwrap());
+    assert!(!store.touch(&stale, 3600).await.unwrap());
+    assert!(store.list_for_user(user_id).await.unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn touch_only_records_activity() {
+    assert_touch_keeps_concurrent_updates(&InMemorySessionStore::default(), 1).await;
+
+    if let Some(pool) = test_database().await {
+        let user_id: i64 = sqlx::query_scalar(
+            "INSERT INTO users (email, password, name) VALUES ('ada@example.com', 'x', 'Ada') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_touch_keeps_concurrent_updates(&PgSessionStore::new(pool, "default".to_string()), user_id).await;
+    }
+    if let Some(redis) = test_redis().await {
+        assert_touch_keeps_concurrent_updates(&RedisSessionStore::new(redis), 1).await;
+    }
+}
+
+#[actix_web::test]
+async fn redis_revocations_keep_the_user_index_in_step() {
+    let Some(redis) = test_redis().await else { return };
+    let store = RedisSessionStore::new(redis);
+    let now = Utc::now().timestamp();
+    let session = |id: &str| Session {
+        id: id.to_string(),
+        user_id: 7,
+        email: EMAIL.to_string(),
+        name: "Ada".to_string(),
+        token: format!("token-{}", id),
+        created_at: now,
+        expires_at: now + 3600,
+        last_seen_at: now,
+        auth_time: now,
+        amr: vec![AMR_MAGIC_LINK.to_string()],
+        org_id: None,
+    };
+    for id in ["a", "b", "c"] {
+        store.create(&session(id), 3600).await.unwrap();
+    }
+
+    assert!(store.revoke("a").await.unwrap());
+    assert!(!store.revoke("a").await.unwrap());
+    let ids: Vec<String> = store.list_for_user(7).await.unwrap().into_iter().map(|s| s.id).collect();
+    assert_eq!(ids.len(), 2);
+    assert!(!ids.contains(&"a".to_string()));
+
+    assert_eq!(store.revoke_all_for_user(7).await.unwrap(), 2);
+    assert!(store.list_for_user(7).await.unwrap().is_empty());
+    assert_eq!(store.revoke_all_for_user(7).await.unwrap(), 0);
+}
@@ -0,0 +1,253 @@
+//! Repositorios de usuarios: errores de las restricciones únicas, borrado de
+//! la cuenta, eventos que emiten para los webhooks, política de contraseñas y
+//! perfil editable.
+
+use actix_web::{test, web};
+use jsonschema::JSONSchema;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::{
+    bearer, init, init_with, login, pg_tenant_app, register, tenant_app, tenant_app_with_users, test_database,
+    EMAIL, PASSWORD,
+};
+use crate::config::profile::ProfileSettings;
+use crate::models::profile::{normalize_phone, UpdateProfile};
+use crate::services::organizations;
+use crate::services::users::{InMemoryUserRepository, PgUserRepository, RepositoryError, UserRepository};
+use crate::services::webhooks::{self, UserEvent};
+
+#[actix_web::test]
+async fn unique_violations_are_reported_by_constraint() {
+    let Some(pool) = test_database().await else { return };
+    let users = PgUserRepository::new(pool.clone(), "default".to_string());
+
+    let ada = users
+        .create_with_identity("ada@example.com", "x", "Ada", "github", "42")
+        .await
+        .unwrap();
+
+    let again = users.create("Ada@Example.com", "x", "Ada").await;
+    assert!(matches!(again, Err(RepositoryError::EmailTaken)));
+
+    // La identidad ya es de Ada: no es un correo repetido
+    let taken = users
+        .create_with_identity("grace@example.com", "x", "Grace", "github", "42")
+        .await;
+    assert!(matches!(taken, Err(RepositoryError::IdentityTaken)));
+    assert!(users.find_by_email("grace@example.com").await.unwrap().is_none());
+
+    let grace = users.create("grace@example.com", "x", "Grace").await.unwrap();
+    let linked = users.link_identity(grace.id, "github", "42", "grace@example.com").await;
+    assert!(matches!(linked, Err(RepositoryError::IdentityTaken)));
+    assert_eq!(users.find_by_identity("github", "42").await.unwrap().unwrap().id, ada.id);
+
+    // Otros tenants tienen su propio espacio de correos e identidades
+    let other = PgUserRepository::new(pool.clone(), "globex".to_string());
+    other
+        .create_with_identity("ada@example.com", "x", "Ada", "github", "42")
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn in_memory_repository_records_the_same_events() {
+    let users = Arc::new(InMemoryUserRepository::default());
+    let tenant_app = tenant_app_with_users(users.clone());
+    let app = init(&tenant_app).await;
+
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+    let request = test::TestRequest::post()
+        .uri("/auth/password")
+        .insert_header(bearer(&token))
+        .set_json(json!({"current_password": PASSWORD, "new_password": "Velvet-Harbor-Quartz-58"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 200);
+    let request = test::TestRequest::delete()
+        .uri("/profile")
+        .insert_header(bearer(&token))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 204);
+
+    let events = users.events();
+    let user_id = match &events[0] {
+        UserEvent::Registered { user_id, .. } => *user_id,
+        other => panic!("unexpected first event {:?}", other),
+    };
+    assert_eq!(
+        events[1..],
+        [
+            UserEvent::Login { user_id, amr: vec!["password".to_string()] },
+            UserEvent::PasswordChanged { user_id },
+            UserEvent::Deleted { user_id, email: EMAIL.to_string() },
+        ]
+    );
+    assert_eq!(events[3].data(), json!({"user_id": user_id, "email": EMAIL}));
+    assert_eq!(events[1].data()["method"], "password");
+}
+
+#[actix_web::test]
+async fn deleting_the_account_enqueues_user_deleted() {
+    let Some(pool) = test_database().await else { return };
+    let tenant_app = pg_tenant_app(&pool);
+    let app = init_with(&tenant_app, pool.clone(), super::blob_store()).await;
+    sqlx::query("INSERT INTO webhook_endpoints (url, secret, events) VALUES ($1, 's', $2)")
+        .bind("http://127.0.0.1:9/hook")
+        .bind(webhooks::SUPPORTED_EVENTS)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+    let user_id = tenant_app.users.find_by_email(EMAIL).await.unwrap().unwrap().id;
+
+    // Único owner de una organización con más miembros: hay que traspasarla antes
+    let org = organizations::create(&pool, "default", user_id, "Analytical Engines").await.unwrap();
+    let grace = tenant_app.users.create("grace@example.com", "x", "Grace").await.unwrap();
+    sqlx::query("INSERT INTO memberships (org_id, user_id, role) VALUES ($1, $2, 'member')")
+        .bind(org.id)
+        .bind(grace.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let delete = || {
+        test::TestRequest::delete()
+            .uri("/profile")
+            .insert_header(bearer(&token))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, delete()).await.status(), 409);
+
+    sqlx::query("UPDATE memberships SET role = 'owner' WHERE user_id = $1")
+        .bind(grace.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(test::call_service(&app, delete()).await.status(), 204);
+    assert!(tenant_app.users.find_by_id(user_id).await.unwrap().is_none());
+    assert_eq!(test::call_service(&app, delete()).await.status(), 401);
+
+    let payloads: Vec<Value> = sqlx::query_scalar(
+        "SELECT payload FROM webhook_outbox WHERE event_type = $1",
+    )
+    .bind(webhooks::USER_DELETED)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(payloads.len(), 1);
+    assert_eq!(payloads[0]["type"], webhooks::USER_DELETED);
+    assert_eq!(payloads[0]["data"], json!({"user_id": user_id, "email": EMAIL}));
+
+    let registered: Value = sqlx::query_scalar(
+        "SELECT payload FROM webhook_outbox WHERE event_type = $1 ORDER BY created_at LIMIT 1",
+    )
+    .bind(webhooks::USER_REGISTERED)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(registered["data"], json!({"user_id": user_id, "email": EMAIL, "name": "Ada Lovelace"}));
+}
+
+#[actix_web::test]
+async fn a_verified_phone_belongs_to_one_account_whatever_its_format() {
+    let Some(pool) = test_database().await else { return };
+    let users = PgUserRepository::new(pool.clone(), "default".to_string());
+    let ada = users.create("ada@example.com", "x", "Ada").await.unwrap();
+    let grace = users.create("grace@example.com", "x", "Grace").await.unwrap();
+
+    let phone = normalize_phone("+34 600 12 34 56").unwrap();
+    let same = normalize_phone("0034-600-123-456").unwrap();
+    for (user, phone) in [(&ada, &phone), (&grace, &same)] {
+        let update: UpdateProfile = serde_json::from_value(json!({"phone_number": phone})).unwrap();
+        users.update_profile(user.id, &update).await.unwrap().unwrap();
+    }
+
+    assert!(users.mark_phone_verified(ada.id, &phone).await.unwrap());
+    let taken = users.mark_phone_verified(grace.id, &same).await;
+    assert!(matches!(taken, Err(RepositoryError::PhoneTaken)));
+}
+
+#[actix_web::test]
+async fn register_and_password_changes_apply_the_password_policy() {
+    let tenant_app = tenant_app();
+    let app = init(&tenant_app).await;
+    let code = |body: &Value, field: &str| body["errors"][field][0]["code"].as_str().unwrap().to_string();
+
+    let request = test::TestRequest::post()
+        .uri("/auth/register")
+        .set_json(json!({"email": EMAIL, "password": "Lovelace-Orbit-71", "name": "Ada Lovelace"}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(code(&body, "password"), "password_contains_name");
+    assert_eq!(body["errors"]["password"][0]["message"], "Password must not contain your name");
+
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+    let change = |new_password: &str| {
+        test::TestRequest::post()
+            .uri("/auth/password")
+            .insert_header(bearer(&token))
+            .set_json(json!({"current_password": PASSWORD, "new_password": new_password}))
+            .to_request()
+    };
+
+    let response = test::call_service(&app, change("password1")).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(code(&body, "new_password"), "password_too_weak");
+
+    assert_eq!(test::call_service(&app, change("Velvet-Harbor-Quartz-58")).await.status(), 200);
+}
+
+#[actix_web::test]
+async fn profile_attributes_are_patched_and_metadata_follows_the_schema() {
+    let mut tenant_app = tenant_app();
+    let schema = json!({"type": "object", "properties": {"theme": {"enum": ["light", "dark"]}}});
+    tenant_app.profile = web::Data::new(ProfileSettings {
+        metadata_schema: Arc::new(JSONSchema::compile(&schema).unwrap()),
+        ..tenant_app.profile.get_ref().clone()
+    });
+    let app = init(&tenant_app).await;
+    register(&app).await;
+    let token = login(&app).await["token"].clone();
+    let patch = |body: Value| {
+        test::TestRequest::patch()
+            .uri("/profile")
+            .insert_header(bearer(&token))
+            .set_json(body)
+            .to_request()
+    };
+
+    let request = patch(json!({
+        "locale": "es-ES",
+        "timezone": "Europe/Madrid",
+        "metadata": {"theme": "dark", "beta": true}
+    }));
+    assert_eq!(test::call_service(&app, request).await.status(), 200);
+    // `metadata` se fusiona: `null` borra la clave y el resto se conserva
+    let profile: Value = test::call_and_read_body_json(&app, patch(json!({"metadata": {"beta": null}}))).await;
+    assert_eq!(profile["metadata"], json!({"theme": "dark"}));
+    assert_eq!(profile["locale"], "es-ES");
+    assert!(profile["updated_at"].is_string());
+
+    let response = test::call_service(&app, patch(json!({"metadata": {"theme": "sepia"}}))).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["error"], "Invalid metadata");
+
+    let response = test::call_service(&app, patch(json!({"locale": "not a locale", "timezone": "../passwd"}))).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["errors"]["locale"][0]["code"], "locale");
+    assert_eq!(body["errors"]["timezone"][0]["code"], "timezone");
+
+    // Lo rechazado no se guardó
+    let request = test::TestRequest::get().uri("/profile").insert_header(bearer(&token)).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(profile["metadata"], json!({"theme": "dark"}));
+    assert_eq!(profile["timezone"], "Europe/Madrid");
+}